@@ -0,0 +1,84 @@
+// Copyright 2017-2019 Leonardo Schwarz <mail@leoschwarz.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Compares the evaluation of simple paths by the direct tree walk with
+//! the general XPath engine.
+//!
+//! An expression created from an `XPath` has no source, so it is always
+//! evaluated by the engine, while the same expression given as string or
+//! parsed with `expression::parse` takes the fast path. Only evaluation
+//! is measured, as converting the result nodeset into values costs the
+//! same either way.
+
+extern crate sxd_xpath;
+extern crate xpath_reader;
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use sxd_xpath::Factory;
+use xpath_reader::{expression, Reader};
+
+/// The time spent measuring each variant.
+const MEASUREMENT_TIME: Duration = Duration::from_secs(2);
+
+fn document() -> String {
+    let mut xml = String::from("<catalog>");
+    for i in 0..1000 {
+        xml.push_str(&format!(
+            "<entry id=\"{0}\"><title>Entry {0}</title><tags>\
+             <tag name=\"a{0}\"/><tag name=\"b{0}\"/><tag name=\"c{0}\"/></tags></entry>",
+            i
+        ));
+    }
+    xml.push_str("</catalog>");
+    xml
+}
+
+/// Runs `f` repeatedly for `MEASUREMENT_TIME` and prints the mean time of
+/// one run.
+fn bench<F: FnMut()>(name: &str, mut f: F) {
+    f();
+    let start = Instant::now();
+    let mut runs = 0u32;
+    while start.elapsed() < MEASUREMENT_TIME {
+        f();
+        runs += 1;
+    }
+    println!("  {:<20} {:>10.2?}", name, start.elapsed() / runs);
+}
+
+fn compare(anchor: &str, expr: &str) {
+    let xml = document();
+    let reader = Reader::from_str(&xml, None).unwrap();
+    let anchor = reader.with_nodeset_eval(anchor).unwrap();
+    let xpath = Factory::new().build(expr).unwrap().unwrap();
+    let parsed = expression::parse(expr).unwrap();
+
+    println!("{}", expr);
+    bench("engine", || {
+        black_box(anchor.with_nodeset_eval(&xpath).unwrap());
+    });
+    bench("fast path, parsed", || {
+        black_box(anchor.with_nodeset_eval(&parsed).unwrap());
+    });
+    bench("fast path, string", || {
+        black_box(anchor.with_nodeset_eval(expr).unwrap());
+    });
+}
+
+fn main() {
+    compare("/catalog/entry[500]", "./title");
+    compare("/catalog/entry[500]", "tags/tag/@name");
+    compare("/catalog/entry[500]", "/catalog/entry/@id");
+}
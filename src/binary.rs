@@ -0,0 +1,218 @@
+// Copyright 2017-2019 Leonardo Schwarz <mail@leoschwarz.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Binary content decoding.
+//!
+//! Provides wrapper types to read `xs:base64Binary` and `xs:hexBinary`
+//! values directly into bytes.
+//!
+//! # Examples
+//! ```
+//! use xpath_reader::Reader;
+//! use xpath_reader::binary::{Base64, Hex};
+//!
+//! let xml = r#"<?xml version="1.0"?><root><b64>aGVs
+//!     bG8=</b64><hex>68 65 6C 6C 6F</hex></root>"#;
+//! let reader = Reader::from_str(xml, None).unwrap();
+//!
+//! let b64: Base64 = reader.read("//b64").unwrap();
+//! let hex: Hex = reader.read("//hex").unwrap();
+//! assert_eq!(b64.0, b"hello".to_vec());
+//! assert_eq!(hex.into_inner(), b"hello".to_vec());
+//! ```
+
+use errors::Error;
+use reader::{FromXmlOptional, FromXmlResult, Reader};
+
+/// Bytes read from an `xs:base64Binary` value.
+///
+/// Whitespace (including line breaks) anywhere in the value is ignored.
+/// An empty value decodes to zero bytes.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Base64<T = Vec<u8>>(pub T);
+
+/// Bytes read from an `xs:hexBinary` value.
+///
+/// Both upper and lower case digits are accepted and whitespace anywhere
+/// in the value is ignored. An empty value decodes to zero bytes.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Hex<T = Vec<u8>>(pub T);
+
+impl<T> Base64<T> {
+    /// Returns the wrapped value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Hex<T> {
+    /// Returns the wrapped value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl FromXmlOptional for Base64<Vec<u8>> {
+    fn from_xml_optional<'d>(reader: &'d Reader<'d>) -> FromXmlResult<Option<Self>> {
+        match reader.preferred_node() {
+            Some(node) => decode_base64(&node.string_value()).map(|bytes| Some(Base64(bytes))),
+            None => Ok(None),
+        }
+    }
+}
+
+impl FromXmlOptional for Hex<Vec<u8>> {
+    fn from_xml_optional<'d>(reader: &'d Reader<'d>) -> FromXmlResult<Option<Self>> {
+        match reader.preferred_node() {
+            Some(node) => decode_hex(&node.string_value()).map(|bytes| Some(Hex(bytes))),
+            None => Ok(None),
+        }
+    }
+}
+
+fn is_xml_whitespace(b: u8) -> bool {
+    b == b' ' || b == b'\t' || b == b'\n' || b == b'\r'
+}
+
+fn base64_value(b: u8) -> Option<u8> {
+    match b {
+        b'A'..=b'Z' => Some(b - b'A'),
+        b'a'..=b'z' => Some(b - b'a' + 26),
+        b'0'..=b'9' => Some(b - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+fn decode_base64(s: &str) -> Result<Vec<u8>, Error> {
+    let chars: Vec<u8> = s.bytes().filter(|b| !is_xml_whitespace(*b)).collect();
+    if chars.len() % 4 != 0 {
+        return Err(Error::custom_msg(format!(
+            "Invalid base64 length {} (not a multiple of 4).",
+            chars.len()
+        )));
+    }
+
+    let padding = chars.iter().rev().take_while(|b| **b == b'=').count();
+    if padding > 2 {
+        return Err(Error::custom_msg("Invalid base64 padding."));
+    }
+
+    let mut out = Vec::with_capacity(chars.len() / 4 * 3);
+    let data_len = chars.len() - padding;
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for (i, &b) in chars[..data_len].iter().enumerate() {
+        let value = base64_value(b).ok_or_else(|| {
+            Error::custom_msg(format!(
+                "Invalid base64 character {:?} at position {}.",
+                b as char, i
+            ))
+        })?;
+        acc = (acc << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+
+    // Leftover bits must be zero for a canonical encoding.
+    if acc != 0 {
+        return Err(Error::custom_msg("Invalid base64 trailing bits."));
+    }
+    Ok(out)
+}
+
+fn hex_value(b: u8) -> Option<u8> {
+    match b {
+        b'0'..=b'9' => Some(b - b'0'),
+        b'a'..=b'f' => Some(b - b'a' + 10),
+        b'A'..=b'F' => Some(b - b'A' + 10),
+        _ => None,
+    }
+}
+
+fn decode_hex(s: &str) -> Result<Vec<u8>, Error> {
+    let chars: Vec<u8> = s.bytes().filter(|b| !is_xml_whitespace(*b)).collect();
+    if chars.len() % 2 != 0 {
+        return Err(Error::custom_msg(
+            "Invalid hex length (odd number of digits).",
+        ));
+    }
+
+    chars
+        .chunks(2)
+        .map(|pair| match (hex_value(pair[0]), hex_value(pair[1])) {
+            (Some(hi), Some(lo)) => Ok((hi << 4) | lo),
+            _ => Err(Error::custom_msg(format!(
+                "Invalid hex digits {:?}.",
+                String::from_utf8_lossy(pair)
+            ))),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_decoding() {
+        assert_eq!(decode_base64("").unwrap(), b"".to_vec());
+        assert_eq!(decode_base64("Zg==").unwrap(), b"f".to_vec());
+        assert_eq!(decode_base64("Zm8=").unwrap(), b"fo".to_vec());
+        assert_eq!(decode_base64("Zm9v").unwrap(), b"foo".to_vec());
+        assert_eq!(
+            decode_base64("Zm9v\n YmFy\r\n").unwrap(),
+            b"foobar".to_vec()
+        );
+        assert!(decode_base64("Zm9").is_err());
+        assert!(decode_base64("Zm9=").is_err());
+        assert!(decode_base64("Zm!v").is_err());
+    }
+
+    #[test]
+    fn hex_decoding() {
+        assert_eq!(decode_hex("0fA0").unwrap(), vec![0x0f, 0xa0]);
+        assert_eq!(decode_hex(" 0f\n a0 ").unwrap(), vec![0x0f, 0xa0]);
+        assert!(decode_hex("0fa").is_err());
+        assert!(decode_hex("0g").is_err());
+    }
+
+    #[test]
+    fn binary_from_xml() {
+        let xml = r#"<?xml version="1.0"?>
+                     <root><b64>
+                        AAEC
+                        /w==
+                     </b64><hex>00ff</hex><empty/></root>"#;
+        let reader = Reader::from_str(xml, None).unwrap();
+
+        let b64: Base64 = reader.read("//b64").unwrap();
+        assert_eq!(b64, Base64(vec![0, 1, 2, 255]));
+        let hex: Hex = reader.read("//hex").unwrap();
+        assert_eq!(hex, Hex(vec![0, 255]));
+
+        let empty: Option<Hex> = reader.read("//empty").unwrap();
+        assert_eq!(empty, Some(Hex(Vec::new())));
+        let empty: Base64 = reader.read("//empty").unwrap();
+        assert_eq!(empty, Base64(Vec::new()));
+        let missing: Option<Base64> = reader.read("//missing").unwrap();
+        assert_eq!(missing, None);
+        assert!(reader.read::<Base64, _>("//missing").is_err());
+    }
+}
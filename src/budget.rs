@@ -0,0 +1,469 @@
+// Copyright 2017-2019 Leonardo Schwarz <mail@leoschwarz.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Resource budgets for evaluating untrusted XPath expressions.
+//!
+//! A budget set with `Reader::set_budget` applies to every evaluation of
+//! the reader and the readers created from it, exceeding it fails with
+//! `ErrorKind::EvalXPath`.
+//!
+//! The evaluation itself cannot be interrupted, so the number of visited
+//! nodes is limited before evaluating, by a worst-case estimate computed
+//! from the parsed expression: a location path visits each node of the
+//! document once, multiplied by the number of nodes for each further step
+//! along the descendant, ancestor, following or preceding axes. Predicates
+//! are evaluated once for every node selected before them, so each level
+//! of predicates evaluating an absolute path or one of these axes
+//! multiplies the estimate by the number of nodes again. For
+//! `//item[@id = //ref/@id]` and `//a/following::b` this is the square of
+//! the document size.
+//!
+//! The result size is checked once an evaluation has finished, discarding
+//! its result. There is no limit on the wall-clock time, as an evaluation
+//! cannot be stopped once it has started; the node limit bounds the time
+//! spent instead.
+//!
+//! # Examples
+//! ```
+//! use xpath_reader::{ErrorKind, Reader};
+//! use xpath_reader::budget::EvalBudget;
+//!
+//! let xml = "<list><a/><a/><a/><a/></list>";
+//! let mut reader = Reader::from_str(xml, None).unwrap();
+//! reader.set_budget(Some(EvalBudget::new().max_visited_nodes(20).max_results(3)));
+//!
+//! let first: Vec<String> = reader.read("//a[position() < 3]").unwrap();
+//! assert_eq!(first.len(), 2);
+//!
+//! let error = reader.read::<Vec<String>, _>("//a").unwrap_err();
+//! assert_eq!(error.kind(), ErrorKind::EvalXPath);
+//! let error = reader.read::<bool, _>("boolean(//*[count(//*) > 0])").unwrap_err();
+//! assert_eq!(error.kind(), ErrorKind::EvalXPath);
+//! ```
+
+use errors::{Error, ErrorKind};
+use expression::XPathExpression;
+use std::cell::Cell;
+use sxd_xpath::nodeset::Node;
+use sxd_xpath::{Value, XPath};
+
+/// The axes which can visit large parts of the document from any node.
+const EXPENSIVE_AXES: [&str; 8] = [
+    "Ancestor",
+    "AncestorOrSelf",
+    "Descendant",
+    "DescendantOrSelf",
+    "Following",
+    "FollowingSibling",
+    "Preceding",
+    "PrecedingSibling",
+];
+
+/// The axes which only visit the node itself or its direct neighbours.
+const CHEAP_AXES: [&str; 5] = ["Attribute", "Child", "Namespace", "Parent", "SelfAxis"];
+
+/// Limits for a single evaluation of an XPath expression.
+#[derive(Clone, Debug, Default)]
+pub struct EvalBudget {
+    max_visited_nodes: Option<usize>,
+    max_results: Option<usize>,
+}
+
+impl EvalBudget {
+    /// Creates a budget which does not limit anything.
+    pub fn new() -> Self {
+        EvalBudget::default()
+    }
+
+    /// Sets the maximum estimated number of nodes visited by an
+    /// evaluation.
+    pub fn max_visited_nodes(mut self, nodes: usize) -> Self {
+        self.max_visited_nodes = Some(nodes);
+        self
+    }
+
+    /// Sets the maximum number of nodes in the result of an evaluation.
+    pub fn max_results(mut self, nodes: usize) -> Self {
+        self.max_results = Some(nodes);
+        self
+    }
+}
+
+/// A budget shared by the readers of one document.
+#[derive(Debug)]
+pub(crate) struct Budget {
+    limits: EvalBudget,
+    document_nodes: Cell<Option<usize>>,
+}
+
+impl Budget {
+    pub(crate) fn new(limits: EvalBudget) -> Self {
+        Budget {
+            limits,
+            document_nodes: Cell::new(None),
+        }
+    }
+
+    /// Checks the estimated cost of evaluating `expr` relative to `anchor`,
+    /// with `xpath` its parsed form or `None` for simple paths.
+    pub(crate) fn start(
+        &self,
+        expr: &XPathExpression,
+        xpath: Option<&XPath>,
+        anchor: Node,
+    ) -> Result<(), Error> {
+        if let Some(max) = self.limits.max_visited_nodes {
+            let nodes = match self.document_nodes.get() {
+                Some(nodes) => nodes,
+                None => {
+                    let nodes = count_nodes(Node::Root(anchor.document().root()));
+                    self.document_nodes.set(Some(nodes));
+                    nodes
+                }
+            };
+            let visits = match xpath {
+                Some(xpath) => estimated_visits(xpath, nodes).ok_or_else(|| {
+                    exceeded(format!(
+                        "The cost of evaluating '{}' cannot be estimated.",
+                        expr
+                    ))
+                })?,
+                None => nodes,
+            };
+            if visits > max {
+                return Err(exceeded(format!(
+                    "Evaluating '{}' may visit {} nodes, the limit is {}.",
+                    expr, visits, max
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the size of the result of an evaluation.
+    pub(crate) fn finish(&self, value: &Value) -> Result<(), Error> {
+        if let (Some(max), Value::Nodeset(nodeset)) = (self.limits.max_results, value) {
+            if nodeset.size() > max {
+                return Err(exceeded(format!(
+                    "The result of {} nodes exceeds the limit of {}.",
+                    nodeset.size(),
+                    max
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn exceeded(msg: String) -> Error {
+    Error::internal(msg, ErrorKind::EvalXPath)
+}
+
+fn count_nodes(node: Node) -> usize {
+    let own = match node {
+        Node::Element(e) => 1 + e.attributes().len() + e.namespaces_in_scope().len(),
+        _ => 1,
+    };
+    own + node.children().into_iter().map(count_nodes).sum::<usize>()
+}
+
+/// Estimates the number of nodes visited by `xpath` in a document of
+/// `nodes` nodes, as described in the module documentation.
+///
+/// The expression tree is read from the `Debug` representation of `xpath`,
+/// the only view sxd_xpath provides of it. `None` is returned if it does
+/// not have the expected structure.
+fn estimated_visits(xpath: &XPath, nodes: usize) -> Option<usize> {
+    let debug = format!("{:?}", xpath);
+    let mut parser = DebugParser { rest: &debug };
+    let tree = parser.value()?;
+    if !parser.rest.is_empty() {
+        return None;
+    }
+    let exponent = u32::max(exponent(&tree, 0)?, 1);
+    Some((0..exponent).fold(1, |visits: usize, _| visits.saturating_mul(nodes)))
+}
+
+/// A value in a `Debug` representation.
+#[derive(Debug, PartialEq)]
+enum Tree {
+    /// `Name { field: value, .. }`, `Name(value, ..)` with the fields named
+    /// by their index, or a plain `Name`.
+    Struct(String, Vec<(String, Tree)>),
+    /// `[value, ..]`
+    List(Vec<Tree>),
+    /// A string or number.
+    Literal,
+}
+
+impl Tree {
+    fn name(&self) -> Option<&str> {
+        match *self {
+            Tree::Struct(ref name, _) => Some(name),
+            _ => None,
+        }
+    }
+
+    fn field(&self, name: &str) -> Option<&Tree> {
+        match *self {
+            Tree::Struct(_, ref fields) => fields.iter().find(|f| f.0 == name).map(|f| &f.1),
+            _ => None,
+        }
+    }
+
+    fn list(&self) -> Option<&[Tree]> {
+        match *self {
+            Tree::List(ref items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+struct DebugParser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> DebugParser<'a> {
+    fn value(&mut self) -> Option<Tree> {
+        if self.eat('[') {
+            let mut items = Vec::new();
+            while !self.eat(']') {
+                items.push(self.value()?);
+                self.separator(']')?;
+            }
+            Some(Tree::List(items))
+        } else if self.eat('"') {
+            let mut escaped = false;
+            let end = self.rest.find(|c| {
+                let end = c == '"' && !escaped;
+                escaped = c == '\\' && !escaped;
+                end
+            })?;
+            self.rest = &self.rest[end + 1..];
+            Some(Tree::Literal)
+        } else if self
+            .rest
+            .starts_with(|c: char| c.is_ascii_digit() || c == '-')
+        {
+            self.take_while(|c| c.is_ascii_alphanumeric() || "+-.".contains(c));
+            Some(Tree::Literal)
+        } else {
+            let name = self.take_while(|c| c.is_alphanumeric() || c == '_');
+            if name.is_empty() {
+                return None;
+            }
+            let mut fields = Vec::new();
+            if self.eat('{') {
+                while !self.eat('}') {
+                    self.skip_whitespace();
+                    let field = self.take_while(|c| c.is_alphanumeric() || c == '_');
+                    if field.is_empty() || !self.eat(':') {
+                        return None;
+                    }
+                    fields.push((field.to_string(), self.value()?));
+                    self.separator('}')?;
+                }
+            } else if self.eat('(') {
+                while !self.eat(')') {
+                    fields.push((fields.len().to_string(), self.value()?));
+                    self.separator(')')?;
+                }
+            }
+            Some(Tree::Struct(name.to_string(), fields))
+        }
+    }
+
+    /// Consumes a `,` or checks that `close` follows.
+    fn separator(&mut self, close: char) -> Option<()> {
+        if self.eat(',') || self.rest.starts_with(close) {
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    /// Consumes `c` after optional whitespace.
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> &'a str {
+        let end = self.rest.find(|c| !f(c)).unwrap_or(self.rest.len());
+        let taken = &self.rest[..end];
+        self.rest = &self.rest[end..];
+        taken
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+}
+
+/// Returns `e` for an estimate of `nodes^e` visits by the expression
+/// `tree`, evaluated once for every node selected by an expression with
+/// estimate `nodes^base`.
+fn exponent(tree: &Tree, base: u32) -> Option<u32> {
+    match tree.name() {
+        Some("Path") => {
+            let start = tree.field("start_point")?;
+            let start_exponent = exponent(start, base)?;
+            // An absolute path visits the document once, even without
+            // expensive steps.
+            let absolute = u32::from(start.name() == Some("RootNode"));
+            let mut chain = start_exponent - base;
+            let mut max = u32::max(start_exponent, base + absolute);
+            for step in tree.field("steps")?.list()? {
+                let axis = step.field("axis")?.name()?;
+                if EXPENSIVE_AXES.contains(&axis) {
+                    chain += 1;
+                } else if !CHEAP_AXES.contains(&axis) {
+                    return None;
+                }
+                max = u32::max(max, base + u32::max(chain, absolute));
+                let predicate_base = base + u32::max(chain, 1);
+                for predicate in step.field("predicates")?.list()? {
+                    max = u32::max(max, exponent(predicate, predicate_base)?);
+                }
+            }
+            Some(max)
+        }
+        Some("Filter") => {
+            let selected = exponent(tree.field("node_selector")?, base)?;
+            let predicate = exponent(tree.field("predicate")?, u32::max(selected, base + 1))?;
+            Some(u32::max(selected, predicate))
+        }
+        Some("ParameterizedStep") => None,
+        _ => {
+            let children: Vec<&Tree> = match *tree {
+                Tree::Struct(_, ref fields) => fields.iter().map(|f| &f.1).collect(),
+                Tree::List(ref items) => items.iter().collect(),
+                Tree::Literal => Vec::new(),
+            };
+            children.into_iter().try_fold(base, |max, child| {
+                Some(u32::max(max, exponent(child, base)?))
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reader::Reader;
+    use sxd_xpath::Factory;
+
+    #[test]
+    fn budget_applies_to_derived_readers() {
+        let xml = "<list><item><a/><a/></item></list>";
+        let mut reader = Reader::from_str(xml, None).unwrap();
+        reader.set_budget(Some(EvalBudget::new().max_results(1)));
+        let item = reader.with_nodeset_eval("//item").unwrap();
+        assert!(item.read::<Vec<String>, _>("a").is_err());
+        assert!(item.read::<String, _>(".").is_ok());
+
+        reader.set_budget(None);
+        assert!(reader.read::<Vec<String>, _>("//a").is_ok());
+    }
+
+    #[test]
+    fn node_limit_on_parsed_expressions() {
+        let mut reader = Reader::from_str("<a><b/><b/></a>", None).unwrap();
+        reader.set_budget(Some(EvalBudget::new().max_visited_nodes(20)));
+        let xpath = Factory::new().build("/a").unwrap().unwrap();
+        assert!(reader.read::<String, _>(&xpath).is_ok());
+        assert!(reader.read::<String, _>(xpath).is_ok());
+
+        let xpath = Factory::new().build("//b/following::*").unwrap().unwrap();
+        let error = reader.read::<Vec<String>, _>(&xpath).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::EvalXPath);
+    }
+
+    fn estimate(expr: &str, nodes: usize) -> usize {
+        let xpath = Factory::new().build(expr).unwrap().unwrap();
+        estimated_visits(&xpath, nodes).unwrap()
+    }
+
+    #[test]
+    fn estimates() {
+        assert_eq!(estimate("/a/b", 10), 10);
+        assert_eq!(estimate("a/b/..", 10), 10);
+        assert_eq!(estimate("//a[b/c][1]", 10), 10);
+        assert_eq!(estimate("count(//a[@id = 'x//y\"'])", 10), 10);
+        assert_eq!(estimate("count(//a | //b) > -1", 10), 10);
+        assert_eq!(estimate("//a[@ref = //b/@id]", 10), 100);
+        assert_eq!(estimate("a[following::b][1]", 10), 100);
+        assert_eq!(estimate("a[. = /x]", 10), 100);
+        assert_eq!(estimate("a[b div 2 = 1]", 10), 10);
+        assert_eq!(estimate("//a[b[//c]]", 10), 1000);
+        assert_eq!(estimate("//a[//b[//c]]", 1 << 40), usize::MAX);
+    }
+
+    #[test]
+    fn chained_axes() {
+        assert_eq!(estimate("//a/following::b", 10), 100);
+        assert_eq!(estimate("/descendant::a/b/ancestor::c", 10), 100);
+        assert_eq!(estimate("//*/following::*/following::*", 10), 1000);
+        assert_eq!(estimate("(//a)/preceding::b", 10), 100);
+        assert_eq!(estimate("(//a)[following::b]", 10), 100);
+        assert_eq!(estimate("//a/following::b[//c]", 10), 1000);
+    }
+
+    #[test]
+    fn debug_trees() {
+        let mut parser = DebugParser {
+            rest: r#"A { b: [C(1.5, "\"]"), D], e: -2 }"#,
+        };
+        assert_eq!(
+            parser.value(),
+            Some(Tree::Struct(
+                "A".to_string(),
+                vec![
+                    (
+                        "b".to_string(),
+                        Tree::List(vec![
+                            Tree::Struct(
+                                "C".to_string(),
+                                vec![
+                                    ("0".to_string(), Tree::Literal),
+                                    ("1".to_string(), Tree::Literal),
+                                ]
+                            ),
+                            Tree::Struct("D".to_string(), vec![]),
+                        ])
+                    ),
+                    ("e".to_string(), Tree::Literal),
+                ]
+            ))
+        );
+        assert_eq!(parser.rest, "");
+
+        for debug in &["A {", "A { b }", "[A B]", "\"x", "{}"] {
+            assert!(DebugParser { rest: debug }.value().is_none(), "{}", debug);
+        }
+        let tree = DebugParser {
+            rest: "Path { start_point: RootNode, steps: [ParameterizedStep { axis: Sideways, \
+                   node_test: Node, predicates: [] }] }",
+        }
+        .value()
+        .unwrap();
+        assert_eq!(exponent(&tree, 0), None);
+    }
+}
@@ -0,0 +1,448 @@
+// Copyright 2017-2019 Leonardo Schwarz <mail@leoschwarz.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Canonical XML serialization.
+//!
+//! Implements [Canonical XML 1.0](https://www.w3.org/TR/xml-c14n) and
+//! [Exclusive XML Canonicalization 1.0](https://www.w3.org/TR/xml-exc-c14n/)
+//! for whole documents and for the subtree of an element, as needed for
+//! hashing and signing documents.
+//!
+//! Since the document has already been parsed, entity references, CDATA
+//! sections and default attributes from DTDs are not visible to the
+//! canonicalization and are treated as the parser resolved them.
+//!
+//! # Examples
+//! ```
+//! use xpath_reader::Reader;
+//! use xpath_reader::c14n::{Algorithm, Canonicalizer};
+//!
+//! let xml = r#"<doc xmlns:a="urn:a"><a:e   b="2" a="1"/></doc>"#;
+//! let reader = Reader::from_str(xml, None).unwrap();
+//! let element = reader.with_nodeset_eval("//*[local-name() = 'e']").unwrap();
+//!
+//! let inclusive = Canonicalizer::new(Algorithm::Inclusive);
+//! assert_eq!(
+//!     inclusive.canonicalize(&element).unwrap(),
+//!     r#"<a:e xmlns:a="urn:a" a="1" b="2"></a:e>"#
+//! );
+//! ```
+
+use errors::Error;
+use node::{self, XML_NAMESPACE};
+use reader::Reader;
+use std::collections::BTreeMap;
+use sxd_document::dom::Element;
+use sxd_xpath::nodeset::Node;
+
+/// The canonicalization algorithm.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Algorithm {
+    /// Canonical XML 1.0, which renders all namespaces in scope and
+    /// inherits `xml:*` attributes into the apex of a subtree.
+    Inclusive,
+    /// Exclusive XML Canonicalization 1.0, which only renders the
+    /// namespaces visibly utilized by an element.
+    Exclusive,
+}
+
+/// Canonicalization settings.
+#[derive(Clone, Debug)]
+pub struct Canonicalizer {
+    algorithm: Algorithm,
+    with_comments: bool,
+    inclusive_prefixes: Vec<String>,
+}
+
+impl Canonicalizer {
+    /// Creates a canonicalizer for `algorithm`, omitting comments.
+    pub fn new(algorithm: Algorithm) -> Self {
+        Canonicalizer {
+            algorithm,
+            with_comments: false,
+            inclusive_prefixes: Vec::new(),
+        }
+    }
+
+    /// Sets whether comments are kept (the `#WithComments` variants).
+    pub fn with_comments(mut self, with_comments: bool) -> Self {
+        self.with_comments = with_comments;
+        self
+    }
+
+    /// Sets the `InclusiveNamespaces PrefixList` of exclusive
+    /// canonicalization, i.e. prefixes which are rendered like in inclusive
+    /// canonicalization. The default namespace is written as `#default`.
+    pub fn inclusive_prefixes<I, S>(mut self, prefixes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.inclusive_prefixes = prefixes
+            .into_iter()
+            .map(Into::into)
+            .map(|p| if p == "#default" { String::new() } else { p })
+            .collect();
+        self
+    }
+
+    /// Canonicalizes the anchor node of `reader`, which has to be the
+    /// document root or an element.
+    pub fn canonicalize<'d>(&self, reader: &'d Reader<'d>) -> Result<String, Error> {
+        let node = reader
+            .anchor_node()
+            .ok_or_else(|| Error::custom_msg("No node to canonicalize."))?;
+        self.canonicalize_node(node)
+    }
+
+    pub(crate) fn canonicalize_node(&self, node: Node) -> Result<String, Error> {
+        let mut out = String::new();
+        match node {
+            Node::Root(root) => {
+                let mut after_element = false;
+                for child in root.children() {
+                    let child: Node = child.into();
+                    if let Node::Comment(_) = child {
+                        if !self.with_comments {
+                            continue;
+                        }
+                    }
+                    if after_element {
+                        out.push('\n');
+                    }
+                    match child {
+                        Node::Element(e) => {
+                            self.write_element(e, true, &BTreeMap::new(), &mut out);
+                            after_element = true;
+                        }
+                        _ => {
+                            self.write_child(child, &BTreeMap::new(), &mut out);
+                            if !after_element {
+                                out.push('\n');
+                            }
+                        }
+                    }
+                }
+            }
+            Node::Element(e) => self.write_element(e, true, &BTreeMap::new(), &mut out),
+            _ => {
+                return Err(Error::custom_msg(
+                    "Only documents and elements can be canonicalized.",
+                ))
+            }
+        }
+        Ok(out)
+    }
+
+    fn write_child(&self, child: Node, rendered: &BTreeMap<String, String>, out: &mut String) {
+        match child {
+            Node::Element(e) => self.write_element(e, false, rendered, out),
+            Node::Text(t) => escape_text(t.text(), out),
+            Node::Comment(c) if self.with_comments => {
+                out.push_str("<!--");
+                out.push_str(c.text());
+                out.push_str("-->");
+            }
+            Node::ProcessingInstruction(pi) => {
+                out.push_str("<?");
+                out.push_str(pi.target());
+                if let Some(value) = pi.value().filter(|v| !v.is_empty()) {
+                    out.push(' ');
+                    out.push_str(value);
+                }
+                out.push_str("?>");
+            }
+            _ => {}
+        }
+    }
+
+    /// Writes the element `e`, `rendered` being the namespace declarations
+    /// in effect at the nearest output ancestor ("" for the default).
+    fn write_element(
+        &self,
+        e: Element,
+        apex: bool,
+        rendered: &BTreeMap<String, String>,
+        out: &mut String,
+    ) {
+        let node = Node::Element(e);
+        let mut in_scope: BTreeMap<String, String> = e
+            .namespaces_in_scope()
+            .into_iter()
+            .filter(|ns| ns.prefix() != "xml")
+            .map(|ns| (ns.prefix().to_string(), ns.uri().to_string()))
+            .collect();
+        in_scope.insert(
+            String::new(),
+            e.recursive_default_namespace_uri()
+                .unwrap_or("")
+                .to_string(),
+        );
+
+        let element_prefix = node::prefix(node).unwrap_or("");
+        // The parsed default namespace declarations are not reliable for
+        // `xmlns=""`, but the name of the element determines the binding.
+        in_scope.insert(
+            element_prefix.to_string(),
+            e.name().namespace_uri().unwrap_or("").to_string(),
+        );
+
+        let mut attributes = Vec::new();
+        for a in e.attributes() {
+            let prefix = node::prefix(Node::Attribute(a)).or_else(|| a.preferred_prefix());
+            if let (Some(p), Some(uri)) = (prefix, a.name().namespace_uri()) {
+                if p != "xml" {
+                    in_scope.insert(p.to_string(), uri.to_string());
+                }
+            }
+            attributes.push((
+                a.name().namespace_uri().unwrap_or("").to_string(),
+                a.name().local_part().to_string(),
+                prefix.map(String::from),
+                a.value().to_string(),
+            ));
+        }
+        if apex && self.algorithm == Algorithm::Inclusive {
+            inherit_xml_attributes(e, &mut attributes);
+        }
+        attributes.sort();
+
+        let visible = |prefix: &str| match self.algorithm {
+            Algorithm::Inclusive => true,
+            Algorithm::Exclusive => {
+                prefix == element_prefix
+                    || self.inclusive_prefixes.iter().any(|p| p == prefix)
+                    || attributes.iter().any(|a| a.2.as_deref() == Some(prefix))
+            }
+        };
+
+        let mut declarations = Vec::new();
+        let mut now_rendered = rendered.clone();
+        for (prefix, uri) in &in_scope {
+            if !visible(prefix) || rendered.get(prefix) == Some(uri) {
+                continue;
+            }
+            if prefix.is_empty() && uri.is_empty() && rendered.get(prefix).is_none() {
+                continue;
+            }
+            declarations.push((prefix.as_str(), uri.as_str()));
+            now_rendered.insert(prefix.clone(), uri.clone());
+        }
+
+        let name = match element_prefix {
+            "" => e.name().local_part().to_string(),
+            p => format!("{}:{}", p, e.name().local_part()),
+        };
+        out.push('<');
+        out.push_str(&name);
+        for (prefix, uri) in declarations {
+            out.push_str(" xmlns");
+            if !prefix.is_empty() {
+                out.push(':');
+                out.push_str(prefix);
+            }
+            out.push_str("=\"");
+            escape_attribute(uri, out);
+            out.push('"');
+        }
+        for (_, local, prefix, value) in &attributes {
+            out.push(' ');
+            if let Some(ref p) = *prefix {
+                out.push_str(p);
+                out.push(':');
+            }
+            out.push_str(local);
+            out.push_str("=\"");
+            escape_attribute(value, out);
+            out.push('"');
+        }
+        out.push('>');
+        for child in e.children() {
+            self.write_child(child.into(), &now_rendered, out);
+        }
+        out.push_str("</");
+        out.push_str(&name);
+        out.push('>');
+    }
+}
+
+type Attribute = (String, String, Option<String>, String);
+
+/// Adds the `xml:*` attributes of the ancestors of `e` which are not
+/// overridden by `e` itself, as required for the apex of a subtree.
+fn inherit_xml_attributes(e: Element, attributes: &mut Vec<Attribute>) {
+    let mut current = e.parent().and_then(|p| p.element());
+    while let Some(ancestor) = current {
+        for a in ancestor.attributes() {
+            if a.name().namespace_uri() != Some(XML_NAMESPACE) {
+                continue;
+            }
+            let local = a.name().local_part();
+            if attributes
+                .iter()
+                .any(|b| b.0 == XML_NAMESPACE && b.1 == local)
+            {
+                continue;
+            }
+            attributes.push((
+                XML_NAMESPACE.to_string(),
+                local.to_string(),
+                Some("xml".to_string()),
+                a.value().to_string(),
+            ));
+        }
+        current = ancestor.parent().and_then(|p| p.element());
+    }
+}
+
+fn escape_text(s: &str, out: &mut String) {
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '\r' => out.push_str("&#xD;"),
+            c => out.push(c),
+        }
+    }
+}
+
+fn escape_attribute(s: &str, out: &mut String) {
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '"' => out.push_str("&quot;"),
+            '\t' => out.push_str("&#x9;"),
+            '\n' => out.push_str("&#xA;"),
+            '\r' => out.push_str("&#xD;"),
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canonical(xml: &str, expr: &str, c14n: &Canonicalizer) -> String {
+        let reader = Reader::from_str(xml, None).unwrap();
+        let anchor = reader.with_nodeset_eval(expr).unwrap();
+        c14n.canonicalize(&anchor).unwrap()
+    }
+
+    // W3C Canonical XML 1.0, example 3.1 (without the document type).
+    #[test]
+    fn pis_and_comments() {
+        let xml = "<?xml version=\"1.0\"?>\n\n<?xml-stylesheet   href=\"doc.xsl\"\n   type=\"text/xsl\"   ?>\n\n<doc>Hello, world!<!-- Comment 1 --></doc>\n\n<?pi-without-data     ?>\n\n<!-- Comment 2 -->\n\n<!-- Comment 3 -->";
+
+        let c14n = Canonicalizer::new(Algorithm::Inclusive);
+        assert_eq!(
+            canonical(xml, "/", &c14n),
+            "<?xml-stylesheet href=\"doc.xsl\"\n   type=\"text/xsl\"   ?>\n<doc>Hello, world!</doc>\n<?pi-without-data?>"
+        );
+
+        let c14n = c14n.with_comments(true);
+        assert_eq!(
+            canonical(xml, "/", &c14n),
+            "<?xml-stylesheet href=\"doc.xsl\"\n   type=\"text/xsl\"   ?>\n<doc>Hello, world!<!-- Comment 1 --></doc>\n<?pi-without-data?>\n<!-- Comment 2 -->\n<!-- Comment 3 -->"
+        );
+    }
+
+    // W3C Canonical XML 1.0, example 3.3 (without DTD default attributes).
+    #[test]
+    fn start_and_end_tags() {
+        let xml = r#"<doc>
+   <e1   />
+   <e2   ></e2>
+   <e3   name = "elem3"   id="elem3"   />
+   <e4   name="elem4"   id="elem4"   ></e4>
+   <e5 a:attr="out" b:attr="sorted" attr2="all" attr="I'm"
+      xmlns:b="http://www.ietf.org"
+      xmlns:a="http://www.w3.org"
+      xmlns="http://example.org"/>
+   <e6 xmlns="" xmlns:a="http://www.w3.org">
+      <e7 xmlns="http://www.ietf.org">
+         <e8 xmlns="" xmlns:a="http://www.w3.org">
+            <e9 xmlns="" xmlns:a="http://www.ietf.org"/>
+         </e8>
+      </e7>
+   </e6>
+</doc>"#;
+        let expected = r#"<doc>
+   <e1></e1>
+   <e2></e2>
+   <e3 id="elem3" name="elem3"></e3>
+   <e4 id="elem4" name="elem4"></e4>
+   <e5 xmlns="http://example.org" xmlns:a="http://www.w3.org" xmlns:b="http://www.ietf.org" attr="I'm" attr2="all" b:attr="sorted" a:attr="out"></e5>
+   <e6 xmlns:a="http://www.w3.org">
+      <e7 xmlns="http://www.ietf.org">
+         <e8 xmlns="">
+            <e9 xmlns:a="http://www.ietf.org"></e9>
+         </e8>
+      </e7>
+   </e6>
+</doc>"#;
+        let c14n = Canonicalizer::new(Algorithm::Inclusive);
+        assert_eq!(canonical(xml, "/", &c14n), expected);
+    }
+
+    // W3C Canonical XML 1.0, example 3.4 (without DTD normalization).
+    #[test]
+    fn character_modifications() {
+        let xml = "<doc>\n   <text>First line&#x0d;&#10;Second line</text>\n   <value>&#x32;</value>\n   <compute><![CDATA[value>\"0\" && value<\"10\" ?\"valid\":\"error\"]]></compute>\n   <compute expr='value>\"0\" &amp;&amp; value&lt;\"10\" ?\"valid\":\"error\"'>valid</compute>\n   <norm attr=' &apos;   &#x20;&#13;&#xa;&#9;   &apos; '/>\n</doc>";
+        let expected = "<doc>\n   <text>First line&#xD;\nSecond line</text>\n   <value>2</value>\n   <compute>value&gt;\"0\" &amp;&amp; value&lt;\"10\" ?\"valid\":\"error\"</compute>\n   <compute expr=\"value>&quot;0&quot; &amp;&amp; value&lt;&quot;10&quot; ?&quot;valid&quot;:&quot;error&quot;\">valid</compute>\n   <norm attr=\" '    &#xD;&#xA;&#x9;   ' \"></norm>\n</doc>";
+        let c14n = Canonicalizer::new(Algorithm::Inclusive);
+        assert_eq!(canonical(xml, "/", &c14n), expected);
+    }
+
+    // W3C Exclusive XML Canonicalization 1.0, section 2.2.
+    #[test]
+    fn exclusive_subtree() {
+        let xml = r#"<n0:local xmlns:n0="foo:bar" xmlns:n3="ftp://example.org"><n1:elem2 xmlns:n1="http://example.net" xml:lang="en"><n3:stuff xmlns:n3="ftp://example.org"/></n1:elem2></n0:local>"#;
+        let expr = "//*[local-name() = 'elem2']";
+
+        let inclusive = Canonicalizer::new(Algorithm::Inclusive);
+        assert_eq!(
+            canonical(xml, expr, &inclusive),
+            r#"<n1:elem2 xmlns:n0="foo:bar" xmlns:n1="http://example.net" xmlns:n3="ftp://example.org" xml:lang="en"><n3:stuff></n3:stuff></n1:elem2>"#
+        );
+
+        let exclusive = Canonicalizer::new(Algorithm::Exclusive);
+        assert_eq!(
+            canonical(xml, expr, &exclusive),
+            r#"<n1:elem2 xmlns:n1="http://example.net" xml:lang="en"><n3:stuff xmlns:n3="ftp://example.org"></n3:stuff></n1:elem2>"#
+        );
+
+        let exclusive = exclusive.inclusive_prefixes(vec!["n0"]);
+        assert_eq!(
+            canonical(xml, expr, &exclusive),
+            r#"<n1:elem2 xmlns:n0="foo:bar" xmlns:n1="http://example.net" xml:lang="en"><n3:stuff xmlns:n3="ftp://example.org"></n3:stuff></n1:elem2>"#
+        );
+    }
+
+    #[test]
+    fn inherited_xml_attributes() {
+        let xml = r#"<a xml:lang="en" xml:space="preserve"><b xml:lang="de"><c/></b></a>"#;
+        let expr = "//c";
+        assert_eq!(
+            canonical(xml, expr, &Canonicalizer::new(Algorithm::Inclusive)),
+            r#"<c xml:lang="de" xml:space="preserve"></c>"#
+        );
+        assert_eq!(
+            canonical(xml, expr, &Canonicalizer::new(Algorithm::Exclusive)),
+            "<c></c>"
+        );
+    }
+}
@@ -0,0 +1,317 @@
+// Copyright 2017-2019 Leonardo Schwarz <mail@leoschwarz.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reading compressed documents.
+//!
+//! `Reader::from_path` and `Reader::from_read` detect the compression of
+//! their input by its magic bytes and decompress it while reading. Each
+//! format is enabled by a cargo feature:
+//!
+//! - `gzip`: gzip (`.gz`), using the `flate2` crate
+//! - `zstd`: Zstandard (`.zst`), using the `zstd` crate
+//! - `bzip2`: bzip2 (`.bz2`), using the `bzip2` crate
+//!
+//! Uncompressed input is always accepted. Input compressed with a format
+//! whose feature is disabled fails with `ErrorKind::Other`, as does
+//! corrupt input.
+//!
+//! Parsing happens on the decompressed document, so the byte offsets in
+//! parse errors refer to positions within the decompressed XML. The
+//! maximum input size of the `Limits` applies to the decompressed
+//! document, and decompression stops as soon as it is exceeded.
+//!
+//! Documents are decoded as UTF-8, or as UTF-16 if they start with a UTF-16
+//! byte order mark. UTF-16 without a byte order mark is rejected.
+//!
+//! # Examples
+//! ```
+//! use xpath_reader::compression::Compression;
+//!
+//! assert_eq!(Compression::detect(b"\x1f\x8b\x08\x00"), Compression::Gzip);
+//! assert_eq!(Compression::detect(b"<?xml version=\"1.0\"?>"), Compression::None);
+//! ```
+
+use errors::{Error, ErrorKind};
+use limits::Limits;
+use std::io::{self, Cursor, Read};
+
+/// The compression format of a document.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Compression {
+    /// No known compression.
+    None,
+    /// gzip, starting with the bytes `1f 8b`.
+    Gzip,
+    /// Zstandard, starting with the bytes `28 b5 2f fd` or a skippable
+    /// frame.
+    Zstd,
+    /// bzip2, starting with `BZh`.
+    Bzip2,
+}
+
+impl Compression {
+    /// Detects the compression of `data` by its magic bytes.
+    pub fn detect(data: &[u8]) -> Self {
+        if data.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if data.starts_with(&[0x28, 0xb5, 0x2f, 0xfd])
+            || (data.len() >= 4 && data[0] & 0xf0 == 0x50 && data[1..4] == [0x2a, 0x4d, 0x18])
+        {
+            Compression::Zstd
+        } else if data.starts_with(b"BZh") {
+            Compression::Bzip2
+        } else {
+            Compression::None
+        }
+    }
+
+    fn decoder<'r, R: 'r + Read>(self, read: R) -> Result<Box<dyn Read + 'r>, Error> {
+        match self {
+            Compression::None => Ok(Box::new(read)),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Ok(Box::new(::flate2::read::MultiGzDecoder::new(read))),
+            #[cfg(not(feature = "gzip"))]
+            Compression::Gzip => Err(unsupported("gzip", "gzip")),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => ::zstd::stream::read::Decoder::new(read)
+                .map(|d| Box::new(d) as Box<dyn Read>)
+                .map_err(|e| Error::internal(e, ErrorKind::Other)),
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd => Err(unsupported("Zstandard", "zstd")),
+            #[cfg(feature = "bzip2")]
+            Compression::Bzip2 => Ok(Box::new(::bzip2::read::MultiBzDecoder::new(read))),
+            #[cfg(not(feature = "bzip2"))]
+            Compression::Bzip2 => Err(unsupported("bzip2", "bzip2")),
+        }
+    }
+}
+
+#[cfg(not(all(feature = "gzip", feature = "zstd", feature = "bzip2")))]
+fn unsupported(format: &str, feature: &str) -> Error {
+    Error::internal(
+        format!(
+            "Input is {} compressed, which requires the '{}' feature.",
+            format, feature
+        ),
+        ErrorKind::Other,
+    )
+}
+
+/// Reads a possibly compressed document from `read`, reading at most the
+/// maximum input size of `limits` after decompression.
+pub(crate) fn read_document<R: Read>(mut read: R, limits: &Limits) -> Result<String, Error> {
+    let io_error = |e: io::Error| Error::internal(e, ErrorKind::Other);
+
+    let mut magic = Vec::with_capacity(4);
+    (&mut read)
+        .take(4)
+        .read_to_end(&mut magic)
+        .map_err(io_error)?;
+    let compression = Compression::detect(&magic);
+
+    let mut data = Vec::new();
+    compression
+        .decoder(Cursor::new(magic).chain(read))?
+        .take(limits.read_limit())
+        .read_to_end(&mut data)
+        .map_err(|e| {
+            Error::internal(
+                format!("Failed to decompress {:?} input: {}", compression, e),
+                ErrorKind::Other,
+            )
+        })?;
+    limits.check_input_size(data.len())?;
+    decode(data)
+}
+
+/// Decodes a document from UTF-8 or, if it starts with a byte order mark,
+/// UTF-16, removing the byte order mark.
+pub(crate) fn decode(mut data: Vec<u8>) -> Result<String, Error> {
+    let utf16 = |data: &[u8], unit: fn([u8; 2]) -> u16| {
+        if data.len() % 2 != 0 {
+            return Err(Error::internal(
+                "Truncated UTF-16 input.",
+                ErrorKind::ParseXml,
+            ));
+        }
+        let units = data.chunks(2).map(|c| unit([c[0], c[1]]));
+        ::std::char::decode_utf16(units)
+            .collect::<Result<String, _>>()
+            .map_err(|e| Error::internal(e, ErrorKind::ParseXml))
+    };
+    match data.get(..2) {
+        Some(b"\xff\xfe") => utf16(&data[2..], u16::from_le_bytes),
+        Some(b"\xfe\xff") => utf16(&data[2..], u16::from_be_bytes),
+        Some(b"<\0") | Some(b"\0<") => Err(Error::internal(
+            "UTF-16 input without byte order mark is not supported.",
+            ErrorKind::ParseXml,
+        )),
+        _ => {
+            if data.starts_with(b"\xef\xbb\xbf") {
+                data.drain(..3);
+            }
+            String::from_utf8(data).map_err(|e| Error::internal(e, ErrorKind::ParseXml))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reader::Reader;
+
+    /// The document compressed in the test vectors.
+    #[cfg(any(feature = "gzip", feature = "zstd", feature = "bzip2"))]
+    fn list() -> String {
+        let entries: String = (0..12)
+            .map(|i| format!("<entry n=\"{}\">item {} of the list</entry>", i, i * i % 7))
+            .collect();
+        format!("<list>{}</list>", entries)
+    }
+
+    #[cfg(any(feature = "gzip", feature = "zstd", feature = "bzip2"))]
+    fn check(data: &[u8]) {
+        let reader = Reader::from_read(data, None).unwrap();
+        let entries: Vec<String> = reader.read("/list/entry/@n").unwrap();
+        assert_eq!(entries.len(), 12);
+        assert_eq!(read_document(data, &Limits::default()).unwrap(), list());
+
+        let limits = Limits::unlimited().max_input_size(100);
+        let error = Reader::from_read_with_limits(data, None, &limits)
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), ErrorKind::LimitExceeded);
+
+        let mut corrupt = data.to_vec();
+        corrupt[60] ^= 4;
+        let error = Reader::from_read(&corrupt[..], None).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::Other);
+        let error = Reader::from_read(&data[..90], None).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::Other);
+    }
+
+    #[test]
+    fn detect() {
+        assert_eq!(Compression::detect(b""), Compression::None);
+        assert_eq!(Compression::detect(b"<a/>"), Compression::None);
+        assert_eq!(Compression::detect(b"\x28\xb5\x2f\xfd"), Compression::Zstd);
+        assert_eq!(Compression::detect(b"\x5a\x2a\x4d\x18"), Compression::Zstd);
+        assert_eq!(Compression::detect(b"BZh9"), Compression::Bzip2);
+    }
+
+    #[test]
+    fn uncompressed() {
+        let reader = Reader::from_read(&b"<a>text</a>"[..], None).unwrap();
+        let text: String = reader.read("/a").unwrap();
+        assert_eq!(text, "text");
+
+        let reader = Reader::from_read(&b"\xef\xbb\xbf<a>bom</a>"[..], None).unwrap();
+        let text: String = reader.read("/a").unwrap();
+        assert_eq!(text, "bom");
+
+        let limits = Limits::unlimited().max_input_size(10);
+        assert!(Reader::from_read_with_limits(&b"<a>text</a>"[..], None, &limits).is_err());
+        assert!(Reader::from_read_with_limits(&b"<a>tex</a>"[..], None, &limits).is_ok());
+
+        let utf16 = |bom: &[u8], unit: fn(u16) -> [u8; 2]| {
+            let mut data = bom.to_vec();
+            "<?xml version=\"1.0\" encoding=\"UTF-16\"?><a>\u{1f600}</a>"
+                .encode_utf16()
+                .for_each(|u| data.extend_from_slice(&unit(u)));
+            data
+        };
+        for data in &[
+            utf16(b"\xff\xfe", u16::to_le_bytes),
+            utf16(b"\xfe\xff", u16::to_be_bytes),
+        ] {
+            let reader = Reader::from_read(&data[..], None).unwrap();
+            let text: String = reader.read("/a").unwrap();
+            assert_eq!(text, "\u{1f600}");
+        }
+        let no_bom = utf16(b"", u16::to_le_bytes);
+        let error = Reader::from_read(&no_bom[..], None).err().unwrap();
+        assert!(error.to_string().contains("byte order mark"));
+        assert!(decode(b"\xff\xfe<".to_vec()).is_err());
+
+        let error = Reader::from_read(&b"<a>\xff</a>"[..], None).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::ParseXml);
+        assert!(Reader::from_read(&b"<"[..], None).is_err());
+    }
+
+    #[test]
+    fn error_position() {
+        // `<a><b></a>` compressed with gzip.
+        let data = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xb3, 0x49, 0xb4, 0xb3,
+            0x49, 0xb2, 0xb3, 0xd1, 0x4f, 0xb4, 0x03, 0x00, 0x71, 0xb8, 0x9f, 0x4a, 0x0a, 0x00,
+            0x00, 0x00,
+        ];
+        let error = Reader::from_read(&data[..], None).err().unwrap();
+        if cfg!(feature = "gzip") {
+            assert_eq!(error.kind(), ErrorKind::ParseXml);
+            assert!(error.to_string().contains("error at 8"));
+        } else {
+            assert_eq!(error.kind(), ErrorKind::Other);
+            assert!(error.to_string().contains("'gzip' feature"));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn gzip() {
+        check(&[
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xb3, 0xc9, 0xc9, 0x2c,
+            0x2e, 0xb1, 0xb3, 0x49, 0xcd, 0x2b, 0x29, 0xaa, 0x54, 0xc8, 0xb3, 0x55, 0x32, 0x50,
+            0xb2, 0xcb, 0x2c, 0x49, 0xcd, 0x55, 0x30, 0x50, 0xc8, 0x4f, 0x53, 0x28, 0xc9, 0x48,
+            0x55, 0x00, 0xc9, 0xdb, 0xe8, 0x83, 0xe5, 0x91, 0x94, 0x19, 0x42, 0x95, 0x19, 0xe2,
+            0x57, 0x66, 0x04, 0x55, 0x66, 0x82, 0x5f, 0x99, 0x31, 0x54, 0x99, 0x11, 0x7e, 0x65,
+            0x26, 0xc4, 0x29, 0x33, 0x25, 0xce, 0x52, 0x33, 0xe2, 0xbc, 0x60, 0x4e, 0x5c, 0x80,
+            0x58, 0x10, 0x67, 0x9a, 0x25, 0x71, 0x6e, 0x33, 0x34, 0x20, 0xce, 0xab, 0x86, 0x86,
+            0x78, 0xd5, 0xe9, 0x83, 0x23, 0x17, 0x00, 0x1d, 0xbd, 0x31, 0x3b, 0xe3, 0x01, 0x00,
+            0x00,
+        ]);
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn zstd() {
+        check(&[
+            0x28, 0xb5, 0x2f, 0xfd, 0x64, 0xe3, 0x00, 0xe5, 0x02, 0x00, 0x92, 0x03, 0x0d, 0x11,
+            0xa0, 0x6f, 0x88, 0x2e, 0x5b, 0x7f, 0xab, 0x0a, 0x93, 0x65, 0xb0, 0x09, 0x29, 0xbd,
+            0xfd, 0x87, 0x35, 0xec, 0x2d, 0x8c, 0xc1, 0xbd, 0x3d, 0xa1, 0x63, 0x0a, 0x11, 0x83,
+            0x08, 0xf7, 0xa0, 0xb8, 0xbd, 0x67, 0x47, 0xb9, 0x59, 0xb6, 0x87, 0x0c, 0x34, 0x58,
+            0x93, 0x6f, 0xfd, 0xd9, 0x14, 0x7b, 0x0b, 0xa3, 0x0a, 0x11, 0xa0, 0x30, 0x72, 0xfb,
+            0x7f, 0x06, 0x50, 0x33, 0xb5, 0x0e, 0x6b, 0x37, 0xbb, 0x9f, 0x1f, 0xfd, 0x49, 0x91,
+            0xa7, 0xb1, 0x1f, 0x71, 0xcf, 0x27, 0xc1, 0x15, 0xbb, 0xc4, 0x38, 0x53, 0x11, 0x10,
+            0xca, 0x08, 0x74, 0x0a, 0x44, 0x14, 0x38, 0x61,
+        ]);
+    }
+
+    #[test]
+    #[cfg(feature = "bzip2")]
+    fn bzip2() {
+        check(&[
+            0x42, 0x5a, 0x68, 0x39, 0x31, 0x41, 0x59, 0x26, 0x53, 0x59, 0xbf, 0xd2, 0xf9, 0xf2,
+            0x00, 0x00, 0x4a, 0x19, 0x80, 0x50, 0x00, 0xff, 0xe7, 0x03, 0x67, 0x9c, 0x20, 0x20,
+            0x00, 0x88, 0x12, 0x53, 0x44, 0x34, 0xd0, 0x61, 0x00, 0x01, 0x4a, 0x49, 0x47, 0xa8,
+            0xd0, 0x00, 0x68, 0xf5, 0x3d, 0x4e, 0x01, 0x54, 0x17, 0xc0, 0xa3, 0x84, 0x9b, 0x16,
+            0x61, 0xcc, 0xf7, 0x6c, 0x0a, 0xc0, 0x9f, 0x38, 0x4e, 0x8d, 0x09, 0xc6, 0xb2, 0xad,
+            0x90, 0xa8, 0x7b, 0xf4, 0xd5, 0xad, 0x63, 0x46, 0x32, 0xc5, 0xc9, 0x7d, 0x2b, 0xf4,
+            0xbe, 0x36, 0xca, 0x67, 0x61, 0x75, 0x2d, 0x17, 0x79, 0x75, 0x2e, 0x0b, 0x62, 0xe1,
+            0x5d, 0x0b, 0xc0, 0xb7, 0xdf, 0xf0, 0xb6, 0x2c, 0x16, 0x16, 0xe2, 0xe1, 0x5c, 0xab,
+            0x0b, 0x62, 0xf3, 0x17, 0x22, 0xff, 0x17, 0x72, 0x45, 0x38, 0x50, 0x90, 0xbf, 0xd2,
+            0xf9, 0xf2,
+        ]);
+    }
+}
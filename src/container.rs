@@ -0,0 +1,513 @@
+// Copyright 2017-2019 Leonardo Schwarz <mail@leoschwarz.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reading XML parts out of ZIP containers.
+//!
+//! Office Open XML (`.docx`, `.xlsx`, ...), OpenDocument (`.odt`, `.ods`,
+//! ...) and EPUB files are ZIP archives of XML parts. A `Container` lists
+//! the parts of such an archive, opens them as a `Reader`, and locates the
+//! main document of the package by following its relationships:
+//!
+//! - OPC packages (Office Open XML): the `officeDocument` relationship in
+//!   `_rels/.rels`,
+//! - EPUB: the package document referenced by `META-INF/container.xml`,
+//! - OpenDocument: `content.xml` as listed in `META-INF/manifest.xml`.
+//!
+//! Part names are the names of the archive entries, without a leading
+//! `/`. Stored and deflated entries are supported, encrypted entries are
+//! not. The archive is read with the `zip` crate.
+//!
+//! Parts are read within `Limits::default()` unless other limits are set
+//! with `Container::set_limits`; the maximum input size applies to the
+//! decompressed part. XML parts are decoded like `Reader::from_read` does,
+//! as UTF-8 or, with a byte order mark, UTF-16.
+//!
+//! This module requires the `zip` feature.
+//!
+//! # Examples
+//! ```no_run
+//! use xpath_reader::container::Container;
+//! use xpath_reader::Context;
+//!
+//! let container = Container::from_path("report.docx").unwrap();
+//! let main = container.main_document().unwrap();
+//! assert_eq!(main, "word/document.xml");
+//!
+//! let mut context = Context::new();
+//! context.set_namespace(
+//!     "w",
+//!     "http://schemas.openxmlformats.org/wordprocessingml/2006/main",
+//! );
+//! let reader = container.reader(&main, Some(&context)).unwrap();
+//! let paragraphs: Vec<String> = reader.read("//w:p").unwrap();
+//! ```
+
+use compression;
+use errors::{Error, ErrorKind};
+use limits::Limits;
+use reader::{FromXml, FromXmlResult, Reader};
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use sxd_xpath::Context;
+use zip::ZipArchive;
+
+const RELATIONSHIPS_NAMESPACE: &str =
+    "http://schemas.openxmlformats.org/package/2006/relationships";
+const CONTAINER_NAMESPACE: &str = "urn:oasis:names:tc:opendocument:xmlns:container";
+const MANIFEST_NAMESPACE: &str = "urn:oasis:names:tc:opendocument:xmlns:manifest:1.0";
+
+/// The kind of a package, determined by the parts describing its
+/// structure.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PackageKind {
+    /// An OPC package with `_rels/.rels`, e.g. Office Open XML.
+    Opc,
+    /// An OpenDocument package with `META-INF/manifest.xml`.
+    OpenDocument,
+    /// An EPUB publication with `META-INF/container.xml`.
+    Epub,
+    /// None of the above.
+    Unknown,
+}
+
+/// A relationship of an OPC part or package.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Relationship {
+    /// The identifier of the relationship.
+    pub id: String,
+    /// The relationship type, an URI.
+    pub kind: String,
+    /// The name of the target part, or the target URI if `external`.
+    pub target: String,
+    /// Whether the target is outside of the package.
+    pub external: bool,
+}
+
+impl FromXml for Relationship {
+    fn from_xml<'d>(reader: &'d Reader<'d>) -> FromXmlResult<Self> {
+        let mode: Option<String> = reader.read("@TargetMode")?;
+        Ok(Relationship {
+            id: reader.read("@Id")?,
+            kind: reader.read("@Type")?,
+            target: reader.read("@Target")?,
+            external: mode.as_deref() == Some("External"),
+        })
+    }
+}
+
+/// A ZIP archive of XML parts.
+pub struct Container {
+    archive: RefCell<ZipArchive<Cursor<Vec<u8>>>>,
+    names: Vec<String>,
+    limits: Limits,
+}
+
+impl Container {
+    /// Opens the archive stored at `path`.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = File::open(path).map_err(|e| Error::internal(e, ErrorKind::Other))?;
+        Self::from_read(file)
+    }
+
+    /// Opens the archive read from `read`.
+    pub fn from_read<R: Read>(mut read: R) -> Result<Self, Error> {
+        let mut data = Vec::new();
+        read.read_to_end(&mut data)
+            .map_err(|e| Error::internal(e, ErrorKind::Other))?;
+        Self::from_bytes(data)
+    }
+
+    /// Opens the archive contained in `data`.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, Error> {
+        let archive =
+            ZipArchive::new(Cursor::new(data)).map_err(|e| Error::internal(e, ErrorKind::Other))?;
+        let names = (0..archive.len())
+            .map(|i| archive.name_for_index(i).unwrap_or_default().to_string())
+            .collect();
+        Ok(Container {
+            archive: RefCell::new(archive),
+            names,
+            limits: Limits::default(),
+        })
+    }
+
+    /// Sets the limits for reading and parsing parts.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Returns the names of the parts of the archive, omitting
+    /// directories.
+    pub fn parts(&self) -> Vec<&str> {
+        self.names
+            .iter()
+            .map(String::as_str)
+            .filter(|name| !name.ends_with('/'))
+            .collect()
+    }
+
+    /// Returns whether the archive contains the part `name`.
+    ///
+    /// Part names are compared ignoring ASCII case if there is no exact
+    /// match, as OPC part names are case insensitive.
+    pub fn contains(&self, name: &str) -> bool {
+        self.index(name).is_some()
+    }
+
+    fn index(&self, name: &str) -> Option<usize> {
+        self.names
+            .iter()
+            .position(|n| n == name)
+            .or_else(|| self.names.iter().position(|n| n.eq_ignore_ascii_case(name)))
+    }
+
+    /// Returns the decompressed content of the part `name`.
+    ///
+    /// Fails if the part is missing, encrypted, compressed with a method
+    /// other than deflate, or corrupt, and with `ErrorKind::LimitExceeded`
+    /// if it is larger than the maximum input size.
+    pub fn read_part(&self, name: &str) -> Result<Vec<u8>, Error> {
+        let index = self.index(name).ok_or_else(|| {
+            Error::internal(format!("No part named '{}'.", name), ErrorKind::Other)
+        })?;
+        let mut archive = self.archive.borrow_mut();
+        let file = archive.by_index(index).map_err(|e| {
+            Error::internal(
+                format!("Failed to open part '{}': {}", name, e),
+                ErrorKind::Other,
+            )
+        })?;
+        self.limits
+            .check_input_size(usize::try_from(file.size()).unwrap_or(usize::MAX))?;
+        let mut content = Vec::new();
+        file.take(self.limits.read_limit())
+            .read_to_end(&mut content)
+            .map_err(|e| {
+                Error::internal(
+                    format!("Failed to read part '{}': {}", name, e),
+                    ErrorKind::Other,
+                )
+            })?;
+        // The size declared in the archive is not trusted.
+        self.limits.check_input_size(content.len())?;
+        Ok(content)
+    }
+
+    /// Construct a new reader for the XML part `name`.
+    ///
+    /// A context can be specified to define custom functions,
+    /// variables and namespaces.
+    pub fn reader<'d>(
+        &self,
+        name: &str,
+        context: Option<&'d Context<'d>>,
+    ) -> Result<Reader<'d>, Error> {
+        let xml = compression::decode(self.read_part(name)?)?;
+        Reader::from_str_with_limits(&xml, context, &self.limits)
+    }
+
+    /// Returns the kind of the package.
+    pub fn kind(&self) -> PackageKind {
+        if self.contains("_rels/.rels") {
+            PackageKind::Opc
+        } else if self.contains("META-INF/container.xml") {
+            PackageKind::Epub
+        } else if self.contains("META-INF/manifest.xml") {
+            PackageKind::OpenDocument
+        } else {
+            PackageKind::Unknown
+        }
+    }
+
+    /// Returns the OPC relationships of the part `source`, or of the
+    /// package if `source` is empty.
+    ///
+    /// The targets of internal relationships are resolved to part names.
+    pub fn relationships(&self, source: &str) -> Result<Vec<Relationship>, Error> {
+        let (dir, file) = match source.rfind('/') {
+            Some(i) => (&source[..=i], &source[i + 1..]),
+            None => ("", source),
+        };
+        let name = format!("{}_rels/{}.rels", dir, file);
+        if !self.contains(&name) {
+            return Ok(Vec::new());
+        }
+
+        let mut context = Context::new();
+        context.set_namespace("r", RELATIONSHIPS_NAMESPACE);
+        let reader = self.reader(&name, Some(&context))?;
+        let mut relationships: Vec<Relationship> =
+            reader.read("/r:Relationships/r:Relationship")?;
+        for relationship in &mut relationships {
+            if !relationship.external {
+                relationship.target = resolve(source, &relationship.target);
+            }
+        }
+        Ok(relationships)
+    }
+
+    /// Returns the name of the main document of the package.
+    ///
+    /// Fails if the package is of an unknown kind or does not declare a
+    /// main document.
+    pub fn main_document(&self) -> Result<String, Error> {
+        let main = match self.kind() {
+            PackageKind::Opc => self
+                .relationships("")?
+                .into_iter()
+                .find(|r| !r.external && r.kind.ends_with("/officeDocument"))
+                .map(|r| r.target),
+            PackageKind::Epub => {
+                let mut context = Context::new();
+                context.set_namespace("c", CONTAINER_NAMESPACE);
+                let reader = self.reader("META-INF/container.xml", Some(&context))?;
+                let paths: Vec<String> = reader
+                    .read("//c:rootfile[@media-type='application/oebps-package+xml']/@full-path")?;
+                paths.into_iter().next().map(|p| resolve("", &p))
+            }
+            PackageKind::OpenDocument => {
+                let mut context = Context::new();
+                context.set_namespace("m", MANIFEST_NAMESPACE);
+                let reader = self.reader("META-INF/manifest.xml", Some(&context))?;
+                let paths: Vec<String> = reader.read("//m:file-entry/@m:full-path")?;
+                paths.into_iter().find(|p| p == "content.xml")
+            }
+            PackageKind::Unknown => None,
+        };
+        main.ok_or_else(|| {
+            Error::internal(
+                format!("No main document declared in {:?} package.", self.kind()),
+                ErrorKind::Other,
+            )
+        })
+    }
+}
+
+/// Resolves the relative reference `target` against the part `source`,
+/// returning a part name.
+fn resolve(source: &str, target: &str) -> String {
+    let target = target.split('#').next().unwrap_or("");
+    let mut bytes = Vec::with_capacity(target.len());
+    let mut rest = target.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        let hex = tail
+            .get(..2)
+            .and_then(|h| ::std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match hex {
+            Some(decoded) if b == b'%' => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(b);
+                rest = tail;
+            }
+        }
+    }
+    let target = String::from_utf8_lossy(&bytes);
+
+    let mut segments: Vec<&str> = if target.starts_with('/') {
+        Vec::new()
+    } else {
+        let mut segments: Vec<&str> = source.split('/').collect();
+        segments.pop();
+        segments
+    };
+    for segment in target.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+    use zip::write::{SimpleFileOptions, ZipWriter};
+
+    /// Builds an archive of stored entries.
+    fn archive(entries: &[(&str, &str)]) -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for &(name, content) in entries {
+            let options =
+                SimpleFileOptions::default().compression_method(zip::CompressionMethod::Stored);
+            writer.start_file(name, options).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn deflated_entries() {
+        // A directory `b/` and a deflated part `b/a.xml`, written by Python's
+        // zipfile module.
+        let data = [
+            0x50, 0x4b, 0x03, 0x04, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x21, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00,
+            0x00, 0x00, 0x62, 0x2f, 0x50, 0x4b, 0x03, 0x04, 0x14, 0x00, 0x00, 0x00, 0x08, 0x00,
+            0x00, 0x00, 0x21, 0x00, 0xae, 0x75, 0x3e, 0x7f, 0x10, 0x00, 0x00, 0x00, 0x6b, 0x00,
+            0x00, 0x00, 0x07, 0x00, 0x00, 0x00, 0x62, 0x2f, 0x61, 0x2e, 0x78, 0x6d, 0x6c, 0xb3,
+            0x49, 0xb4, 0x2b, 0x49, 0xad, 0x28, 0x51, 0xa0, 0x31, 0x61, 0xa3, 0x9f, 0x68, 0x07,
+            0x00, 0x50, 0x4b, 0x01, 0x02, 0x14, 0x03, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x21, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80,
+            0x01, 0x00, 0x00, 0x00, 0x00, 0x62, 0x2f, 0x50, 0x4b, 0x01, 0x02, 0x14, 0x03, 0x14,
+            0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x21, 0x00, 0xae, 0x75, 0x3e, 0x7f, 0x10,
+            0x00, 0x00, 0x00, 0x6b, 0x00, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x01, 0x20, 0x00, 0x00, 0x00, 0x62, 0x2f, 0x61,
+            0x2e, 0x78, 0x6d, 0x6c, 0x50, 0x4b, 0x05, 0x06, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00,
+            0x02, 0x00, 0x65, 0x00, 0x00, 0x00, 0x55, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let container = Container::from_bytes(data.to_vec()).unwrap();
+        assert_eq!(container.parts(), vec!["b/a.xml"]);
+        let text: String = container
+            .reader("b/a.xml", None)
+            .unwrap()
+            .read("/a")
+            .unwrap();
+        assert_eq!(text.trim(), vec!["text"; 20].join(" "));
+
+        let mut corrupt = data;
+        corrupt[80] ^= 1;
+        let container = Container::from_bytes(corrupt.to_vec()).unwrap();
+        assert!(container.read_part("b/a.xml").is_err());
+    }
+
+    #[test]
+    fn resolve_targets() {
+        assert_eq!(resolve("", "word/document.xml"), "word/document.xml");
+        assert_eq!(resolve("", "/word/document.xml"), "word/document.xml");
+        assert_eq!(
+            resolve("word/document.xml", "media/image%201.png"),
+            "word/media/image 1.png"
+        );
+        assert_eq!(
+            resolve("word/document.xml", "../customXml/item1.xml"),
+            "customXml/item1.xml"
+        );
+        assert_eq!(
+            resolve("word/document.xml", "./styles.xml#top"),
+            "word/styles.xml"
+        );
+    }
+
+    #[test]
+    fn office_open_xml() {
+        let data = archive(&[
+            ("[Content_Types].xml", "<Types/>"),
+            (
+                "_rels/.rels",
+                r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+                    <Relationship Id="rId2" Type="http://schemas.openxmlformats.org/package/2006/relationships/metadata/core-properties" Target="docProps/core.xml"/>
+                    <Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="/word/document.xml"/>
+                </Relationships>"#,
+            ),
+            (
+                "word/_rels/document.xml.rels",
+                r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+                    <Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/>
+                    <Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink" Target="https://example.com/" TargetMode="External"/>
+                </Relationships>"#,
+            ),
+            ("word/document.xml", "<document><p>Hello</p></document>"),
+            ("word/styles.xml", "<styles/>"),
+        ]);
+        let container = Container::from_bytes(data).unwrap();
+        assert_eq!(container.kind(), PackageKind::Opc);
+        assert_eq!(container.parts().len(), 5);
+
+        let main = container.main_document().unwrap();
+        assert_eq!(main, "word/document.xml");
+        let text: String = container.reader(&main, None).unwrap().read("//p").unwrap();
+        assert_eq!(text, "Hello");
+
+        let relationships = container.relationships(&main).unwrap();
+        assert_eq!(relationships[0].target, "word/styles.xml");
+        assert!(!relationships[0].external);
+        assert_eq!(relationships[1].target, "https://example.com/");
+        assert!(relationships[1].external);
+        assert!(container
+            .relationships("word/styles.xml")
+            .unwrap()
+            .is_empty());
+
+        assert!(container.contains("[content_types].xml"));
+        assert!(container.read_part("word/missing.xml").is_err());
+    }
+
+    #[test]
+    fn limits_and_encodings() {
+        let data = archive(&[("a.xml", "\u{feff}<a>text</a>")]);
+        let mut container = Container::from_bytes(data).unwrap();
+        let text: String = container.reader("a.xml", None).unwrap().read("/a").unwrap();
+        assert_eq!(text, "text");
+
+        container.set_limits(Limits::unlimited().max_input_size(8));
+        let error = container.read_part("a.xml").err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::LimitExceeded);
+    }
+
+    #[test]
+    fn open_document() {
+        let data = archive(&[
+            ("mimetype", "application/vnd.oasis.opendocument.text"),
+            (
+                "META-INF/manifest.xml",
+                r#"<manifest:manifest xmlns:manifest="urn:oasis:names:tc:opendocument:xmlns:manifest:1.0">
+                    <manifest:file-entry manifest:full-path="/" manifest:media-type="application/vnd.oasis.opendocument.text"/>
+                    <manifest:file-entry manifest:full-path="content.xml" manifest:media-type="text/xml"/>
+                </manifest:manifest>"#,
+            ),
+            ("content.xml", "<content/>"),
+        ]);
+        let container = Container::from_bytes(data).unwrap();
+        assert_eq!(container.kind(), PackageKind::OpenDocument);
+        assert_eq!(container.main_document().unwrap(), "content.xml");
+    }
+
+    #[test]
+    fn epub() {
+        let data = archive(&[
+            ("mimetype", "application/epub+zip"),
+            (
+                "META-INF/container.xml",
+                r#"<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+                    <rootfiles>
+                        <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+                    </rootfiles>
+                </container>"#,
+            ),
+            ("OEBPS/content.opf", "<package/>"),
+        ]);
+        let container = Container::from_bytes(data).unwrap();
+        assert_eq!(container.kind(), PackageKind::Epub);
+        assert_eq!(container.main_document().unwrap(), "OEBPS/content.opf");
+
+        let unknown = Container::from_bytes(archive(&[("a.xml", "<a/>")])).unwrap();
+        assert_eq!(unknown.kind(), PackageKind::Unknown);
+        assert!(unknown.main_document().is_err());
+        assert!(Container::from_bytes(b"<a/>".to_vec()).is_err());
+    }
+}
@@ -0,0 +1,425 @@
+// Copyright 2017-2019 Leonardo Schwarz <mail@leoschwarz.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Structural comparison of documents.
+//!
+//! Child elements are matched by their expanded name and their position
+//! among the siblings of the same name, text nodes, comments and processing
+//! instructions by their position among the siblings of the same kind.
+//!
+//! # Examples
+//! ```
+//! use xpath_reader::Reader;
+//! use xpath_reader::diff::{ChangeKind, Differ};
+//!
+//! let old = Reader::from_str(r#"<feed><item id="1">A</item></feed>"#, None).unwrap();
+//! let new = Reader::from_str(r#"<feed><item id="2">A</item><item/></feed>"#, None).unwrap();
+//!
+//! let changes = Differ::new().compare(&old, &new).unwrap();
+//! assert_eq!(changes.len(), 2);
+//! assert_eq!(changes[0].kind, ChangeKind::Changed);
+//! assert_eq!(changes[0].path, "/feed[1]/item[1]/@id");
+//! assert_eq!(changes[1].kind, ChangeKind::Added);
+//! assert_eq!(changes[1].path, "/feed[1]/item[2]");
+//! ```
+
+use errors::Error;
+use node::{self, NodeKind};
+use raw;
+use reader::Reader;
+use std::fmt;
+use sxd_xpath::nodeset::Node;
+use text::{is_xml_whitespace, normalize_space};
+
+/// The kind of a change between two documents.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ChangeKind {
+    /// The node only exists in the new document.
+    Added,
+    /// The node only exists in the old document.
+    Removed,
+    /// The node exists in both documents with different values.
+    Changed,
+}
+
+/// A difference between two documents.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Change {
+    /// The kind of the change.
+    pub kind: ChangeKind,
+    /// The kind of the changed node.
+    pub node_kind: NodeKind,
+    /// The location path of the node, in the new document for added nodes
+    /// and in the old document otherwise.
+    pub path: String,
+    /// The value in the old document, the serialized element for elements.
+    pub old: Option<String>,
+    /// The value in the new document, the serialized element for elements.
+    pub new: Option<String>,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ChangeKind::Added => write!(f, "added {}", self.path),
+            ChangeKind::Removed => write!(f, "removed {}", self.path),
+            ChangeKind::Changed => write!(
+                f,
+                "changed {}: {:?} -> {:?}",
+                self.path,
+                self.old.as_deref().unwrap_or(""),
+                self.new.as_deref().unwrap_or("")
+            ),
+        }
+    }
+}
+
+/// Comparison settings.
+#[derive(Clone, Debug, Default)]
+pub struct Differ {
+    ignore_whitespace: bool,
+    ignore_attribute_order: bool,
+    ignore_comments: bool,
+}
+
+/// The key by which child nodes are matched.
+#[derive(Debug, Eq, PartialEq)]
+enum Key {
+    Element(Option<String>, String),
+    Text,
+    Comment,
+    ProcessingInstruction(String),
+}
+
+impl Differ {
+    /// Creates a differ reporting all differences.
+    pub fn new() -> Self {
+        Differ::default()
+    }
+
+    /// Sets whether whitespace only text nodes are skipped and whitespace
+    /// in text is normalized before comparison.
+    pub fn ignore_whitespace(mut self, ignore: bool) -> Self {
+        self.ignore_whitespace = ignore;
+        self
+    }
+
+    /// Sets whether a different order of the same attributes is ignored.
+    ///
+    /// Note that the parser sorts attributes by name, so only the order of
+    /// attributes in documents built with `Writer` or `Editor` can differ.
+    pub fn ignore_attribute_order(mut self, ignore: bool) -> Self {
+        self.ignore_attribute_order = ignore;
+        self
+    }
+
+    /// Sets whether comments are skipped.
+    pub fn ignore_comments(mut self, ignore: bool) -> Self {
+        self.ignore_comments = ignore;
+        self
+    }
+
+    /// Compares the anchor nodes of `old` and `new`.
+    ///
+    /// The changes of a node precede the changes of its children, and the
+    /// changes of children are grouped by the key they are matched by.
+    pub fn compare<'a, 'b>(
+        &self,
+        old: &'a Reader<'a>,
+        new: &'b Reader<'b>,
+    ) -> Result<Vec<Change>, Error> {
+        let old = old
+            .anchor_node()
+            .ok_or_else(|| Error::custom_msg("No old node to compare."))?;
+        let new = new
+            .anchor_node()
+            .ok_or_else(|| Error::custom_msg("No new node to compare."))?;
+
+        let mut changes = Vec::new();
+        match (old, new) {
+            (Node::Root(_), Node::Root(_)) => self.compare_children(old, new, &mut changes),
+            (Node::Element(_), Node::Element(_)) if key(old) == key(new) => {
+                self.compare_elements(old, new, &mut changes)
+            }
+            (Node::Element(_), Node::Element(_)) => {
+                changes.push(added_or_removed(ChangeKind::Removed, old));
+                changes.push(added_or_removed(ChangeKind::Added, new));
+            }
+            _ => {
+                return Err(Error::custom_msg(
+                    "Only documents and elements can be compared.",
+                ))
+            }
+        }
+        Ok(changes)
+    }
+
+    fn compare_elements(&self, old: Node, new: Node, changes: &mut Vec<Change>) {
+        let (old_element, new_element) = match (old, new) {
+            (Node::Element(o), Node::Element(n)) => (o, n),
+            _ => return,
+        };
+
+        let old_attrs = old_element.attributes();
+        let new_attrs = new_element.attributes();
+        for a in &old_attrs {
+            match new_element.attribute(a.name()) {
+                Some(b) if a.value() == b.value() => {}
+                Some(b) => changes.push(Change {
+                    kind: ChangeKind::Changed,
+                    node_kind: NodeKind::Attribute,
+                    path: node::node_path(Node::Attribute(*a)),
+                    old: Some(a.value().to_string()),
+                    new: Some(b.value().to_string()),
+                }),
+                None => changes.push(added_or_removed(ChangeKind::Removed, Node::Attribute(*a))),
+            }
+        }
+        for b in &new_attrs {
+            if old_element.attribute(b.name()).is_none() {
+                changes.push(added_or_removed(ChangeKind::Added, Node::Attribute(*b)));
+            }
+        }
+
+        if !self.ignore_attribute_order {
+            let names = |attrs: &[_]| -> Vec<String> {
+                attrs
+                    .iter()
+                    .filter_map(|a| node::qualified_name(Node::Attribute(*a)))
+                    .collect()
+            };
+            let (old_names, new_names) = (names(&old_attrs), names(&new_attrs));
+            let mut sorted_old = old_names.clone();
+            let mut sorted_new = new_names.clone();
+            sorted_old.sort();
+            sorted_new.sort();
+            if sorted_old == sorted_new && old_names != new_names {
+                changes.push(Change {
+                    kind: ChangeKind::Changed,
+                    node_kind: NodeKind::Element,
+                    path: node::node_path(old),
+                    old: Some(old_names.join(" ")),
+                    new: Some(new_names.join(" ")),
+                });
+            }
+        }
+
+        self.compare_children(old, new, changes);
+    }
+
+    fn compare_children(&self, old: Node, new: Node, changes: &mut Vec<Change>) {
+        let mut groups: Vec<(Key, Vec<Node>, Vec<Node>)> = Vec::new();
+        for (child, is_old) in old
+            .children()
+            .into_iter()
+            .map(|c| (c, true))
+            .chain(new.children().into_iter().map(|c| (c, false)))
+        {
+            if !self.is_compared(child) {
+                continue;
+            }
+            let key = key(child);
+            let index = match groups.iter().position(|g| g.0 == key) {
+                Some(index) => index,
+                None => {
+                    groups.push((key, Vec::new(), Vec::new()));
+                    groups.len() - 1
+                }
+            };
+            if is_old {
+                groups[index].1.push(child);
+            } else {
+                groups[index].2.push(child);
+            }
+        }
+
+        for (_, old_nodes, new_nodes) in groups {
+            for i in 0..old_nodes.len().max(new_nodes.len()) {
+                match (old_nodes.get(i), new_nodes.get(i)) {
+                    (Some(&o), Some(&n)) => match o {
+                        Node::Element(_) => self.compare_elements(o, n, changes),
+                        _ => {
+                            let (old_value, new_value) = (self.value(o), self.value(n));
+                            if old_value != new_value {
+                                changes.push(Change {
+                                    kind: ChangeKind::Changed,
+                                    node_kind: NodeKind::of(o),
+                                    path: node::node_path(o),
+                                    old: Some(old_value),
+                                    new: Some(new_value),
+                                });
+                            }
+                        }
+                    },
+                    (Some(&o), None) => changes.push(added_or_removed(ChangeKind::Removed, o)),
+                    (None, Some(&n)) => changes.push(added_or_removed(ChangeKind::Added, n)),
+                    (None, None) => {}
+                }
+            }
+        }
+    }
+
+    fn is_compared(&self, node: Node) -> bool {
+        match node {
+            Node::Comment(_) => !self.ignore_comments,
+            Node::Text(t) => {
+                !(self.ignore_whitespace && t.text().trim_matches(is_xml_whitespace).is_empty())
+            }
+            _ => true,
+        }
+    }
+
+    fn value(&self, node: Node) -> String {
+        match node {
+            Node::Text(_) if self.ignore_whitespace => normalize_space(&node.string_value()),
+            _ => node.string_value(),
+        }
+    }
+}
+
+fn key(node: Node) -> Key {
+    match node {
+        Node::Element(e) => Key::Element(
+            e.name().namespace_uri().map(String::from),
+            e.name().local_part().to_string(),
+        ),
+        Node::Comment(_) => Key::Comment,
+        Node::ProcessingInstruction(pi) => Key::ProcessingInstruction(pi.target().to_string()),
+        _ => Key::Text,
+    }
+}
+
+fn added_or_removed(kind: ChangeKind, node: Node) -> Change {
+    let value = match node {
+        Node::Element(_) => raw::outer_xml(node),
+        _ => node.string_value(),
+    };
+    let (old, new) = match kind {
+        ChangeKind::Added => (None, Some(value)),
+        _ => (Some(value), None),
+    };
+    Change {
+        kind,
+        node_kind: NodeKind::of(node),
+        path: node::node_path(node),
+        old,
+        new,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use writer::Writer;
+
+    fn paths(changes: &[Change]) -> Vec<String> {
+        changes.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn compare_documents() {
+        let old = Reader::from_str(
+            r#"<feed xmlns:p="urn:p"><!-- v1 --><item id="1" p:x="a">Chair</item><item id="2">Table</item></feed>"#,
+            None,
+        )
+        .unwrap();
+        let new = Reader::from_str(
+            r#"<feed xmlns:p="urn:p"><!-- v2 --><item p:x="b" id="1">Chair</item><item>Desk</item><note/></feed>"#,
+            None,
+        )
+        .unwrap();
+
+        let changes = Differ::new().compare(&old, &new).unwrap();
+        assert_eq!(
+            paths(&changes),
+            vec![
+                r#"changed /feed[1]/comment()[1]: " v1 " -> " v2 ""#,
+                r#"changed /feed[1]/item[1]/@p:x: "a" -> "b""#,
+                "removed /feed[1]/item[2]/@id",
+                r#"changed /feed[1]/item[2]/text()[1]: "Table" -> "Desk""#,
+                "added /feed[1]/note[1]",
+            ]
+        );
+        assert_eq!(
+            changes[4].new,
+            Some(r#"<note xmlns:p="urn:p"/>"#.to_string())
+        );
+
+        let changes = Differ::new()
+            .ignore_comments(true)
+            .compare(&old, &new)
+            .unwrap();
+        assert_eq!(changes.len(), 4);
+    }
+
+    #[test]
+    fn compare_attribute_order() {
+        let old = Reader::from_str(r#"<item a="1" b="2"/>"#, None).unwrap();
+        let writer = Writer::new();
+        writer.write("/item/@b", "2").unwrap();
+        writer.write("/item/@a", "1").unwrap();
+        let new = Reader::from_node(writer.document().root().into(), None);
+
+        let changes = Differ::new().compare(&old, &new).unwrap();
+        assert_eq!(paths(&changes), vec![r#"changed /item[1]: "a b" -> "b a""#]);
+
+        let changes = Differ::new()
+            .ignore_attribute_order(true)
+            .compare(&old, &new)
+            .unwrap();
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn compare_whitespace() {
+        let old = Reader::from_str("<a>\n  <b>x  y</b>\n</a>", None).unwrap();
+        let new = Reader::from_str("<a><b> x y </b></a>", None).unwrap();
+
+        let changes = Differ::new().compare(&old, &new).unwrap();
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0].kind, ChangeKind::Removed);
+
+        let changes = Differ::new()
+            .ignore_whitespace(true)
+            .compare(&old, &new)
+            .unwrap();
+        assert!(changes.is_empty());
+
+        // A no-break space is text, not XML whitespace.
+        let new = Reader::from_str("<a>\u{a0}<b> x y </b></a>", None).unwrap();
+        let changes = Differ::new()
+            .ignore_whitespace(true)
+            .compare(&old, &new)
+            .unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].kind, ChangeKind::Added);
+    }
+
+    #[test]
+    fn compare_anchored() {
+        let old = Reader::from_str("<a><b><c>1</c></b><d/></a>", None).unwrap();
+        let new = Reader::from_str("<x><b><c>2</c></b></x>", None).unwrap();
+        let old_b = old.with_nodeset_eval("//b").unwrap();
+        let new_b = new.with_nodeset_eval("//b").unwrap();
+
+        let changes = Differ::new().compare(&old_b, &new_b).unwrap();
+        assert_eq!(
+            paths(&changes),
+            vec![r#"changed /a[1]/b[1]/c[1]/text()[1]: "1" -> "2""#]
+        );
+
+        let changes = Differ::new().compare(&old, &new).unwrap();
+        assert_eq!(paths(&changes), vec!["removed /a[1]", "added /x[1]"]);
+    }
+}
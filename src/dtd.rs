@@ -0,0 +1,1236 @@
+// Copyright 2017-2019 Leonardo Schwarz <mail@leoschwarz.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Processing of document type declarations.
+//!
+//! The parser behind `Reader::from_str` skips the document type
+//! declaration. With `Reader::from_str_with_dtd` the declarations of the
+//! internal subset are applied instead: general entities are expanded,
+//! default and `#FIXED` attribute values are added, and the document can
+//! optionally be validated.
+//!
+//! The external subset is only loaded if a resolver is configured, since
+//! loading it would allow documents to make the application access files
+//! or the network. Parameter entities and conditional sections are not
+//! supported.
+//!
+//! Documents are parsed with `Limits::default()` unless other limits are
+//! configured, bounding the growth of the document by entity expansion.
+//!
+//! # Examples
+//! ```
+//! use xpath_reader::Reader;
+//! use xpath_reader::dtd::DtdOptions;
+//!
+//! let xml = r#"<!DOCTYPE note [
+//!   <!ENTITY company "ACME Corp.">
+//!   <!ATTLIST note priority CDATA "normal">
+//! ]>
+//! <note>Sent by &company;</note>"#;
+//! let reader = Reader::from_str_with_dtd(xml, None, &DtdOptions::new()).unwrap();
+//!
+//! let text: String = reader.read("/note").unwrap();
+//! assert_eq!(text, "Sent by ACME Corp.");
+//! let priority: String = reader.read("/note/@priority").unwrap();
+//! assert_eq!(priority, "normal");
+//! ```
+
+use errors::{Error, ErrorKind};
+use limits::Limits;
+use node::{self, XML_NAMESPACE};
+use std::collections::{BTreeMap, BTreeSet};
+use sxd_document::dom::Element;
+use sxd_document::parser::parse as sxd_parse;
+use sxd_document::Package;
+use sxd_xpath::nodeset::Node;
+
+type Resolver = Box<dyn Fn(&str) -> Result<String, Error>>;
+
+/// Settings for processing document type declarations.
+pub struct DtdOptions {
+    expand_entities: bool,
+    apply_defaults: bool,
+    validate: bool,
+    resolver: Option<Resolver>,
+    limits: Limits,
+}
+
+impl Default for DtdOptions {
+    fn default() -> Self {
+        DtdOptions {
+            expand_entities: true,
+            apply_defaults: true,
+            validate: false,
+            resolver: None,
+            limits: Limits::default(),
+        }
+    }
+}
+
+impl DtdOptions {
+    /// Creates options expanding entities and applying default attribute
+    /// values, without validation or loading of the external subset.
+    pub fn new() -> Self {
+        DtdOptions::default()
+    }
+
+    /// Sets whether references to general entities are expanded.
+    pub fn expand_entities(mut self, expand: bool) -> Self {
+        self.expand_entities = expand;
+        self
+    }
+
+    /// Sets whether default and `#FIXED` attribute values are added to
+    /// elements which do not specify the attribute.
+    pub fn apply_defaults(mut self, apply: bool) -> Self {
+        self.apply_defaults = apply;
+        self
+    }
+
+    /// Sets whether the document is validated against the declarations,
+    /// failing to parse invalid documents.
+    pub fn validate(mut self, validate: bool) -> Self {
+        self.validate = validate;
+        self
+    }
+
+    /// Sets the limits enforced while parsing, including the limit for
+    /// entity expansion.
+    ///
+    /// Defaults to `Limits::default()`, use `Limits::unlimited()` to parse
+    /// trusted documents without limits.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Enables loading of the external subset, `resolver` returning the
+    /// declarations for the system identifier of the document type
+    /// declaration.
+    ///
+    /// Declarations of the internal subset take precedence.
+    pub fn external_resolver<F>(mut self, resolver: F) -> Self
+    where
+        F: Fn(&str) -> Result<String, Error> + 'static,
+    {
+        self.resolver = Some(Box::new(resolver));
+        self
+    }
+}
+
+#[derive(Debug, Default)]
+struct Dtd {
+    root: String,
+    entities: BTreeMap<String, Entity>,
+    attributes: BTreeMap<String, Vec<AttributeDef>>,
+    elements: BTreeMap<String, ContentSpec>,
+}
+
+#[derive(Debug)]
+enum Entity {
+    Internal(String),
+    External,
+}
+
+#[derive(Debug)]
+struct AttributeDef {
+    name: String,
+    ty: AttributeType,
+    default: AttributeDefault,
+}
+
+#[derive(Debug, PartialEq)]
+enum AttributeType {
+    Cdata,
+    Id,
+    IdRef,
+    IdRefs,
+    Token,
+    Enumeration(Vec<String>),
+}
+
+#[derive(Debug)]
+enum AttributeDefault {
+    Required,
+    Implied,
+    Fixed(String),
+    Value(String),
+}
+
+#[derive(Debug)]
+enum ContentSpec {
+    Empty,
+    Any,
+    Mixed(Vec<String>),
+    Children(ContentParticle, String),
+}
+
+#[derive(Debug)]
+struct ContentParticle {
+    term: Term,
+    min: usize,
+    max: Option<usize>,
+}
+
+#[derive(Debug)]
+enum Term {
+    Name(String),
+    Sequence(Vec<ContentParticle>),
+    Choice(Vec<ContentParticle>),
+}
+
+fn syntax_error<S: Into<String>>(msg: S) -> Error {
+    Error::internal(msg.into(), ErrorKind::ParseXml)
+}
+
+struct Cursor<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn rest(&self) -> &'a str {
+        &self.s[self.pos..]
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.s.len()
+    }
+
+    fn starts_with(&self, lit: &str) -> bool {
+        self.rest().starts_with(lit)
+    }
+
+    fn eat(&mut self, lit: &str) -> bool {
+        if self.starts_with(lit) {
+            self.pos += lit.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, lit: &str) -> Result<(), Error> {
+        if self.eat(lit) {
+            Ok(())
+        } else {
+            Err(syntax_error(format!(
+                "Expected '{}' in document type declaration.",
+                lit
+            )))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn name(&mut self) -> Result<&'a str, Error> {
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| c.is_whitespace() || "()|,?*+>\"'[]%;=&<".contains(c))
+            .unwrap_or(rest.len());
+        if end == 0 {
+            return Err(syntax_error(
+                "Expected a name in document type declaration.",
+            ));
+        }
+        self.pos += end;
+        Ok(&rest[..end])
+    }
+
+    fn quoted(&mut self) -> Result<&'a str, Error> {
+        let quote = match self.rest().chars().next() {
+            Some(q) if q == '"' || q == '\'' => q,
+            _ => return Err(syntax_error("Expected a quoted literal.")),
+        };
+        self.pos += 1;
+        let value = self.until(if quote == '"' { "\"" } else { "'" })?;
+        Ok(value)
+    }
+
+    /// Consumes everything up to and including `lit`, returning the text
+    /// before it.
+    fn until(&mut self, lit: &str) -> Result<&'a str, Error> {
+        let rest = self.rest();
+        let end = rest
+            .find(lit)
+            .ok_or_else(|| syntax_error(format!("Missing '{}'.", lit)))?;
+        self.pos += end + lit.len();
+        Ok(&rest[..end])
+    }
+
+    fn external_id(&mut self) -> Result<Option<&'a str>, Error> {
+        if self.eat("SYSTEM") {
+            self.skip_whitespace();
+            Ok(Some(self.quoted()?))
+        } else if self.eat("PUBLIC") {
+            self.skip_whitespace();
+            self.quoted()?;
+            self.skip_whitespace();
+            Ok(Some(self.quoted()?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// The location and content of a document type declaration.
+struct Doctype<'a> {
+    start: usize,
+    end: usize,
+    name: &'a str,
+    system: Option<&'a str>,
+    subset: &'a str,
+}
+
+fn find_doctype(xml: &str) -> Result<Option<Doctype<'_>>, Error> {
+    let mut cursor = Cursor { s: xml, pos: 0 };
+    cursor.eat("\u{feff}");
+    if cursor.eat("<?xml") {
+        cursor.until("?>")?;
+    }
+    loop {
+        cursor.skip_whitespace();
+        if cursor.eat("<!--") {
+            cursor.until("-->")?;
+        } else if cursor.starts_with("<?") {
+            cursor.until("?>")?;
+        } else {
+            break;
+        }
+    }
+
+    let start = cursor.pos;
+    if !cursor.eat("<!DOCTYPE") {
+        return Ok(None);
+    }
+    cursor.skip_whitespace();
+    let name = cursor.name()?;
+    cursor.skip_whitespace();
+    let system = cursor.external_id()?;
+    cursor.skip_whitespace();
+
+    let mut subset = "";
+    if cursor.eat("[") {
+        let subset_start = cursor.pos;
+        loop {
+            if cursor.is_empty() {
+                return Err(syntax_error("Unterminated internal subset."));
+            }
+            if cursor.starts_with("]") {
+                break;
+            } else if cursor.eat("<!--") {
+                cursor.until("-->")?;
+            } else if cursor.eat("<?") {
+                cursor.until("?>")?;
+            } else if cursor.starts_with("\"") || cursor.starts_with("'") {
+                cursor.quoted()?;
+            } else {
+                cursor.pos += cursor.rest().chars().next().map_or(1, char::len_utf8);
+            }
+        }
+        subset = &xml[subset_start..cursor.pos];
+        cursor.pos += 1;
+        cursor.skip_whitespace();
+    }
+    cursor.expect(">")?;
+
+    Ok(Some(Doctype {
+        start,
+        end: cursor.pos,
+        name,
+        system,
+        subset,
+    }))
+}
+
+fn parse_declarations(subset: &str, dtd: &mut Dtd) -> Result<(), Error> {
+    let mut cursor = Cursor { s: subset, pos: 0 };
+    loop {
+        cursor.skip_whitespace();
+        if cursor.is_empty() {
+            return Ok(());
+        }
+        if cursor.eat("<!--") {
+            cursor.until("-->")?;
+        } else if cursor.eat("<?") {
+            cursor.until("?>")?;
+        } else if cursor.eat("<!ENTITY") {
+            parse_entity(&mut cursor, dtd)?;
+        } else if cursor.eat("<!ATTLIST") {
+            parse_attlist(&mut cursor, dtd)?;
+        } else if cursor.eat("<!ELEMENT") {
+            parse_element(&mut cursor, dtd)?;
+        } else if cursor.eat("<!NOTATION") {
+            cursor.skip_whitespace();
+            cursor.name()?;
+            cursor.skip_whitespace();
+            cursor.external_id()?;
+            cursor.skip_whitespace();
+            if cursor.starts_with("\"") || cursor.starts_with("'") {
+                cursor.quoted()?;
+                cursor.skip_whitespace();
+            }
+            cursor.expect(">")?;
+        } else if cursor.starts_with("%") || cursor.starts_with("<![") {
+            return Err(syntax_error(
+                "Parameter entities and conditional sections are not supported.",
+            ));
+        } else {
+            return Err(syntax_error("Invalid markup declaration."));
+        }
+    }
+}
+
+fn parse_entity(cursor: &mut Cursor, dtd: &mut Dtd) -> Result<(), Error> {
+    cursor.skip_whitespace();
+    let parameter = cursor.eat("%");
+    cursor.skip_whitespace();
+    let name = cursor.name()?;
+    cursor.skip_whitespace();
+    let entity = if cursor.starts_with("\"") || cursor.starts_with("'") {
+        Entity::Internal(replacement_text(cursor.quoted()?)?)
+    } else {
+        cursor
+            .external_id()?
+            .ok_or_else(|| syntax_error("Expected an entity value."))?;
+        cursor.skip_whitespace();
+        if cursor.eat("NDATA") {
+            cursor.skip_whitespace();
+            cursor.name()?;
+        }
+        Entity::External
+    };
+    cursor.skip_whitespace();
+    cursor.expect(">")?;
+
+    // The first declaration is binding.
+    if !parameter && !dtd.entities.contains_key(name) {
+        dtd.entities.insert(name.to_string(), entity);
+    }
+    Ok(())
+}
+
+fn parse_enumeration(cursor: &mut Cursor) -> Result<Vec<String>, Error> {
+    cursor.expect("(")?;
+    let mut values = Vec::new();
+    loop {
+        cursor.skip_whitespace();
+        values.push(cursor.name()?.to_string());
+        cursor.skip_whitespace();
+        if cursor.eat(")") {
+            return Ok(values);
+        }
+        cursor.expect("|")?;
+    }
+}
+
+fn parse_attlist(cursor: &mut Cursor, dtd: &mut Dtd) -> Result<(), Error> {
+    cursor.skip_whitespace();
+    let element = cursor.name()?.to_string();
+    loop {
+        cursor.skip_whitespace();
+        if cursor.eat(">") {
+            return Ok(());
+        }
+        let name = cursor.name()?.to_string();
+        cursor.skip_whitespace();
+        let ty = if cursor.starts_with("(") {
+            AttributeType::Enumeration(parse_enumeration(cursor)?)
+        } else {
+            match cursor.name()? {
+                "CDATA" => AttributeType::Cdata,
+                "ID" => AttributeType::Id,
+                "IDREF" => AttributeType::IdRef,
+                "IDREFS" => AttributeType::IdRefs,
+                "ENTITY" | "ENTITIES" | "NMTOKEN" | "NMTOKENS" => AttributeType::Token,
+                "NOTATION" => {
+                    cursor.skip_whitespace();
+                    AttributeType::Enumeration(parse_enumeration(cursor)?)
+                }
+                other => return Err(syntax_error(format!("Unknown attribute type '{}'.", other))),
+            }
+        };
+        cursor.skip_whitespace();
+        let default = if cursor.eat("#REQUIRED") {
+            AttributeDefault::Required
+        } else if cursor.eat("#IMPLIED") {
+            AttributeDefault::Implied
+        } else if cursor.eat("#FIXED") {
+            cursor.skip_whitespace();
+            AttributeDefault::Fixed(cursor.quoted()?.to_string())
+        } else {
+            AttributeDefault::Value(cursor.quoted()?.to_string())
+        };
+
+        let defs = dtd.attributes.entry(element.clone()).or_default();
+        if !defs.iter().any(|d| d.name == name) {
+            defs.push(AttributeDef { name, ty, default });
+        }
+    }
+}
+
+fn parse_element(cursor: &mut Cursor, dtd: &mut Dtd) -> Result<(), Error> {
+    cursor.skip_whitespace();
+    let name = cursor.name()?.to_string();
+    cursor.skip_whitespace();
+    let start = cursor.pos;
+    let spec = if cursor.eat("EMPTY") {
+        ContentSpec::Empty
+    } else if cursor.eat("ANY") {
+        ContentSpec::Any
+    } else {
+        cursor.expect("(")?;
+        cursor.skip_whitespace();
+        if cursor.eat("#PCDATA") {
+            let mut names = Vec::new();
+            loop {
+                cursor.skip_whitespace();
+                if cursor.eat(")") {
+                    cursor.eat("*");
+                    break;
+                }
+                cursor.expect("|")?;
+                cursor.skip_whitespace();
+                names.push(cursor.name()?.to_string());
+            }
+            ContentSpec::Mixed(names)
+        } else {
+            let term = parse_group(cursor)?;
+            let particle = parse_quantifier(cursor, term);
+            let source = cursor.s[start..cursor.pos].to_string();
+            ContentSpec::Children(particle, source)
+        }
+    };
+    cursor.skip_whitespace();
+    cursor.expect(">")?;
+    dtd.elements.entry(name).or_insert(spec);
+    Ok(())
+}
+
+/// Parses the rest of a group whose opening parenthesis was consumed.
+fn parse_group(cursor: &mut Cursor) -> Result<Term, Error> {
+    let mut particles = vec![parse_particle(cursor)?];
+    let mut separator = None;
+    loop {
+        cursor.skip_whitespace();
+        if cursor.eat(")") {
+            break;
+        }
+        let sep = if cursor.eat(",") {
+            ','
+        } else if cursor.eat("|") {
+            '|'
+        } else {
+            return Err(syntax_error("Expected ',', '|' or ')' in content model."));
+        };
+        if separator.is_some_and(|s| s != sep) {
+            return Err(syntax_error("Mixed separators in content model."));
+        }
+        separator = Some(sep);
+        particles.push(parse_particle(cursor)?);
+    }
+    Ok(match separator {
+        Some('|') => Term::Choice(particles),
+        _ => Term::Sequence(particles),
+    })
+}
+
+fn parse_particle(cursor: &mut Cursor) -> Result<ContentParticle, Error> {
+    cursor.skip_whitespace();
+    let term = if cursor.eat("(") {
+        parse_group(cursor)?
+    } else {
+        Term::Name(cursor.name()?.to_string())
+    };
+    Ok(parse_quantifier(cursor, term))
+}
+
+fn parse_quantifier(cursor: &mut Cursor, term: Term) -> ContentParticle {
+    let (min, max) = if cursor.eat("?") {
+        (0, Some(1))
+    } else if cursor.eat("*") {
+        (0, None)
+    } else if cursor.eat("+") {
+        (1, None)
+    } else {
+        (1, Some(1))
+    };
+    ContentParticle { term, min, max }
+}
+
+/// Replaces the character references of an entity value literal, which
+/// are expanded when the entity is declared.
+fn replacement_text(literal: &str) -> Result<String, Error> {
+    let mut out = String::new();
+    let mut rest = literal;
+    while let Some(pos) = rest.find("&#") {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        let end = rest
+            .find(';')
+            .ok_or_else(|| syntax_error("Unterminated character reference."))?;
+        let c = char_ref(&rest[1..end]).ok_or_else(|| {
+            syntax_error(format!("Invalid character reference '{}'.", &rest[..=end]))
+        })?;
+        out.push(c);
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn check_size(out: &str, max_size: Option<usize>) -> Result<(), Error> {
+    if max_size.is_some_and(|max| out.len() > max) {
+        return Err(Error::internal(
+            "Entity expansion exceeds the limit.",
+            ErrorKind::LimitExceeded,
+        ));
+    }
+    Ok(())
+}
+
+/// Expands the general entity references in `text`, skipping comments,
+/// CDATA sections and processing instructions.
+fn expand(
+    text: &str,
+    dtd: &Dtd,
+    max_size: Option<usize>,
+    stack: &mut Vec<String>,
+    out: &mut String,
+) -> Result<(), Error> {
+    let mut cursor = Cursor { s: text, pos: 0 };
+    while !cursor.is_empty() {
+        check_size(out, max_size)?;
+        let rest = cursor.rest();
+        let next = rest.find(['<', '&']).unwrap_or(rest.len());
+        out.push_str(&rest[..next]);
+        cursor.pos += next;
+
+        let skipped = [("<!--", "-->"), ("<![CDATA[", "]]>"), ("<?", "?>")]
+            .iter()
+            .find(|&&(open, _)| cursor.starts_with(open));
+        if let Some(&(open, close)) = skipped {
+            let start = cursor.pos;
+            cursor.pos += open.len();
+            cursor.until(close)?;
+            out.push_str(&text[start..cursor.pos]);
+        } else if cursor.eat("&") {
+            reference(&mut cursor, dtd, max_size, stack, out, false)?;
+        } else if cursor.eat("<") {
+            out.push('<');
+            tag(&mut cursor, dtd, max_size, stack, out)?;
+        }
+    }
+    Ok(())
+}
+
+/// Copies the rest of a tag whose `<` was consumed, expanding the entity
+/// references in its attribute values.
+fn tag(
+    cursor: &mut Cursor,
+    dtd: &Dtd,
+    max_size: Option<usize>,
+    stack: &mut Vec<String>,
+    out: &mut String,
+) -> Result<(), Error> {
+    let mut quote = None;
+    while let Some(c) = cursor.rest().chars().next() {
+        cursor.pos += c.len_utf8();
+        match (quote, c) {
+            (Some(_), '&') => {
+                reference(cursor, dtd, max_size, stack, out, true)?;
+                continue;
+            }
+            (Some(q), _) if q == c => quote = None,
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '>') => {
+                out.push(c);
+                break;
+            }
+            _ => {}
+        }
+        out.push(c);
+    }
+    Ok(())
+}
+
+/// Expands the reference whose `&` was consumed. Character references and
+/// predefined entities are left to the parser.
+///
+/// In attribute values the replacement text is included as data, so its
+/// quotes are escaped and it must not contain `<`.
+fn reference(
+    cursor: &mut Cursor,
+    dtd: &Dtd,
+    max_size: Option<usize>,
+    stack: &mut Vec<String>,
+    out: &mut String,
+    in_attribute: bool,
+) -> Result<(), Error> {
+    out.push('&');
+    if cursor.starts_with("#") {
+        return Ok(());
+    }
+    let name = cursor.name()?;
+    cursor.expect(";")?;
+    let value = match name {
+        "lt" | "gt" | "amp" | "apos" | "quot" => {
+            out.push_str(name);
+            out.push(';');
+            return Ok(());
+        }
+        _ => match dtd.entities.get(name) {
+            Some(Entity::Internal(value)) => value,
+            Some(Entity::External) => {
+                return Err(syntax_error(format!(
+                    "External entity '{}' is not expanded.",
+                    name
+                )))
+            }
+            None => {
+                return Err(syntax_error(format!("Undefined entity '{}'.", name)));
+            }
+        },
+    };
+    out.pop();
+    if stack.iter().any(|n| n == name) {
+        return Err(syntax_error(format!("Recursive entity '{}'.", name)));
+    }
+    stack.push(name.to_string());
+    if in_attribute {
+        let mut cursor = Cursor { s: value, pos: 0 };
+        while let Some(c) = cursor.rest().chars().next() {
+            check_size(out, max_size)?;
+            cursor.pos += c.len_utf8();
+            match c {
+                '&' => reference(&mut cursor, dtd, max_size, stack, out, true)?,
+                '"' => out.push_str("&quot;"),
+                '\'' => out.push_str("&apos;"),
+                '<' => {
+                    return Err(syntax_error(format!(
+                        "Entity '{}' contains '<' in an attribute value.",
+                        name
+                    )))
+                }
+                _ => out.push(c),
+            }
+        }
+    } else {
+        expand(value, dtd, max_size, stack, out)?;
+    }
+    stack.pop();
+    Ok(())
+}
+
+/// Returns the character of a character reference like `#x41`.
+fn char_ref(reference: &str) -> Option<char> {
+    let code = if let Some(hex) = reference.strip_prefix("#x") {
+        u32::from_str_radix(hex, 16).ok()
+    } else {
+        reference.strip_prefix('#').and_then(|d| d.parse().ok())
+    };
+    code.and_then(::std::char::from_u32)
+}
+
+/// Replaces the predefined entities and character references of an
+/// attribute value literal.
+fn unescape(value: &str) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(pos) = rest.find('&') {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => break,
+        };
+        let reference = &rest[1..end];
+        let c = match reference {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "apos" => Some('\''),
+            "quot" => Some('"'),
+            _ => char_ref(reference),
+        };
+        match c {
+            Some(c) => out.push(c),
+            None => out.push_str(&rest[..=end]),
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Parses `xml`, applying the declarations of its document type
+/// declaration according to `options`.
+pub(crate) fn parse(xml: &str, options: &DtdOptions) -> Result<Package, Error> {
+    let parse_xml = |xml: &str| {
+        sxd_parse(xml).map_err(|e| Error::internal(format!("{}", e), ErrorKind::ParseXml))
+    };
+    let limits = &options.limits;
+    limits.check_input_size(xml.len())?;
+    let doctype = match find_doctype(xml)? {
+        Some(doctype) => doctype,
+        None => {
+            limits.check_markup(xml)?;
+            return parse_xml(xml);
+        }
+    };
+
+    let mut dtd = Dtd {
+        root: doctype.name.to_string(),
+        ..Dtd::default()
+    };
+    parse_declarations(doctype.subset, &mut dtd)?;
+    if let (Some(system), Some(resolver)) = (doctype.system, options.resolver.as_ref()) {
+        parse_declarations(&resolver(system)?, &mut dtd)?;
+    }
+
+    let mut source = xml[..doctype.start].to_string();
+    let body = &xml[doctype.end..];
+    if options.expand_entities {
+        let max_size = limits.max_expanded_size(body.len());
+        expand(body, &dtd, max_size, &mut Vec::new(), &mut source)?;
+    } else {
+        source.push_str(body);
+    }
+    limits.check_markup(&source)?;
+    let package = parse_xml(&source)?;
+
+    {
+        let document = package.as_document();
+        let elements: Vec<Element> = document
+            .root()
+            .children()
+            .into_iter()
+            .filter_map(|c| c.element())
+            .collect();
+        if options.apply_defaults {
+            for e in &elements {
+                apply_defaults(*e, &dtd);
+            }
+        }
+        if options.validate {
+            let mut validator = Validator {
+                dtd: &dtd,
+                errors: Vec::new(),
+                ids: BTreeSet::new(),
+                references: Vec::new(),
+            };
+            for e in &elements {
+                if node::qualified_name(Node::Element(*e)).as_ref() != Some(&dtd.root) {
+                    validator.error(
+                        Node::Element(*e),
+                        "Root element does not match the document type.",
+                    );
+                }
+                validator.element(*e);
+            }
+            validator.check_references();
+            if !validator.errors.is_empty() {
+                return Err(Error::custom_msg(format!(
+                    "Invalid document: {}",
+                    validator.errors.join("; ")
+                )));
+            }
+        }
+    }
+    Ok(package)
+}
+
+fn attribute_value<'d>(e: Element<'d>, name: &str) -> Option<&'d str> {
+    e.attributes()
+        .into_iter()
+        .find(|a| node::qualified_name(Node::Attribute(*a)).as_deref() == Some(name))
+        .map(|a| a.value())
+}
+
+fn apply_defaults(e: Element, dtd: &Dtd) {
+    if let Some(defs) = node::qualified_name(Node::Element(e)).and_then(|n| dtd.attributes.get(&n))
+    {
+        for def in defs {
+            let value = match def.default {
+                AttributeDefault::Fixed(ref v) | AttributeDefault::Value(ref v) => unescape(v),
+                _ => continue,
+            };
+            if def.name.starts_with("xmlns") || attribute_value(e, &def.name).is_some() {
+                continue;
+            }
+            match def.name.find(':') {
+                Some(pos) => {
+                    let prefix = &def.name[..pos];
+                    let uri = match prefix {
+                        "xml" => Some(XML_NAMESPACE),
+                        _ => e.namespace_uri_for_prefix(prefix),
+                    };
+                    if let Some(uri) = uri {
+                        e.set_attribute_value((uri, &def.name[pos + 1..]), &value)
+                            .set_preferred_prefix(Some(prefix));
+                    }
+                }
+                None => {
+                    e.set_attribute_value(def.name.as_str(), &value);
+                }
+            }
+        }
+    }
+    for child in e.children().into_iter().filter_map(|c| c.element()) {
+        apply_defaults(child, dtd);
+    }
+}
+
+struct Validator<'a> {
+    dtd: &'a Dtd,
+    errors: Vec<String>,
+    ids: BTreeSet<String>,
+    references: Vec<(String, String)>,
+}
+
+impl<'a> Validator<'a> {
+    fn error<S: AsRef<str>>(&mut self, node: Node, message: S) {
+        self.errors
+            .push(format!("{}: {}", node::node_path(node), message.as_ref()));
+    }
+
+    fn element(&mut self, e: Element) {
+        let node = Node::Element(e);
+        let name = node::qualified_name(node).unwrap_or_default();
+        self.attributes(e, &name);
+
+        let children: Vec<Element> = e
+            .children()
+            .into_iter()
+            .filter_map(|c| c.element())
+            .collect();
+        let has_text = e
+            .children()
+            .into_iter()
+            .filter_map(|c| c.text())
+            .any(|t| !t.text().trim().is_empty());
+        let names: Vec<String> = children
+            .iter()
+            .map(|c| node::qualified_name(Node::Element(*c)).unwrap_or_default())
+            .collect();
+
+        match self.dtd.elements.get(&name) {
+            None => self.error(node, "Element is not declared."),
+            Some(ContentSpec::Empty) => {
+                if !e.children().is_empty() {
+                    self.error(node, "Element must be empty.");
+                }
+            }
+            Some(ContentSpec::Any) => {}
+            Some(ContentSpec::Mixed(allowed)) => {
+                for (child, child_name) in children.iter().zip(&names) {
+                    if !allowed.contains(child_name) {
+                        self.error(Node::Element(*child), "Element is not allowed here.");
+                    }
+                }
+            }
+            Some(ContentSpec::Children(particle, source)) => {
+                if has_text {
+                    self.error(node, "Text content is not allowed.");
+                }
+                let mut starts = BTreeSet::new();
+                starts.insert(0);
+                if !matches(particle, &names, &starts).contains(&names.len()) {
+                    self.error(node, format!("Content does not match {}.", source));
+                }
+            }
+        }
+
+        for child in children {
+            self.element(child);
+        }
+    }
+
+    fn attributes(&mut self, e: Element, element_name: &str) {
+        let no_defs = Vec::new();
+        let defs = self.dtd.attributes.get(element_name).unwrap_or(&no_defs);
+        for a in e.attributes() {
+            let node = Node::Attribute(a);
+            let name = node::qualified_name(node).unwrap_or_default();
+            let def = match defs.iter().find(|d| d.name == name) {
+                Some(def) => def,
+                None => {
+                    self.error(node, "Attribute is not declared.");
+                    continue;
+                }
+            };
+            if let AttributeDefault::Fixed(ref fixed) = def.default {
+                if a.value() != unescape(fixed) {
+                    self.error(node, format!("Value must be '{}'.", fixed));
+                }
+            }
+            match def.ty {
+                AttributeType::Enumeration(ref values) => {
+                    if !values.iter().any(|v| v == a.value().trim()) {
+                        self.error(
+                            node,
+                            format!("Value must be one of '{}'.", values.join("', '")),
+                        );
+                    }
+                }
+                AttributeType::Id => {
+                    if !self.ids.insert(a.value().trim().to_string()) {
+                        self.error(node, format!("Duplicate ID '{}'.", a.value()));
+                    }
+                }
+                AttributeType::IdRef | AttributeType::IdRefs => {
+                    for reference in a.value().split_whitespace() {
+                        self.references
+                            .push((node::node_path(node), reference.to_string()));
+                    }
+                }
+                AttributeType::Cdata | AttributeType::Token => {}
+            }
+        }
+        for def in defs {
+            if let AttributeDefault::Required = def.default {
+                if attribute_value(e, &def.name).is_none() {
+                    self.error(
+                        Node::Element(e),
+                        format!("Missing required attribute '{}'.", def.name),
+                    );
+                }
+            }
+        }
+    }
+
+    fn check_references(&mut self) {
+        for (path, reference) in &self.references {
+            if !self.ids.contains(reference) {
+                self.errors.push(format!(
+                    "{}: Reference to unknown ID '{}'.",
+                    path, reference
+                ));
+            }
+        }
+    }
+}
+
+/// Returns the positions after matching `particle` starting at any of
+/// `starts`.
+fn matches(
+    particle: &ContentParticle,
+    names: &[String],
+    starts: &BTreeSet<usize>,
+) -> BTreeSet<usize> {
+    let mut result = BTreeSet::new();
+    if particle.min == 0 {
+        result.extend(starts.iter().cloned());
+    }
+    let mut seen = starts.clone();
+    let mut current = starts.clone();
+    let mut count = 0;
+    while particle.max.is_none_or(|max| count < max) && !current.is_empty() {
+        count += 1;
+        current = match particle.term {
+            Term::Name(ref name) => current
+                .iter()
+                .filter(|&&i| names.get(i) == Some(name))
+                .map(|i| i + 1)
+                .collect(),
+            Term::Sequence(ref particles) => particles
+                .iter()
+                .fold(current, |positions, p| matches(p, names, &positions)),
+            Term::Choice(ref particles) => particles
+                .iter()
+                .flat_map(|p| matches(p, names, &current))
+                .collect(),
+        };
+        if count >= particle.min {
+            result.extend(current.iter().cloned());
+            if current.is_subset(&seen) {
+                break;
+            }
+        }
+        seen.extend(current.iter().cloned());
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reader::Reader;
+
+    const DOCTYPE: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE catalog [
+  <!-- Entities may contain markup. -->
+  <!ENTITY vendor "ACME">
+  <!ENTITY footer "<note>&vendor; &amp; partners</note>">
+  <!ELEMENT catalog (item+, note?)>
+  <!ELEMENT item (#PCDATA)>
+  <!ELEMENT note (#PCDATA)>
+  <!ATTLIST catalog version CDATA #FIXED "2">
+  <!ATTLIST item id ID #REQUIRED
+                 currency (CHF|EUR) "CHF"
+                 replaces IDREF #IMPLIED>
+]>"#;
+
+    #[test]
+    fn entities_and_defaults() {
+        let xml = format!(
+            r#"{}<catalog><item id="a">&vendor; Chair</item><item id="b" currency="EUR"><![CDATA[&vendor;]]></item>&footer;</catalog>"#,
+            DOCTYPE
+        );
+        let reader = Reader::from_str_with_dtd(&xml, None, &DtdOptions::new()).unwrap();
+
+        let items: Vec<String> = reader.read("//item").unwrap();
+        assert_eq!(items, vec!["ACME Chair", "&vendor;"]);
+        let currencies: Vec<String> = reader.read("//item/@currency").unwrap();
+        assert_eq!(currencies, vec!["CHF", "EUR"]);
+        let version: u32 = reader.read("/catalog/@version").unwrap();
+        assert_eq!(version, 2);
+        let note: String = reader.read("//note").unwrap();
+        assert_eq!(note, "ACME & partners");
+
+        let options = DtdOptions::new().apply_defaults(false);
+        let reader = Reader::from_str_with_dtd(&xml, None, &options).unwrap();
+        let version: Option<u32> = reader.read("/catalog/@version").unwrap();
+        assert_eq!(version, None);
+    }
+
+    #[test]
+    fn validation() {
+        let options = DtdOptions::new().validate(true);
+        let valid = format!(
+            r#"{}<catalog><item id="a">1</item><item id="b" replaces="a">2</item></catalog>"#,
+            DOCTYPE
+        );
+        assert!(Reader::from_str_with_dtd(&valid, None, &options).is_ok());
+
+        let invalid = format!(
+            r#"{}<catalog version="3"><item id="a" currency="USD">1</item><item replaces="c"><b/></item><extra/></catalog>"#,
+            DOCTYPE
+        );
+        let error = Reader::from_str_with_dtd(&invalid, None, &options)
+            .err()
+            .unwrap()
+            .to_string();
+        for message in &[
+            "/catalog[1]/@version: Value must be '2'.",
+            "/catalog[1]: Content does not match (item+, note?).",
+            "/catalog[1]/item[1]/@currency: Value must be one of 'CHF', 'EUR'.",
+            "/catalog[1]/item[2]: Missing required attribute 'id'.",
+            "/catalog[1]/item[2]/b[1]: Element is not allowed here.",
+            "/catalog[1]/item[2]/b[1]: Element is not declared.",
+            "/catalog[1]/extra[1]: Element is not declared.",
+            "/catalog[1]/item[2]/@replaces: Reference to unknown ID 'c'.",
+        ] {
+            assert!(error.contains(message), "{} in {}", message, error);
+        }
+    }
+
+    #[test]
+    fn external_subset() {
+        let xml = r#"<!DOCTYPE doc SYSTEM "doc.dtd" [<!ENTITY a "internal">]><doc>&a; &b;</doc>"#;
+        let error = Reader::from_str_with_dtd(xml, None, &DtdOptions::new())
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("Undefined entity 'b'."));
+
+        let options = DtdOptions::new().external_resolver(|system| {
+            assert_eq!(system, "doc.dtd");
+            Ok(r#"<!ENTITY a "external"><!ENTITY b "loaded">"#.to_string())
+        });
+        let reader = Reader::from_str_with_dtd(xml, None, &options).unwrap();
+        let text: String = reader.read("/doc").unwrap();
+        assert_eq!(text, "internal loaded");
+    }
+
+    #[test]
+    fn malicious_entities() {
+        let recursive = r#"<!DOCTYPE a [<!ENTITY x "&y;"><!ENTITY y "&x;">]><a>&x;</a>"#;
+        assert!(Reader::from_str_with_dtd(recursive, None, &DtdOptions::new()).is_err());
+
+        let external = r#"<!DOCTYPE a [<!ENTITY x SYSTEM "file:///etc/passwd">]><a>&x;</a>"#;
+        assert!(Reader::from_str_with_dtd(external, None, &DtdOptions::new()).is_err());
+
+        let parameter = r#"<!DOCTYPE a [<!ENTITY % p "x"> %p;]><a/>"#;
+        assert!(Reader::from_str_with_dtd(parameter, None, &DtdOptions::new()).is_err());
+    }
+
+    #[test]
+    fn billion_laughs() {
+        let mut xml = String::from(r#"<!DOCTYPE lolz [<!ENTITY lol0 "lol">"#);
+        for i in 1..10 {
+            xml.push_str(&format!(
+                r#"<!ENTITY lol{0} "&lol{1}; &lol{1}; &lol{1}; &lol{1}; &lol{1}; &lol{1}; &lol{1}; &lol{1}; &lol{1}; &lol{1};">"#,
+                i,
+                i - 1
+            ));
+        }
+        xml.push_str("]><lolz>&lol9;</lolz>");
+
+        let options = DtdOptions::new();
+        let error = Reader::from_str_with_dtd(&xml, None, &options)
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), ErrorKind::LimitExceeded);
+
+        // Smaller expansions are accepted when explicitly unlimited.
+        let small = xml.replace("&lol9;", "&lol5;");
+        assert!(Reader::from_str_with_dtd(&small, None, &options).is_err());
+        let unlimited = DtdOptions::new().limits(Limits::unlimited());
+        assert!(Reader::from_str_with_dtd(&small, None, &unlimited).is_ok());
+
+        let deep = format!("<!DOCTYPE a [<!ENTITY n \"<a>\">]><a>{}", "&n;".repeat(300));
+        let error = Reader::from_str_with_dtd(&deep, None, &options)
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), ErrorKind::LimitExceeded);
+    }
+
+    #[test]
+    fn entities_in_attributes() {
+        let xml = r#"<!DOCTYPE a [<!ENTITY q 'say "hi" &amp; &r;'><!ENTITY r "'bye'">]><a t="&q;" u='&q;'/>"#;
+        let reader = Reader::from_str_with_dtd(xml, None, &DtdOptions::new()).unwrap();
+        let t: String = reader.read("/a/@t").unwrap();
+        assert_eq!(t, r#"say "hi" & 'bye'"#);
+        let u: String = reader.read("/a/@u").unwrap();
+        assert_eq!(u, t);
+
+        let xml = r#"<!DOCTYPE a [<!ENTITY b "<b/>">]><a t="&b;"/>"#;
+        let error = Reader::from_str_with_dtd(xml, None, &DtdOptions::new())
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("contains '<'"), "{}", error);
+    }
+
+    #[test]
+    fn character_references_in_entities() {
+        let xml = r#"<!DOCTYPE a [<!ENTITY q '&#60;b>&#x26;#38;&lt;&#60;/b>'>]><a>&q;</a>"#;
+        let reader = Reader::from_str_with_dtd(xml, None, &DtdOptions::new()).unwrap();
+        let b: String = reader.read("/a/b").unwrap();
+        assert_eq!(b, "&<");
+
+        assert_eq!(replacement_text("&#65;&amp;&#x42;").unwrap(), "A&amp;B");
+        assert!(replacement_text("&#xD800;").is_err());
+    }
+
+    #[test]
+    fn unescape_values() {
+        assert_eq!(
+            unescape("a &lt; b &#x41;&#66; &unknown;"),
+            "a < b AB &unknown;"
+        );
+    }
+}
@@ -0,0 +1,388 @@
+// Copyright 2017-2019 Leonardo Schwarz <mail@leoschwarz.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! In-place updates of existing documents.
+//!
+//! An `Editor` owns a parsed document and modifies the nodes selected by
+//! XPath expressions, so a document can be patched without mapping all of
+//! it to Rust types and back.
+//!
+//! # Examples
+//! ```
+//! use xpath_reader::edit::Editor;
+//!
+//! let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+//! <config><port>80</port><debug/><user name="root"/></config>"#;
+//! let mut editor = Editor::from_str(xml, None).unwrap();
+//!
+//! editor.set("//port", &8080).unwrap();
+//! editor.set("//user/@name", "admin").unwrap();
+//! editor.remove("//debug").unwrap();
+//! editor.append("/config", "<log level=\"info\"/>").unwrap();
+//!
+//! assert_eq!(
+//!     editor.to_string(),
+//!     r#"<?xml version="1.0" encoding="UTF-8"?><config><port>8080</port><user name="admin"/><log level="info"/></config>"#
+//! );
+//! ```
+
+use errors::{Error, ErrorKind};
+use expression::XPathExpression;
+use raw;
+use reader::Reader;
+use std::borrow::Borrow;
+use std::fmt;
+use sxd_document::dom::{ChildOfElement, Document, Element};
+use sxd_document::parser::parse as sxd_parse;
+use sxd_document::Package;
+use sxd_xpath::nodeset::Node;
+use sxd_xpath::{Context, Value, XPath};
+use util::Refable;
+use writer::{ToXml, Writer};
+
+/// A parsed document which can be modified through XPath expressions.
+///
+/// The `Display` implementation serializes the modified document, keeping
+/// the XML declaration of the input and the namespace prefixes used in it.
+pub struct Editor<'c> {
+    package: Package,
+    context: Refable<'c, Context<'c>>,
+    declaration: Option<String>,
+}
+
+impl<'c> Editor<'c> {
+    /// Parses the XML document `xml` for editing.
+    ///
+    /// A context can be specified to define custom functions, variables and
+    /// namespaces for the expressions selecting the nodes to modify.
+    pub fn from_str(xml: &str, context: Option<&'c Context<'c>>) -> Result<Self, Error> {
+        let package =
+            sxd_parse(xml).map_err(|e| Error::internal(format!("{}", e), ErrorKind::ParseXml))?;
+
+        let context = match context {
+            Some(c) => Refable::Borrowed(c),
+            None => Refable::Owned(Context::default()),
+        };
+
+        Ok(Editor {
+            package,
+            context,
+            declaration: declaration(xml).map(String::from),
+        })
+    }
+
+    /// Returns a reader for the current state of the document.
+    pub fn reader<'d>(&'d self) -> Reader<'d> {
+        let root = self.package.as_document().root();
+        Reader::from_node(root.into(), Some(self.context.borrow()))
+    }
+
+    /// Replaces the value of all nodes selected by `xpath_expr` with
+    /// `value`, returning the number of modified nodes.
+    ///
+    /// Elements and attributes are written to with `ToXml`. Structured
+    /// values update existing children at their paths and create the
+    /// missing ones. Selected text nodes are replaced by the text of the
+    /// value, leaving their siblings unchanged.
+    ///
+    /// Nothing is modified if any selected node cannot be written to. The
+    /// values are written into a copy of the document, which replaces the
+    /// edited document once all of them were written.
+    pub fn set<'a, X, V>(&mut self, xpath_expr: X, value: &V) -> Result<usize, Error>
+    where
+        X: Into<XPathExpression<'a>>,
+        V: ToXml + ?Sized,
+    {
+        let root = self.package.as_document().root();
+        let package = sxd_parse(&raw::inner_xml(root.into()))
+            .map_err(|e| Error::internal(format!("{}", e), ErrorKind::ParseXml))?;
+        let count = {
+            let writers = select(&package, self.context.borrow(), xpath_expr)?
+                .into_iter()
+                .map(Writer::for_node)
+                .collect::<Result<Vec<_>, Error>>()?;
+            for writer in &writers {
+                value.to_xml(writer)?;
+            }
+            writers.len()
+        };
+        self.package = package;
+        Ok(count)
+    }
+
+    /// Removes all nodes selected by `xpath_expr` from the document,
+    /// returning the number of removed nodes.
+    ///
+    /// Nothing is removed if the document root or a namespace node is
+    /// selected.
+    pub fn remove<'a, X>(&mut self, xpath_expr: X) -> Result<usize, Error>
+    where
+        X: Into<XPathExpression<'a>>,
+    {
+        let nodes = self.select(xpath_expr)?;
+        if nodes
+            .iter()
+            .any(|n| matches!(*n, Node::Root(_) | Node::Namespace(_)))
+        {
+            return Err(Error::custom_msg(
+                "Cannot remove the document root or namespace nodes.",
+            ));
+        }
+        for node in &nodes {
+            match *node {
+                Node::Element(e) => e.remove_from_parent(),
+                Node::Attribute(a) => a.remove_from_parent(),
+                Node::Text(t) => t.remove_from_parent(),
+                Node::Comment(c) => c.remove_from_parent(),
+                Node::ProcessingInstruction(pi) => pi.remove_from_parent(),
+                Node::Root(_) | Node::Namespace(_) => unreachable!(),
+            }
+        }
+        Ok(nodes.len())
+    }
+
+    /// Appends the XML fragment `fragment` to the children of all elements
+    /// selected by `xpath_expr`, returning the number of modified elements.
+    ///
+    /// The fragment may contain multiple elements and text, and can use the
+    /// namespace prefixes in scope of the selected element. Nothing is
+    /// modified if any selected node is not an element.
+    pub fn append<'a, X>(&mut self, xpath_expr: X, fragment: &str) -> Result<usize, Error>
+    where
+        X: Into<XPathExpression<'a>>,
+    {
+        let targets = self
+            .select(xpath_expr)?
+            .into_iter()
+            .map(|node| {
+                node.element()
+                    .ok_or_else(|| Error::custom_msg("Fragments can only be appended to elements."))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let packages = targets
+            .iter()
+            .map(|&target| parse_fragment(fragment, target))
+            .collect::<Result<Vec<_>, Error>>()?;
+        for (&target, package) in targets.iter().zip(&packages) {
+            let wrapper = package
+                .as_document()
+                .root()
+                .children()
+                .into_iter()
+                .filter_map(|c| c.element())
+                .next()
+                .expect("fragments are parsed into a wrapper element");
+            copy_children(wrapper, target, self.package.as_document());
+        }
+        Ok(targets.len())
+    }
+
+    fn select<'a, 'd, X>(&'d self, xpath_expr: X) -> Result<Vec<Node<'d>>, Error>
+    where
+        X: Into<XPathExpression<'a>>,
+    {
+        select(&self.package, self.context.borrow(), xpath_expr)
+    }
+}
+
+/// Evaluates `xpath_expr` on the root of `package`, returning the selected
+/// nodes in document order.
+fn select<'a, 'd, X>(
+    package: &'d Package,
+    context: &Context<'d>,
+    xpath_expr: X,
+) -> Result<Vec<Node<'d>>, Error>
+where
+    X: Into<XPathExpression<'a>>,
+{
+    let xpath_expr = xpath_expr.into();
+    let xpath = xpath_expr.parsed()?;
+    let xpath_ref: &XPath = xpath.borrow();
+    let root = package.as_document().root();
+    match xpath_ref
+        .evaluate(context, root)
+        .map_err(|e| Error::internal(format!("{}", e), ErrorKind::EvalXPath))?
+    {
+        Value::Nodeset(nodeset) => Ok(nodeset.document_order()),
+        _ => Err(Error::internal(
+            format!(
+                "XPath expression did not evaluate to nodeset: '{}'",
+                xpath_expr
+            ),
+            ErrorKind::EvalXPath,
+        )),
+    }
+}
+
+impl<'c> fmt::Display for Editor<'c> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let root = self.package.as_document().root();
+        write!(
+            f,
+            "{}{}",
+            self.declaration.as_deref().unwrap_or(""),
+            raw::inner_xml(root.into())
+        )
+    }
+}
+
+/// The XML declaration at the start of `xml`, if any.
+fn declaration(xml: &str) -> Option<&str> {
+    let xml = xml.trim_start_matches('\u{feff}');
+    if !xml.starts_with("<?xml") || !xml[5..].starts_with(|c: char| c.is_whitespace()) {
+        return None;
+    }
+    xml.find("?>").map(|end| &xml[..end + 2])
+}
+
+/// Parses `fragment` as the content of a wrapper element declaring the
+/// namespaces in scope of `target`.
+fn parse_fragment(fragment: &str, target: Element) -> Result<Package, Error> {
+    let mut xml = String::from("<fragment");
+    if let Some(uri) = target.recursive_default_namespace_uri() {
+        xml.push_str(" xmlns=\"");
+        raw::escape_attribute(uri, &mut xml);
+        xml.push('"');
+    }
+    for ns in target.namespaces_in_scope() {
+        if ns.prefix() == "xml" {
+            continue;
+        }
+        xml.push_str(&format!(" xmlns:{}=\"", ns.prefix()));
+        raw::escape_attribute(ns.uri(), &mut xml);
+        xml.push('"');
+    }
+    xml.push('>');
+    xml.push_str(fragment);
+    xml.push_str("</fragment>");
+
+    sxd_parse(&xml).map_err(|e| Error::internal(format!("{}", e), ErrorKind::ParseXml))
+}
+
+/// Deep copies the children of `source` into `target` of `document`.
+fn copy_children(source: Element, target: Element, document: Document) {
+    for child in source.children() {
+        match child {
+            ChildOfElement::Element(e) => {
+                let copy = document.create_element(e.name());
+                copy.set_preferred_prefix(e.preferred_prefix());
+                copy.set_default_namespace_uri(e.default_namespace_uri());
+                for attr in e.attributes() {
+                    copy.set_attribute_value(attr.name(), attr.value())
+                        .set_preferred_prefix(attr.preferred_prefix());
+                }
+                target.append_child(copy);
+                copy_children(e, copy, document);
+            }
+            ChildOfElement::Text(t) => target.append_child(document.create_text(t.text())),
+            ChildOfElement::Comment(c) => target.append_child(document.create_comment(c.text())),
+            ChildOfElement::ProcessingInstruction(pi) => {
+                target.append_child(document.create_processing_instruction(pi.target(), pi.value()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn declaration_detection() {
+        assert_eq!(
+            declaration("<?xml version=\"1.0\"?><a/>"),
+            Some("<?xml version=\"1.0\"?>")
+        );
+        assert_eq!(declaration("<?xml-stylesheet href=\"a\"?><a/>"), None);
+        assert_eq!(declaration("<a/>"), None);
+    }
+
+    #[test]
+    fn edit_namespaced() {
+        let xml = r#"<c:catalog xmlns:c="urn:catalog" xmlns:x="urn:x"><c:item x:id="1">old</c:item><c:item x:id="2">old</c:item><!-- note --></c:catalog>"#;
+        let mut context = Context::new();
+        context.set_namespace("cat", "urn:catalog");
+        context.set_namespace("x", "urn:x");
+        let mut editor = Editor::from_str(xml, Some(&context)).unwrap();
+
+        assert_eq!(editor.set("//cat:item", "new").unwrap(), 2);
+        assert_eq!(editor.set("//cat:item[2]/@x:id", &3).unwrap(), 1);
+        assert_eq!(editor.set("//cat:missing", "new").unwrap(), 0);
+        assert_eq!(editor.remove("//comment()").unwrap(), 1);
+        assert_eq!(
+            editor
+                .append("/cat:catalog", "<c:item x:id=\"4\">added</c:item>text")
+                .unwrap(),
+            1
+        );
+
+        assert_eq!(
+            editor.to_string(),
+            r#"<c:catalog xmlns:c="urn:catalog" xmlns:x="urn:x"><c:item x:id="1">new</c:item><c:item x:id="3">new</c:item><c:item x:id="4">added</c:item>text</c:catalog>"#
+        );
+        let ids: Vec<u32> = editor.reader().read("//cat:item/@x:id").unwrap();
+        assert_eq!(ids, vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn set_text_nodes() {
+        let mut editor = Editor::from_str("<p>a<b/>c</p>", None).unwrap();
+        assert_eq!(editor.set("p/text()[1]", "x").unwrap(), 1);
+        assert_eq!(editor.set("p/text()[2]", &2).unwrap(), 1);
+        assert_eq!(editor.to_string(), "<p>x<b/>2</p>");
+    }
+
+    #[test]
+    fn validate_before_modifying() {
+        let mut editor = Editor::from_str("<a><b/><!-- c --></a>", None).unwrap();
+        assert!(editor.remove("//b | /").is_err());
+        assert!(editor.set("//b | //comment()", "x").is_err());
+        assert!(editor.append("//b | //comment()", "<d/>").is_err());
+        assert_eq!(editor.to_string(), "<a><b/><!-- c --></a>");
+    }
+
+    #[test]
+    fn failure_on_later_target() {
+        struct Prefixed;
+        impl ToXml for Prefixed {
+            fn to_xml<'d>(&self, writer: &'d Writer<'d>) -> Result<(), Error> {
+                writer.write("p:d", "x")
+            }
+        }
+
+        let xml = r#"<a><b xmlns:p="urn:p"/><c/></a>"#;
+        let mut editor = Editor::from_str(xml, None).unwrap();
+        assert!(editor.append("//b | //c", "<p:d/>").is_err());
+        assert!(editor.set("//b | //c", &Prefixed).is_err());
+        assert_eq!(editor.to_string(), xml);
+
+        assert_eq!(editor.set("//b", &Prefixed).unwrap(), 1);
+        assert_eq!(
+            editor.to_string(),
+            r#"<a><b xmlns:p="urn:p"><p:d>x</p:d></b><c/></a>"#
+        );
+    }
+
+    #[test]
+    fn edit_errors() {
+        let mut editor = Editor::from_str("<a><b/></a>", None).unwrap();
+        assert!(editor.remove("/").is_err());
+        assert!(editor.append("//b", "<c>").is_err());
+        assert!(editor.append("//b/text()", "<c/>").is_ok());
+        assert_eq!(
+            editor.set("count(//b)", "1").unwrap_err().kind(),
+            ErrorKind::EvalXPath
+        );
+    }
+}
@@ -18,6 +18,8 @@ pub enum ErrorKind {
     ParseXPath,
     /// There was an error evaluation the XPath expression.
     EvalXPath,
+    /// A limit configured for parsing untrusted input was exceeded.
+    LimitExceeded,
     /// There was an other error.
     Other,
 }
@@ -28,21 +30,21 @@ impl<T> InternalError for T where T: fmt::Display + fmt::Debug + Send + Sync {}
 
 #[derive(Debug)]
 enum ErrorData {
-    Internal(Box<InternalError>),
+    Internal(Box<dyn InternalError>),
     Custom(CustomError),
 }
 
 #[derive(Debug)]
 pub enum CustomError {
     Message(String),
-    Error(Box<error::Error + Send + Sync>),
-    ErrorWithMessage(Box<error::Error + Send + Sync>, String),
+    Error(Box<dyn error::Error + Send + Sync>),
+    ErrorWithMessage(Box<dyn error::Error + Send + Sync>, String),
 }
 
 impl Error {
     pub(crate) fn internal<E: 'static + InternalError>(error: E, kind: ErrorKind) -> Self {
         Error {
-            kind: kind,
+            kind,
             data: ErrorData::Internal(Box::new(error)),
         }
     }
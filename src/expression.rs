@@ -16,9 +16,18 @@
 //!
 //! Provides a way to pass both pre-parsed and unparsed expressions
 //! as parameter to other methods.
+//!
+//! Simple location paths consisting only of unprefixed child element
+//! steps, `*`, `.` and a final attribute step, like `./title`,
+//! `tags/tag/@name` or `/feed/*`, are evaluated by walking the tree
+//! directly, which is considerably faster than the general XPath engine.
+//! All other expressions are evaluated by the engine.
 
 use errors::{Error, ErrorKind};
 use std::borrow::{Borrow, Cow};
+use std::fmt;
+use sxd_document::QName;
+use sxd_xpath::nodeset::{Node, Nodeset};
 use sxd_xpath::{Factory, XPath};
 use util::Refable;
 
@@ -36,43 +45,65 @@ pub struct XPathExpression<'a>(Repr<'a>);
 /// if you want to avoid an XPath expression being parsed
 /// on every invocation.
 pub fn parse(xpath_expr: &str) -> Result<XPathExpression<'static>, Error> {
-    parse_xpath(xpath_expr).map(|x| XPathExpression(Repr::Parsed(Refable::Owned(x))))
+    parse_xpath(xpath_expr).map(|x| {
+        XPathExpression(Repr::Parsed(
+            Refable::Owned(x),
+            Some(Cow::Owned(xpath_expr.to_string())),
+            SimplePath::parse(xpath_expr).map(Refable::Owned),
+        ))
+    })
 }
 
 #[derive(Debug)]
 enum Repr<'a> {
-    Parsed(Refable<'a, XPath>),
+    /// A parsed expression, with its source and fast path if known.
+    Parsed(
+        Refable<'a, XPath>,
+        Option<Cow<'a, str>>,
+        Option<Refable<'a, SimplePath>>,
+    ),
     Unparsed(Cow<'a, str>),
 }
 
 impl<'a> XPathExpression<'a> {
-    pub(crate) fn parsed(&self) -> Result<Refable<XPath>, Error> {
+    pub(crate) fn parsed(&self) -> Result<Refable<'_, XPath>, Error> {
+        match self.0 {
+            Repr::Parsed(ref refable, _, _) => Ok(refable.clone_ref()),
+            Repr::Unparsed(ref s) => parse_xpath(s).map(Refable::Owned),
+        }
+    }
+
+    /// The expression as simple path, if it is one.
+    pub(crate) fn simple_path(&self) -> Option<Refable<'_, SimplePath>> {
         match self.0 {
-            Repr::Parsed(ref refable) => Ok(refable.clone_ref()),
-            Repr::Unparsed(ref s) => parse_xpath(s).map(|x| Refable::Owned(x)),
+            Repr::Parsed(_, _, ref simple) => simple.as_ref().map(|s| s.clone_ref()),
+            Repr::Unparsed(ref s) => SimplePath::parse(s).map(Refable::Owned),
         }
     }
+}
 
-    pub(crate) fn to_string(&self) -> String {
+impl<'a> fmt::Display for XPathExpression<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.0 {
-            Repr::Parsed(ref refable) => {
+            Repr::Parsed(_, Some(ref source), _) => write!(f, "{}", source),
+            Repr::Parsed(ref refable, None, _) => {
                 let xpath: &XPath = refable.borrow();
-                format!("{:?}", xpath)
+                write!(f, "{:?}", xpath)
             }
-            Repr::Unparsed(ref s) => s.to_string(),
+            Repr::Unparsed(ref s) => write!(f, "{}", s),
         }
     }
 }
 
 impl From<XPath> for XPathExpression<'static> {
     fn from(xpath: XPath) -> Self {
-        XPathExpression(Repr::Parsed(Refable::Owned(xpath)))
+        XPathExpression(Repr::Parsed(Refable::Owned(xpath), None, None))
     }
 }
 
 impl<'a> From<&'a XPath> for XPathExpression<'a> {
     fn from(xpath: &'a XPath) -> Self {
-        XPathExpression(Repr::Parsed(Refable::Borrowed(xpath)))
+        XPathExpression(Repr::Parsed(Refable::Borrowed(xpath), None, None))
     }
 }
 
@@ -91,7 +122,11 @@ impl<'a> From<String> for XPathExpression<'a> {
 impl<'a> From<&'a XPathExpression<'a>> for XPathExpression<'a> {
     fn from(x: &'a XPathExpression<'a>) -> Self {
         match x.0 {
-            Repr::Parsed(ref refable) => XPathExpression(Repr::Parsed(refable.clone_ref())),
+            Repr::Parsed(ref refable, ref source, ref simple) => XPathExpression(Repr::Parsed(
+                refable.clone_ref(),
+                source.clone(),
+                simple.as_ref().map(|s| s.clone_ref()),
+            )),
             Repr::Unparsed(ref s) => XPathExpression(Repr::Unparsed(s.clone())),
         }
     }
@@ -103,3 +138,200 @@ fn parse_xpath(xpath_expr: &str) -> Result<XPath, Error> {
         .map_err(|e| Error::internal(format!("{}", e), ErrorKind::ParseXPath))?
         .ok_or_else(|| Error::internal("Empty XPath expression.", ErrorKind::ParseXPath))
 }
+
+/// A location path which can be evaluated without the XPath engine.
+#[derive(Debug, PartialEq)]
+pub(crate) struct SimplePath {
+    absolute: bool,
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Step {
+    /// `.`
+    Current,
+    /// A child element without namespace, or any child element for `*`.
+    Child(Option<String>),
+    /// An attribute without namespace, or any attribute for `@*`.
+    Attribute(Option<String>),
+}
+
+impl SimplePath {
+    /// Recognizes `expr` as simple path, returning `None` for any other
+    /// expression.
+    fn parse(expr: &str) -> Option<Self> {
+        let (absolute, relative) = match expr.strip_prefix('/') {
+            Some(rest) => (true, rest),
+            None => (false, expr),
+        };
+        if relative.is_empty() {
+            return if absolute {
+                Some(SimplePath {
+                    absolute,
+                    steps: Vec::new(),
+                })
+            } else {
+                None
+            };
+        }
+
+        let segments: Vec<&str> = relative.split('/').collect();
+        let mut steps = Vec::with_capacity(segments.len());
+        for (i, segment) in segments.iter().enumerate() {
+            let step = match *segment {
+                "." => Step::Current,
+                "*" => Step::Child(None),
+                "@*" => Step::Attribute(None),
+                name if is_ncname(name) => Step::Child(Some(name.to_string())),
+                attr => match attr.strip_prefix('@') {
+                    Some(name) if is_ncname(name) => Step::Attribute(Some(name.to_string())),
+                    _ => return None,
+                },
+            };
+            if let Step::Attribute(_) = step {
+                if i + 1 != segments.len() {
+                    return None;
+                }
+            }
+            steps.push(step);
+        }
+        Some(SimplePath { absolute, steps })
+    }
+
+    /// Evaluates the path with `anchor` as context node.
+    pub(crate) fn evaluate<'d>(&self, anchor: Node<'d>) -> Nodeset<'d> {
+        let start = if self.absolute {
+            Node::Root(anchor.document().root())
+        } else {
+            anchor
+        };
+        let mut nodes = vec![start];
+        for step in &self.steps {
+            nodes = match *step {
+                Step::Current => continue,
+                Step::Child(ref name) => nodes
+                    .iter()
+                    .flat_map(|n| n.children())
+                    .filter(|child| match *child {
+                        Node::Element(e) => matches(e.name(), name),
+                        _ => false,
+                    })
+                    .collect(),
+                Step::Attribute(ref name) => nodes
+                    .iter()
+                    .filter_map(|n| n.element())
+                    .flat_map(|e| e.attributes())
+                    .filter(|attr| matches(attr.name(), name))
+                    .map(Node::Attribute)
+                    .collect(),
+            };
+        }
+
+        let mut nodeset = Nodeset::new();
+        for node in nodes {
+            nodeset.add(node);
+        }
+        nodeset
+    }
+}
+
+/// Whether `qname` matches the unprefixed name test `name`, with `None`
+/// standing for `*`.
+fn matches(qname: QName, name: &Option<String>) -> bool {
+    match *name {
+        Some(ref name) => qname.namespace_uri().is_none() && qname.local_part() == name,
+        None => true,
+    }
+}
+
+/// Whether `name` is a name without prefix.
+///
+/// Only ASCII names are recognized, other names are left to the XPath
+/// engine.
+fn is_ncname(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sxd_document::parser::parse as sxd_parse;
+    use sxd_xpath::{Context, Value};
+
+    #[test]
+    fn recognize_simple_paths() {
+        for expr in &[
+            "/",
+            ".",
+            "title",
+            "./title",
+            "tags/tag/@name",
+            "/feed/*",
+            "a/./b",
+            "@*",
+            "x-y/z.w",
+        ] {
+            assert!(SimplePath::parse(expr).is_some(), "{}", expr);
+        }
+        for expr in &[
+            "", "//title", "a//b", "a/", "ns:a", "@ns:a", "@a/b", "..", "a/..", "text()", "a[1]",
+            "a | b", " a", "child::a", "$x", "1", "é",
+        ] {
+            assert!(SimplePath::parse(expr).is_none(), "{}", expr);
+        }
+        assert_eq!(
+            SimplePath::parse("/a/./*/@b"),
+            Some(SimplePath {
+                absolute: true,
+                steps: vec![
+                    Step::Child(Some("a".to_string())),
+                    Step::Current,
+                    Step::Child(None),
+                    Step::Attribute(Some("b".to_string())),
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn same_result_as_engine() {
+        let xml = r#"<feed xmlns:x="urn:x">
+                <entry id="1" x:id="x1"><title>One</title><tags><tag name="a"/><tag name="b"/></tags></entry>
+                <x:entry id="2"><title>Two</title></x:entry>
+                <entry xmlns="urn:default"><title>Three</title></entry>
+                <entry><title>Four</title><!-- title --><tags><tag/><tag name="c"/></tags></entry>
+            </feed>"#;
+        let package = sxd_parse(xml).unwrap();
+        let root = Node::Root(package.as_document().root());
+        let feed = root.children()[0];
+        let context = Context::new();
+
+        let cases: &[(Node, &str)] = &[
+            (root, "/"),
+            (feed, "/"),
+            (feed, "."),
+            (root, "feed/entry/title"),
+            (feed, "./entry"),
+            (feed, "entry/tags/tag/@name"),
+            (feed, "entry/@*"),
+            (feed, "*/title"),
+            (feed, "entry/@id"),
+            (feed, "/feed/*/."),
+            (feed, "missing/title"),
+        ];
+        for &(anchor, expr) in cases {
+            let path = SimplePath::parse(expr).unwrap();
+            let xpath = parse_xpath(expr).unwrap();
+            let expected = match xpath.evaluate(&context, anchor).unwrap() {
+                Value::Nodeset(nodeset) => nodeset.document_order(),
+                _ => panic!("{}", expr),
+            };
+            assert_eq!(path.evaluate(anchor).document_order(), expected, "{}", expr);
+        }
+    }
+}
@@ -0,0 +1,752 @@
+// Copyright 2017-2019 Leonardo Schwarz <mail@leoschwarz.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Lenient parsing of HTML and tag soup.
+//!
+//! `Reader::from_html` accepts any input. It is a lenient tag-soup parser,
+//! not an HTML5-conformant one: it handles the common cases the way
+//! browsers do, so void elements like `<br>` need no end tag, attributes
+//! may be unquoted or lack a value, a stray `&` is literal text, unmatched
+//! end tags are ignored and elements like `<p>`, `<li>` or `<td>` are
+//! closed implicitly. The content of `<script>` and `<style>` is not
+//! parsed as markup. Misnested formatting elements or misplaced table
+//! content can result in a different tree than the HTML5 tree
+//! construction algorithm builds.
+//!
+//! All named character references of HTML5 are decoded if they end with
+//! `;`. Numeric references to NUL, surrogates or beyond U+10FFFF are
+//! replaced by U+FFFD.
+//!
+//! The document always has the structure `/html/head` and `/html/body`,
+//! created if missing. Element and attribute names are lowercased.
+//!
+//! # Namespaces
+//!
+//! Unlike in the HTML5 DOM, HTML elements are in no namespace, so plain
+//! expressions like `//div` select them. The content of `<svg>` and
+//! `<math>` elements is in the SVG and MathML namespaces, which need to be
+//! registered in the context to select it. `xmlns` attributes have no
+//! effect in HTML and are dropped.
+//!
+//! # Examples
+//! ```
+//! use xpath_reader::Reader;
+//!
+//! let html = r#"<title>Offers</title>
+//! <ul class=offers>
+//!   <li>Chairs & tables<br>from 20 &euro;
+//!   <li data-new>Lamps
+//! </ul>"#;
+//! let reader = Reader::from_html(html, None);
+//!
+//! let title: String = reader.read("/html/head/title").unwrap();
+//! assert_eq!(title, "Offers");
+//! let offers: Vec<String> = reader.read("//ul[@class = 'offers']/li").unwrap();
+//! assert_eq!(offers, vec!["Chairs & tablesfrom 20 €\n  ", "Lamps\n"]);
+//! ```
+
+use entities::ENTITIES;
+use std::char;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use sxd_document::dom::{Document, Element};
+use sxd_document::{Package, QName};
+
+/// The namespace of SVG elements.
+pub const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+/// The namespace of MathML elements.
+pub const MATHML_NAMESPACE: &str = "http://www.w3.org/1998/Math/MathML";
+/// The namespace of `xlink:` attributes in SVG and MathML.
+pub const XLINK_NAMESPACE: &str = "http://www.w3.org/1999/xlink";
+
+const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+const HEAD_ELEMENTS: [&str; 8] = [
+    "base", "link", "meta", "noscript", "script", "style", "template", "title",
+];
+
+/// Elements closing an open `<p>` element.
+const CLOSES_P: [&str; 33] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "details",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "menu",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+    "summary",
+];
+
+/// Names lowercased by the tokenizer which are camel case in SVG.
+const SVG_NAMES: [&str; 14] = [
+    "clipPath",
+    "foreignObject",
+    "gradientTransform",
+    "gradientUnits",
+    "linearGradient",
+    "markerHeight",
+    "markerWidth",
+    "patternUnits",
+    "preserveAspectRatio",
+    "radialGradient",
+    "textLength",
+    "textPath",
+    "viewBox",
+    "xChannelSelector",
+];
+
+#[derive(Debug, PartialEq)]
+enum Token<'a> {
+    Start {
+        name: String,
+        attributes: Vec<(String, String)>,
+        self_closing: bool,
+    },
+    End(String),
+    Text(String),
+    Comment(&'a str),
+}
+
+struct Tokenizer<'a> {
+    rest: &'a str,
+    /// The element whose content is raw text, set after its start tag.
+    raw: Option<String>,
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        if self.rest.is_empty() {
+            return None;
+        }
+        if let Some(name) = self.raw.take() {
+            let end = find_end_tag(self.rest, &name).unwrap_or(self.rest.len());
+            let text = &self.rest[..end];
+            self.rest = &self.rest[end..];
+            if !text.is_empty() {
+                return Some(Token::Text(if name == "title" || name == "textarea" {
+                    decode(text)
+                } else {
+                    text.to_string()
+                }));
+            }
+        }
+
+        loop {
+            let rest = self.rest;
+            if rest.is_empty() {
+                return None;
+            } else if let Some(content) = rest.strip_prefix("<!--") {
+                let (comment, remaining) = match content.find("-->") {
+                    Some(end) => (&content[..end], &content[end + 3..]),
+                    None => (content, ""),
+                };
+                self.rest = remaining;
+                return Some(Token::Comment(comment));
+            }
+            if rest.starts_with("<!") || rest.starts_with("<?") {
+                // Document type declarations, CDATA sections and processing
+                // instructions are skipped.
+                self.rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+                continue;
+            }
+            let letter_at = |i: usize| rest[i..].starts_with(|c: char| c.is_ascii_alphabetic());
+            if rest.starts_with("</") && letter_at(2) {
+                let end = rest.find('>').unwrap_or(rest.len());
+                let name = rest[2..end]
+                    .split(|c: char| c.is_whitespace() || c == '/')
+                    .next()
+                    .unwrap_or("");
+                self.rest = rest.get(end + 1..).unwrap_or("");
+                return Some(Token::End(name.to_ascii_lowercase()));
+            }
+            if rest.starts_with('<') && letter_at(1) {
+                return Some(self.start_tag());
+            }
+
+            let first = rest.chars().next().map_or(0, char::len_utf8);
+            let end = rest[first..].find('<').map_or(rest.len(), |e| e + first);
+            self.rest = &rest[end..];
+            return Some(Token::Text(decode(&rest[..end])));
+        }
+    }
+}
+
+impl<'a> Tokenizer<'a> {
+    fn take_while<F: Fn(char) -> bool>(&mut self, f: F) -> &'a str {
+        let end = self.rest.find(|c| !f(c)).unwrap_or(self.rest.len());
+        let taken = &self.rest[..end];
+        self.rest = &self.rest[end..];
+        taken
+    }
+
+    fn start_tag(&mut self) -> Token<'a> {
+        self.rest = &self.rest[1..];
+        let name = self
+            .take_while(|c| !c.is_whitespace() && c != '/' && c != '>')
+            .to_ascii_lowercase();
+        let mut attributes: Vec<(String, String)> = Vec::new();
+        let mut self_closing = false;
+        loop {
+            self.take_while(char::is_whitespace);
+            if self.rest.is_empty() {
+                break;
+            }
+            if self.rest.starts_with('>') {
+                self.rest = &self.rest[1..];
+                break;
+            }
+            if self.rest.starts_with('/') {
+                self.rest = &self.rest[1..];
+                self_closing = self.rest.starts_with('>');
+                continue;
+            }
+            // A leading `=` is part of the name.
+            let first = self.rest.chars().next().map_or(0, char::len_utf8);
+            let start = self.rest;
+            self.rest = &self.rest[first..];
+            let len = first
+                + self
+                    .take_while(|c| !c.is_whitespace() && c != '/' && c != '>' && c != '=')
+                    .len();
+            let attribute = start[..len].to_ascii_lowercase();
+            self.take_while(char::is_whitespace);
+            let mut value = String::new();
+            if self.rest.starts_with('=') {
+                self.rest = &self.rest[1..];
+                self.take_while(char::is_whitespace);
+                let raw = match self.rest.chars().next() {
+                    Some(q) if q == '"' || q == '\'' => {
+                        let end = self.rest[1..].find(q).map_or(self.rest.len(), |e| e + 1);
+                        let raw = &self.rest[1..end];
+                        self.rest = self.rest.get(end + 1..).unwrap_or("");
+                        raw
+                    }
+                    _ => self.take_while(|c| !c.is_whitespace() && c != '>'),
+                };
+                value = decode(raw);
+            }
+            if !attributes.iter().any(|(n, _)| *n == attribute) {
+                attributes.push((attribute, value));
+            }
+        }
+        if ["script", "style", "textarea", "title"].contains(&name.as_str()) && !self_closing {
+            self.raw = Some(name.clone());
+        }
+        Token::Start {
+            name,
+            attributes,
+            self_closing,
+        }
+    }
+}
+
+/// Returns the position of the first `</` in `text` followed by `name`,
+/// compared ASCII case-insensitively.
+fn find_end_tag(text: &str, name: &str) -> Option<usize> {
+    text.match_indices("</").map(|(i, _)| i).find(|&i| {
+        text.as_bytes()[i + 2..]
+            .get(..name.len())
+            .is_some_and(|b| b.eq_ignore_ascii_case(name.as_bytes()))
+    })
+}
+
+/// Returns the replacement of the named character reference `name`,
+/// without `&` and `;`.
+fn named_reference(name: &str) -> Option<&'static str> {
+    static NAMED: OnceLock<HashMap<&str, &str>> = OnceLock::new();
+    NAMED
+        .get_or_init(|| {
+            ENTITIES
+                .iter()
+                .filter_map(|e| {
+                    let name = e.entity.strip_prefix('&')?.strip_suffix(';')?;
+                    Some((name, e.characters))
+                })
+                .collect()
+        })
+        .get(name)
+        .cloned()
+}
+
+/// Returns the character of the numeric character reference `number`,
+/// without `&#` and `;`.
+fn numeric_reference(number: &str) -> Option<char> {
+    let (digits, radix) = match number.strip_prefix(['x', 'X']) {
+        Some(hex) => (hex, 16),
+        None => (number, 10),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    let code = u32::from_str_radix(digits, radix).unwrap_or(u32::MAX);
+    Some(
+        char::from_u32(code)
+            .filter(|&c| c != '\0')
+            .unwrap_or(char::REPLACEMENT_CHARACTER),
+    )
+}
+
+/// Replaces character references, leaving unknown ones and stray
+/// ampersands as they are.
+fn decode(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(pos) = rest.find('&') {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        let end = rest[1..]
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '#')
+            .map_or(rest.len(), |e| e + 1);
+        let reference = &rest[1..end];
+        let decoded = if !rest[end..].starts_with(';') {
+            false
+        } else if let Some(number) = reference.strip_prefix('#') {
+            numeric_reference(number).map(|c| out.push(c)).is_some()
+        } else {
+            named_reference(reference)
+                .map(|s| out.push_str(s))
+                .is_some()
+        };
+        if decoded {
+            rest = &rest[end + 1..];
+        } else {
+            out.push('&');
+            rest = &rest[1..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+struct TreeBuilder<'d> {
+    document: Document<'d>,
+    html: Element<'d>,
+    head: Element<'d>,
+    body: Element<'d>,
+    in_body: bool,
+    /// The open elements below `head` or `body`.
+    open: Vec<(String, Element<'d>)>,
+    text: String,
+}
+
+impl<'d> TreeBuilder<'d> {
+    fn current(&self) -> Element<'d> {
+        match self.open.last() {
+            Some(&(_, e)) => e,
+            None if self.in_body => self.body,
+            None => self.head,
+        }
+    }
+
+    fn flush_text(&mut self) {
+        if !self.text.is_empty() {
+            let text = self.document.create_text(&self.text);
+            self.current().append_child(text);
+            self.text.clear();
+        }
+    }
+
+    fn enter_body(&mut self) {
+        if !self.in_body {
+            self.flush_text();
+            self.open.clear();
+            self.in_body = true;
+        }
+    }
+
+    /// Closes the innermost open element named one of `names`, unless an
+    /// element named one of `boundaries` is open inside of it.
+    fn close_implied(&mut self, names: &[&str], boundaries: &[&str]) {
+        for i in (0..self.open.len()).rev() {
+            let name = self.open[i].0.as_str();
+            if names.contains(&name) {
+                self.flush_text();
+                self.open.truncate(i);
+                return;
+            }
+            if boundaries.contains(&name) {
+                return;
+            }
+        }
+    }
+
+    fn namespace(&self, name: &str) -> Option<&'static str> {
+        match self.open.last() {
+            _ if name == "svg" => Some(SVG_NAMESPACE),
+            _ if name == "math" => Some(MATHML_NAMESPACE),
+            Some(&(ref parent, e)) if parent != "foreignObject" && parent != "annotation-xml" => {
+                e.name().namespace_uri().and_then(|ns| match ns {
+                    SVG_NAMESPACE => Some(SVG_NAMESPACE),
+                    MATHML_NAMESPACE => Some(MATHML_NAMESPACE),
+                    _ => None,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn start(&mut self, name: String, attributes: Vec<(String, String)>, self_closing: bool) {
+        match name.as_str() {
+            "html" => return self.merge_attributes(self.html, attributes),
+            "head" if !self.in_body => return self.merge_attributes(self.head, attributes),
+            "body" => {
+                self.enter_body();
+                return self.merge_attributes(self.body, attributes);
+            }
+            "head" => return,
+            _ => {}
+        }
+        let in_head =
+            !self.in_body && self.open.is_empty() && HEAD_ELEMENTS.contains(&name.as_str());
+        if !in_head {
+            self.enter_body();
+        }
+        self.flush_text();
+
+        let namespace = self.namespace(&name);
+        let name = match namespace {
+            Some(SVG_NAMESPACE) => svg_name(name),
+            _ => {
+                if CLOSES_P.contains(&name.as_str()) {
+                    self.close_implied(&["p"], &["button", "table", "td", "th"]);
+                }
+                match name.as_str() {
+                    "li" => self.close_implied(&["li"], &["ol", "ul"]),
+                    "dd" | "dt" => self.close_implied(&["dd", "dt"], &["dl"]),
+                    "tr" => self.close_implied(&["tr"], &["table"]),
+                    "td" | "th" => self.close_implied(&["td", "th"], &["tr", "table"]),
+                    "thead" | "tbody" | "tfoot" => {
+                        self.close_implied(&["thead", "tbody", "tfoot"], &["table"])
+                    }
+                    "option" => self.close_implied(&["option"], &["select"]),
+                    "optgroup" => self.close_implied(&["option", "optgroup"], &["select"]),
+                    _ => {}
+                }
+                name
+            }
+        };
+
+        let element = match namespace {
+            Some(ns) => {
+                let element = self
+                    .document
+                    .create_element(QName::with_namespace_uri(Some(ns), name.as_str()));
+                if name == "svg" || name == "math" {
+                    element.set_default_namespace_uri(Some(ns));
+                }
+                element
+            }
+            None => self.document.create_element(name.as_str()),
+        };
+        self.merge_attributes(element, attributes);
+        self.current().append_child(element);
+
+        let void = match namespace {
+            Some(_) => self_closing,
+            None => VOID_ELEMENTS.contains(&name.as_str()),
+        };
+        if !void {
+            self.open.push((name, element));
+        }
+    }
+
+    fn end(&mut self, name: &str) {
+        match name {
+            "head" => self.enter_body(),
+            "html" | "body" => {}
+            _ => {
+                if let Some(i) = self
+                    .open
+                    .iter()
+                    .rposition(|(n, _)| n.eq_ignore_ascii_case(name))
+                {
+                    self.flush_text();
+                    self.open.truncate(i);
+                }
+            }
+        }
+    }
+
+    fn merge_attributes(&self, element: Element, attributes: Vec<(String, String)>) {
+        let svg = element.name().namespace_uri() == Some(SVG_NAMESPACE);
+        for (name, value) in attributes {
+            if name == "xmlns" || name.starts_with("xmlns:") {
+                continue;
+            }
+            if element.attribute(name.as_str()).is_some() {
+                continue;
+            }
+            match name.strip_prefix("xlink:") {
+                Some(local) if element.name().namespace_uri().is_some() => {
+                    element
+                        .set_attribute_value(
+                            QName::with_namespace_uri(Some(XLINK_NAMESPACE), local),
+                            &value,
+                        )
+                        .set_preferred_prefix(Some("xlink"));
+                }
+                _ if svg => {
+                    element.set_attribute_value(svg_name(name).as_str(), &value);
+                }
+                _ => {
+                    element.set_attribute_value(name.as_str(), &value);
+                }
+            }
+        }
+    }
+}
+
+fn svg_name(name: String) -> String {
+    SVG_NAMES
+        .iter()
+        .find(|n| n.eq_ignore_ascii_case(&name))
+        .map_or(name, |n| n.to_string())
+}
+
+/// Parses `html` into a document, recovering from all errors.
+pub(crate) fn parse(html: &str) -> Package {
+    let package = Package::new();
+    {
+        let document = package.as_document();
+        let html_element = document.create_element("html");
+        let head = document.create_element("head");
+        let body = document.create_element("body");
+        html_element.append_child(head);
+        html_element.append_child(body);
+        document.root().append_child(html_element);
+
+        let mut builder = TreeBuilder {
+            document,
+            html: html_element,
+            head,
+            body,
+            in_body: false,
+            open: Vec::new(),
+            text: String::new(),
+        };
+        let tokenizer = Tokenizer {
+            rest: html.trim_start_matches('\u{feff}'),
+            raw: None,
+        };
+        for token in tokenizer {
+            match token {
+                Token::Start {
+                    name,
+                    attributes,
+                    self_closing,
+                } => builder.start(name, attributes, self_closing),
+                Token::End(name) => builder.end(&name),
+                Token::Text(text) => {
+                    let raw = builder
+                        .open
+                        .last()
+                        .is_some_and(|(n, _)| ["script", "style", "title"].contains(&n.as_str()));
+                    if !raw && !builder.in_body && text.trim().is_empty() {
+                        continue;
+                    }
+                    if !raw && !builder.in_body {
+                        builder.enter_body();
+                    }
+                    builder.text.push_str(&text);
+                }
+                Token::Comment(comment) => {
+                    builder.flush_text();
+                    let comment = builder.document.create_comment(comment);
+                    builder.current().append_child(comment);
+                }
+            }
+        }
+        builder.flush_text();
+    }
+    package
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reader::Reader;
+    use sxd_xpath::Context;
+
+    #[test]
+    fn tokenize() {
+        let tokens: Vec<Token> = Tokenizer {
+            rest: r#"<A HREF=/x?a=1&b=2 checked title='a "b"'>x &amp y &lt;<!--c--></a><script>if (a<b) {}</script>"#,
+            raw: None,
+        }
+        .collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Start {
+                    name: "a".to_string(),
+                    attributes: vec![
+                        ("href".to_string(), "/x?a=1&b=2".to_string()),
+                        ("checked".to_string(), String::new()),
+                        ("title".to_string(), "a \"b\"".to_string()),
+                    ],
+                    self_closing: false,
+                },
+                Token::Text("x &amp y <".to_string()),
+                Token::Comment("c"),
+                Token::End("a".to_string()),
+                Token::Start {
+                    name: "script".to_string(),
+                    attributes: vec![],
+                    self_closing: false,
+                },
+                Token::Text("if (a<b) {}".to_string()),
+                Token::End("script".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn character_references() {
+        assert_eq!(
+            decode("&hearts;&fjlig;&NotSquareSubset;&euro&bogus;"),
+            "\u{2665}fj\u{228f}\u{338}&euro&bogus;"
+        );
+        assert_eq!(
+            decode("&#0;&#xD800;&#x110000;&#99999999999;&#65;&#X42;&#;&#x;"),
+            "\u{fffd}\u{fffd}\u{fffd}\u{fffd}AB&#;&#x;"
+        );
+    }
+
+    #[test]
+    fn implied_structure() {
+        let html = r#"<!DOCTYPE html>
+<html lang=en><meta charset=utf-8><style>p > b {}</style>
+<p>One<p>Two <b>bold <i>both</b> plain</i>
+<table><tr><td>1<td>2<tr><td>3</table>
+<dl><dt>Term<dd>Definition</dl></div></span>"#;
+        let reader = Reader::from_html(html, None);
+
+        let lang: String = reader.read("/html/@lang").unwrap();
+        assert_eq!(lang, "en");
+        let charset: String = reader.read("/html/head/meta/@charset").unwrap();
+        assert_eq!(charset, "utf-8");
+        let style: String = reader.read("/html/head/style").unwrap();
+        assert_eq!(style, "p > b {}");
+        let paragraphs: Vec<String> = reader.read("/html/body/p").unwrap();
+        assert_eq!(paragraphs, vec!["One", "Two bold both plain\n"]);
+        let cells: Vec<String> = reader.read("//table/tr/td").unwrap();
+        assert_eq!(cells, vec!["1", "2", "3"]);
+        let definition: String = reader.read("//dl/dd").unwrap();
+        assert_eq!(definition, "Definition");
+    }
+
+    #[test]
+    fn malformed_input() {
+        for html in &[
+            "",
+            "<",
+            "</",
+            "</>",
+            "<a",
+            "<a b='",
+            "<a b=",
+            "&",
+            "&#;",
+            "&#xZZ;",
+            "é<é",
+            "<!--",
+            "<!DOCTYPE",
+            "<script>",
+            "<title>a &lt; b",
+            "</p></div>",
+            "<svg><p></svg>",
+            "<b\u{e9}>",
+        ] {
+            let reader = Reader::from_html(html, None);
+            assert!(reader.read::<String, _>("/html/body").is_ok());
+        }
+    }
+
+    #[test]
+    fn raw_text_end_tags() {
+        let html = "<script>é</scrip</SCRIPT ><style>a</style ></STYLE><p>x";
+        let reader = Reader::from_html(html, None);
+
+        let script: String = reader.read("//script").unwrap();
+        assert_eq!(script, "é</scrip");
+        let style: String = reader.read("//style").unwrap();
+        assert_eq!(style, "a");
+        let p: String = reader.read("//p").unwrap();
+        assert_eq!(p, "x");
+
+        let html = "<script>x</script>".repeat(20_000);
+        let reader = Reader::from_html(&html, None);
+        assert_eq!(
+            reader.read::<Vec<String>, _>("//script").unwrap().len(),
+            20_000
+        );
+    }
+
+    #[test]
+    fn long_runs_of_skipped_markup() {
+        let html = format!("{}<p>After", "<?x><!y>".repeat(200_000));
+        let reader = Reader::from_html(&html, None);
+
+        let after: String = reader.read("/html/body/p").unwrap();
+        assert_eq!(after, "After");
+    }
+
+    #[test]
+    fn foreign_content() {
+        let html = r##"<body><svg xmlns="http://www.w3.org/2000/svg" viewbox="0 0 10 10"><foreignObject><div>x</div></foreignObject><use xlink:href="#a"/></svg><p>After</p>"##;
+        let mut context = Context::new();
+        context.set_namespace("svg", SVG_NAMESPACE);
+        context.set_namespace("xlink", XLINK_NAMESPACE);
+        let reader = Reader::from_html(html, Some(&context));
+
+        let view_box: String = reader.read("//svg:svg/@viewBox").unwrap();
+        assert_eq!(view_box, "0 0 10 10");
+        let div: String = reader.read("//svg:foreignObject/div").unwrap();
+        assert_eq!(div, "x");
+        let href: String = reader.read("//svg:use/@xlink:href").unwrap();
+        assert_eq!(href, "#a");
+        let after: String = reader.read("/html/body/p").unwrap();
+        assert_eq!(after, "After");
+    }
+}
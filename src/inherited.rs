@@ -0,0 +1,166 @@
+// Copyright 2017-2019 Leonardo Schwarz <mail@leoschwarz.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Values inherited from ancestor elements.
+//!
+//! The `xml:lang` and `xml:base` attributes apply to the element they are
+//! declared on and all of its descendants. The types in this module resolve
+//! them for the anchor node. For other inheritable attributes see
+//! `Reader::inherited`.
+//!
+//! # Examples
+//! ```
+//! use xpath_reader::Reader;
+//! use xpath_reader::inherited::Lang;
+//!
+//! let xml = r#"<doc xml:lang="de-CH"><p>Grüezi</p><p xml:lang="">Hello</p></doc>"#;
+//! let reader = Reader::from_str(xml, None).unwrap();
+//!
+//! let lang: Lang = reader.read("//p[1]").unwrap();
+//! assert_eq!(lang.0, "de-CH");
+//! let lang: Option<Lang> = reader.read("//p[2]").unwrap();
+//! assert_eq!(lang, None);
+//! ```
+
+use node::XML_NAMESPACE;
+use reader::{FromXmlOptional, FromXmlResult, Reader};
+
+#[cfg(feature = "url")]
+use errors::Error;
+#[cfg(feature = "url")]
+use node;
+#[cfg(feature = "url")]
+use reader::FromXml;
+#[cfg(feature = "url")]
+use sxd_xpath::nodeset::Node;
+
+/// The language of the anchor node, as declared by the nearest `xml:lang`.
+///
+/// An empty `xml:lang` attribute undeclares the language, in which case
+/// the `Option<Lang>` is `None`.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Lang(pub String);
+
+impl FromXmlOptional for Lang {
+    fn from_xml_optional<'d>(reader: &'d Reader<'d>) -> FromXmlResult<Option<Self>> {
+        Ok(reader
+            .inherited((XML_NAMESPACE, "lang"))
+            .filter(|lang| !lang.is_empty())
+            .map(|lang| Lang(lang.to_string())))
+    }
+}
+
+/// The base URL of the anchor node, resolved from all `xml:base`
+/// attributes in scope.
+#[cfg(feature = "url")]
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct BaseUrl(pub ::url::Url);
+
+#[cfg(feature = "url")]
+impl FromXmlOptional for BaseUrl {
+    fn from_xml_optional<'d>(reader: &'d Reader<'d>) -> FromXmlResult<Option<Self>> {
+        match reader.anchor_node() {
+            Some(n) => base_url(n).map(|base| base.map(BaseUrl)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Relative URLs are resolved against the `xml:base` attributes in scope
+/// of the anchor node.
+#[cfg(feature = "url")]
+impl FromXmlOptional for ::url::Url {
+    fn from_xml_optional<'d>(reader: &'d Reader<'d>) -> FromXmlResult<Option<Self>> {
+        let s = match Option::<String>::from_xml(reader)? {
+            Some(s) => s,
+            None => return Ok(None),
+        };
+
+        let base = match reader.anchor_node() {
+            Some(n) => base_url(n)?,
+            None => None,
+        };
+        let url = match base {
+            Some(ref b) => b.join(s.trim()),
+            None => ::url::Url::parse(s.trim()),
+        };
+        url.map(Some).map_err(Error::custom_err)
+    }
+}
+
+#[cfg(feature = "url")]
+fn base_url(node: Node) -> Result<Option<::url::Url>, Error> {
+    let values = node::inherited_values(node, (XML_NAMESPACE, "base").into());
+    // Bases are listed innermost first. Bases outside the nearest absolute
+    // one do not matter, the others are resolved from the outside in.
+    let end = values
+        .iter()
+        .position(|value| ::url::Url::parse(value).is_ok())
+        .map_or(values.len(), |i| i + 1);
+    let mut base: Option<::url::Url> = None;
+    for value in values[..end].iter().rev() {
+        let url = match base {
+            Some(ref b) => b.join(value),
+            None => ::url::Url::parse(value),
+        };
+        base = Some(url.map_err(|e| Error::custom_err_msg(e, "Invalid xml:base"))?);
+    }
+    Ok(base)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lang_from_xml() {
+        let xml = r#"<?xml version="1.0"?>
+                     <doc xml:lang="en"><a><b/></a><c xml:lang="fr" d="1"/><e xml:lang=""/></doc>"#;
+        let reader = Reader::from_str(xml, None).unwrap();
+
+        let b: Lang = reader.read("//b").unwrap();
+        assert_eq!(b, Lang("en".to_string()));
+        let c: Lang = reader.read("//c/@d").unwrap();
+        assert_eq!(c, Lang("fr".to_string()));
+        let e: Option<Lang> = reader.read("//e").unwrap();
+        assert_eq!(e, None);
+        assert!(reader.read::<Lang, _>("//e").is_err());
+        let missing: Option<Lang> = reader.read("//missing").unwrap();
+        assert_eq!(missing, None);
+    }
+
+    #[cfg(feature = "url")]
+    #[test]
+    fn base_url_from_xml() {
+        let xml = r#"<?xml version="1.0"?>
+                     <doc xml:base="http://example.com/a/"><b xml:base="../b/"><c/></b><d/></doc>"#;
+        let reader = Reader::from_str(xml, None).unwrap();
+
+        let c: BaseUrl = reader.read("//c").unwrap();
+        assert_eq!(c.0.as_str(), "http://example.com/b/");
+        let d: BaseUrl = reader.read("//d").unwrap();
+        assert_eq!(d.0.as_str(), "http://example.com/a/");
+
+        let xml = r#"<doc xml:base="relative/"><a xml:base="http://example.com/a/">
+                     <b xml:base="b/"/></a><c/></doc>"#;
+        let reader = Reader::from_str(xml, None).unwrap();
+        let b: BaseUrl = reader.read("//b").unwrap();
+        assert_eq!(b.0.as_str(), "http://example.com/a/b/");
+        assert!(reader.read::<BaseUrl, _>("//c").is_err());
+
+        let reader = Reader::from_str("<doc/>", None).unwrap();
+        let none: Option<BaseUrl> = reader.read("//doc").unwrap();
+        assert_eq!(none, None);
+    }
+}
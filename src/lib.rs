@@ -38,16 +38,96 @@
 //! let tags: Vec<String> = reader.read("//b:tags/b:tag/@name").unwrap();
 //! assert_eq!(tags, vec!["cyberpunk".to_string(), "sci-fi".to_string()]);
 //! ```
+//!
+//! # Features
+//!
+//! `FromXml` implementations for some third party types can be enabled
+//! with the following cargo features:
+//!
+//! - `rust_decimal`: `rust_decimal::Decimal`
+//! - `semver`: `semver::Version`
+//! - `url`: `url::Url`, resolving relative URLs against `xml:base`, and
+//!   `inherited::BaseUrl`
+//! - `uuid`: `uuid::Uuid`
+//!
+//! The `html` feature enables `Reader::from_html` for lenient parsing of
+//! HTML, see the `html` module.
+//!
+//! The `gzip`, `zstd` and `bzip2` features enable decompressing input of
+//! `Reader::from_path` and `Reader::from_read`, see the `compression`
+//! module.
+//!
+//! The `zip` feature enables reading XML parts out of ZIP containers like
+//! Office Open XML, OpenDocument and EPUB files, see the `container`
+//! module.
+//!
+//! The `async` feature enables `Reader::from_async_read` and streaming
+//! records out of a `tokio::io::AsyncRead`, see the `stream` module.
+//!
+//! The crate requires Rust 1.82 or later. The `zip` feature requires
+//! Rust 1.88. With the latest releases of their dependencies, the `url`
+//! feature requires Rust 1.88 and the `uuid` feature Rust 1.89; older
+//! releases like url 2.5.2 and uuid 1.20 build with Rust 1.82 and can be
+//! selected with `cargo update --precise`.
 
 #![warn(missing_docs)]
 
 extern crate sxd_document;
 extern crate sxd_xpath;
+extern crate unicode_general_category;
+
+#[cfg(feature = "bzip2")]
+extern crate bzip2;
+#[cfg(feature = "html")]
+extern crate entities;
+#[cfg(feature = "gzip")]
+extern crate flate2;
+#[cfg(feature = "async")]
+extern crate futures_core;
+#[cfg(feature = "rust_decimal")]
+extern crate rust_decimal;
+#[cfg(feature = "semver")]
+extern crate semver;
+#[cfg(feature = "async")]
+extern crate tokio;
+#[cfg(feature = "url")]
+extern crate url;
+#[cfg(feature = "uuid")]
+extern crate uuid;
+#[cfg(feature = "zip")]
+extern crate zip;
+#[cfg(feature = "zstd")]
+extern crate zstd;
 
+pub mod binary;
+pub mod budget;
+pub mod c14n;
+pub mod compression;
+#[cfg(feature = "zip")]
+pub mod container;
+pub mod diff;
+pub mod dtd;
+pub mod edit;
 mod errors;
 pub mod expression;
+#[cfg(feature = "html")]
+pub mod html;
+pub mod inherited;
+pub mod limits;
+pub mod localized;
+pub mod node;
+mod pattern;
+pub mod raw;
 pub mod reader;
+pub mod recover;
+pub mod rules;
+#[cfg(feature = "async")]
+pub mod stream;
+mod strict;
+pub mod text;
 mod util;
+pub mod writer;
+pub mod xsd;
 pub use self::errors::{Error, ErrorKind};
 pub use self::reader::{FromXml, FromXmlOptional, FromXmlResult, Reader};
 // TODO: Replace the documentation of Context with an example for xpath_reader.
@@ -0,0 +1,347 @@
+// Copyright 2017-2019 Leonardo Schwarz <mail@leoschwarz.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Limits for parsing untrusted documents.
+//!
+//! The limits are not enforced by the parser itself. Instead the markup is
+//! checked by a separate scan of the text, which does not allocate per node,
+//! before the document tree is built, so deeply nested or huge documents are
+//! rejected before they can exhaust memory. Violations are reported with
+//! `ErrorKind::LimitExceeded`.
+//!
+//! The scan only tokenises as much as needed for counting: it skips
+//! comments, processing instructions, CDATA sections and quoted attribute
+//! values like the parser, and counts an attribute for every `=` outside of
+//! quotes in a start tag. Where it disagrees with the parser on malformed
+//! markup it errs towards counting more, and markup it cannot tokenise is
+//! left to the parser to reject.
+//!
+//! Only the constructors taking limits apply them: `Reader::from_str` and
+//! `Reader::from_str_recovering` parse without any limits, so use
+//! `Reader::from_str_with_limits` for untrusted input.
+//!
+//! `Reader::from_str_with_dtd` always parses within limits, defaulting to
+//! `Limits::default()`, as expanding entities can grow a document
+//! exponentially.
+//!
+//! # Examples
+//! ```
+//! use xpath_reader::{ErrorKind, Reader};
+//! use xpath_reader::limits::Limits;
+//!
+//! let limits = Limits::default().max_depth(2);
+//! let reader = Reader::from_str_with_limits("<a><b/></a>", None, &limits);
+//! assert!(reader.is_ok());
+//!
+//! let error = Reader::from_str_with_limits("<a><b><c/></b></a>", None, &limits)
+//!     .err()
+//!     .unwrap();
+//! assert_eq!(error.kind(), ErrorKind::LimitExceeded);
+//! ```
+
+use errors::{Error, ErrorKind};
+
+/// Expansions below this size are not restricted by the expansion ratio.
+const MIN_EXPANSION_LIMIT: usize = 64 * 1024;
+
+/// Limits enforced while parsing a document.
+///
+/// The defaults are generous for regular documents, use `unlimited` and
+/// the setters to configure individual limits.
+#[derive(Clone, Debug)]
+pub struct Limits {
+    max_input_size: Option<usize>,
+    max_depth: Option<usize>,
+    max_attributes: Option<usize>,
+    max_nodes: Option<usize>,
+    max_entity_expansion: Option<usize>,
+    max_text_length: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_input_size: Some(64 * 1024 * 1024),
+            max_depth: Some(256),
+            max_attributes: Some(256),
+            max_nodes: Some(4_000_000),
+            max_entity_expansion: Some(10),
+            max_text_length: Some(16 * 1024 * 1024),
+        }
+    }
+}
+
+impl Limits {
+    /// Creates limits which do not restrict anything.
+    pub fn unlimited() -> Self {
+        Limits {
+            max_input_size: None,
+            max_depth: None,
+            max_attributes: None,
+            max_nodes: None,
+            max_entity_expansion: None,
+            max_text_length: None,
+        }
+    }
+
+    /// Sets the maximum size of the input in bytes.
+    pub fn max_input_size(mut self, bytes: usize) -> Self {
+        self.max_input_size = Some(bytes);
+        self
+    }
+
+    /// Sets the maximum nesting depth of elements, the root element having
+    /// depth 1.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Sets the maximum number of attributes, including namespace
+    /// declarations, of a single element.
+    pub fn max_attributes(mut self, attributes: usize) -> Self {
+        self.max_attributes = Some(attributes);
+        self
+    }
+
+    /// Sets the maximum total number of elements, attributes, text nodes,
+    /// comments and processing instructions.
+    pub fn max_nodes(mut self, nodes: usize) -> Self {
+        self.max_nodes = Some(nodes);
+        self
+    }
+
+    /// Sets the maximum factor by which expanding the entities declared in
+    /// the document type declaration may grow the document.
+    ///
+    /// Documents expanding to less than 64 KiB are always accepted. This
+    /// limit only applies to `Reader::from_str_with_dtd`, which uses
+    /// `Limits::default()` unless `DtdOptions::limits` is set. The other
+    /// constructors do not expand entities, so it has no effect on
+    /// `Reader::from_str_with_limits`.
+    pub fn max_entity_expansion(mut self, ratio: usize) -> Self {
+        self.max_entity_expansion = Some(ratio);
+        self
+    }
+
+    /// Sets the maximum length in bytes of a single text node or CDATA
+    /// section.
+    pub fn max_text_length(mut self, bytes: usize) -> Self {
+        self.max_text_length = Some(bytes);
+        self
+    }
+
+    /// Checks the size of the input of `bytes` bytes.
+    pub(crate) fn check_input_size(&self, bytes: usize) -> Result<(), Error> {
+        check("input size", bytes, self.max_input_size)
+    }
+
+    /// The number of bytes to read from a stream so that exceeding the
+    /// maximum input size is detected.
+    pub(crate) fn read_limit(&self) -> u64 {
+        self.max_input_size
+            .map_or(u64::MAX, |max| (max as u64).saturating_add(1))
+    }
+
+    /// The maximum size the document of size `input` may have after
+    /// expanding entities.
+    pub(crate) fn max_expanded_size(&self, input: usize) -> Option<usize> {
+        self.max_entity_expansion
+            .map(|ratio| input.saturating_mul(ratio).max(MIN_EXPANSION_LIMIT))
+    }
+
+    /// Checks the structure of `xml`, leaving syntax errors to the parser.
+    pub(crate) fn check_markup(&self, xml: &str) -> Result<(), Error> {
+        let mut depth = 0;
+        let mut nodes = 0;
+        let mut rest = xml;
+        while !rest.is_empty() {
+            let text_end = rest.find('<').unwrap_or(rest.len());
+            if !rest[..text_end].is_empty() {
+                check("text length", text_end, self.max_text_length)?;
+                nodes += 1;
+            }
+            rest = &rest[text_end..];
+            if rest.is_empty() {
+                break;
+            }
+
+            let end = if rest.starts_with("<!--") {
+                nodes += 1;
+                rest.find("-->").map(|e| e + 3)
+            } else if rest.starts_with("<![CDATA[") {
+                nodes += 1;
+                rest.find("]]>")
+                    .map(|e| check("text length", e - 9, self.max_text_length).map(|_| e + 3))
+                    .transpose()?
+            } else if rest.starts_with("<?") {
+                nodes += 1;
+                rest.find("?>").map(|e| e + 2)
+            } else if rest.starts_with("<!") {
+                declaration_end(rest)
+            } else if rest.starts_with("</") {
+                depth = usize::saturating_sub(depth, 1);
+                rest.find('>').map(|e| e + 1)
+            } else {
+                let (end, attributes, empty) = match start_tag(rest) {
+                    Some(tag) => tag,
+                    None => break,
+                };
+                check("attribute count", attributes, self.max_attributes)?;
+                nodes += 1 + attributes;
+                check("element depth", depth + 1, self.max_depth)?;
+                if !empty {
+                    depth += 1;
+                }
+                Some(end)
+            };
+            check("node count", nodes, self.max_nodes)?;
+            match end {
+                Some(end) => rest = &rest[end..],
+                None => break,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check(what: &str, value: usize, limit: Option<usize>) -> Result<(), Error> {
+    match limit {
+        Some(limit) if value > limit => Err(Error::internal(
+            format!("The {} of {} exceeds the limit of {}.", what, value, limit),
+            ErrorKind::LimitExceeded,
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Returns the end of a start tag, its number of attributes and whether it
+/// is an empty element tag.
+fn start_tag(tag: &str) -> Option<(usize, usize, bool)> {
+    let mut quote = None;
+    let mut attributes = 0;
+    for (i, c) in tag.char_indices() {
+        match (quote, c) {
+            (Some(q), _) if q == c => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '=') => attributes += 1,
+            (None, '>') => return Some((i + 1, attributes, tag[..i].ends_with('/'))),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Returns the end of a document type declaration, skipping its internal
+/// subset including the comments and processing instructions in it.
+fn declaration_end(declaration: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut brackets = 0;
+    let mut skip = 0;
+    for (i, c) in declaration.char_indices() {
+        if i < skip {
+            continue;
+        }
+        match (quote, c) {
+            (Some(q), _) if q == c => quote = None,
+            (Some(_), _) => {}
+            (None, '<') if declaration[i..].starts_with("<!--") => {
+                skip = i + declaration[i..].find("-->")? + 3;
+            }
+            (None, '<') if declaration[i..].starts_with("<?") => {
+                skip = i + declaration[i..].find("?>")? + 2;
+            }
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '[') => brackets += 1,
+            (None, ']') => brackets -= 1,
+            (None, '>') if brackets == 0 => return Some(i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind(limits: &Limits, xml: &str) -> Option<ErrorKind> {
+        limits.check_markup(xml).err().map(|e| e.kind())
+    }
+
+    #[test]
+    fn markup_limits() {
+        let limits = Limits::unlimited()
+            .max_depth(2)
+            .max_attributes(2)
+            .max_nodes(8)
+            .max_text_length(5);
+        let exceeded = Some(ErrorKind::LimitExceeded);
+
+        assert_eq!(kind(&limits, r#"<a x="1" y='>'><b/><b>text</b></a>"#), None);
+        assert_eq!(kind(&limits, "<a><b><c/></b></a>"), exceeded);
+        assert_eq!(
+            kind(&limits, "<a><b/><b/><b/><b/><b/><b/><b/><b/></a>"),
+            exceeded
+        );
+        assert_eq!(kind(&limits, r#"<a x="1" y="2" z="3"/>"#), exceeded);
+        assert_eq!(kind(&limits, "<a>long text</a>"), exceeded);
+        assert_eq!(kind(&limits, "<a><![CDATA[long text]]></a>"), exceeded);
+        assert_eq!(
+            kind(
+                &limits,
+                "<!DOCTYPE a [<!ELEMENT a ANY>]><!-- <b><c> --><a/>"
+            ),
+            None
+        );
+        // Quotes and markup in comments and processing instructions neither
+        // hide nor add elements and attributes.
+        assert_eq!(
+            kind(&limits, r#"<a><!-- x="1" y="2" z="3" > ' --><b/></a>"#),
+            None
+        );
+        assert_eq!(
+            kind(&limits, r#"<a><!-- x="1" ' > --><b><c/></b></a>"#),
+            exceeded
+        );
+        assert_eq!(
+            kind(&limits, r#"<?p a=" ?><a x="1" y="2" z="3"/><?q "?>"#),
+            exceeded
+        );
+        assert_eq!(
+            kind(&limits, r#"<a y='=>'><?p ' > ?><b><c/></b></a>"#),
+            exceeded
+        );
+        assert_eq!(
+            kind(&limits, "<!DOCTYPE a [<!-- ' ] -->]><a><b><c/></b></a>"),
+            exceeded
+        );
+        assert_eq!(
+            kind(&limits, "<!DOCTYPE a [<?p \" ?>]><a x='1' y='2' z='3'/>"),
+            exceeded
+        );
+        // Syntax errors are left to the parser.
+        assert_eq!(kind(&limits, "<a><b"), None);
+    }
+
+    #[test]
+    fn expansion() {
+        let limits = Limits::default();
+        assert_eq!(limits.max_expanded_size(100), Some(MIN_EXPANSION_LIMIT));
+        assert_eq!(limits.max_expanded_size(1_000_000), Some(10_000_000));
+        assert_eq!(Limits::unlimited().max_expanded_size(100), None);
+        assert!(limits.max_input_size(3).check_input_size(4).is_err());
+    }
+}
@@ -0,0 +1,228 @@
+// Copyright 2017-2019 Leonardo Schwarz <mail@leoschwarz.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Multilingual values selected by `xml:lang`.
+//!
+//! Documents often contain the same element multiple times, once for each
+//! language. `Localized<T>` reads all of these variants, while
+//! `Reader::set_preferred_languages` makes plain `String` reads pick the
+//! best matching variant.
+//!
+//! # Examples
+//! ```
+//! use xpath_reader::Reader;
+//! use xpath_reader::localized::Localized;
+//!
+//! let xml = r#"<book>
+//!     <title>Untitled</title>
+//!     <title xml:lang="en">Hello</title>
+//!     <title xml:lang="de">Hallo</title>
+//! </book>"#;
+//! let reader = Reader::from_str(xml, None).unwrap();
+//!
+//! let title: Localized = reader.read("//title").unwrap();
+//! assert_eq!(title.get("EN"), Some(&"Hello".to_string()));
+//! assert_eq!(title.best(&["de-CH"]), Some(&"Hallo".to_string()));
+//! assert_eq!(title.best(&["fr"]), Some(&"Untitled".to_string()));
+//! ```
+
+use node::{self, XML_NAMESPACE};
+use reader::{FromXml, FromXmlResult, Reader};
+use std::collections::BTreeMap;
+
+/// All language variants of a value, keyed by their `xml:lang` tag.
+///
+/// Values without language are stored with an empty tag. If multiple
+/// values have the same tag, the first one in document order is kept.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Localized<T = String>(pub BTreeMap<String, T>);
+
+impl<T> Default for Localized<T> {
+    fn default() -> Self {
+        Localized(BTreeMap::new())
+    }
+}
+
+impl<T> Localized<T> {
+    /// Returns the value for the language tag `lang`, compared case
+    /// insensitively and without fallback.
+    pub fn get(&self, lang: &str) -> Option<&T> {
+        self.0
+            .iter()
+            .find(|&(tag, _)| tag.eq_ignore_ascii_case(lang))
+            .map(|(_, value)| value)
+    }
+
+    /// Returns the value without language tag.
+    pub fn untagged(&self) -> Option<&T> {
+        self.0.get("")
+    }
+
+    /// Returns the value best matching the preferred languages, most
+    /// preferred first.
+    ///
+    /// Language tags are matched with the lookup scheme of RFC 4647, i.e.
+    /// `de-CH` falls back to `de`, and finally the untagged value is used.
+    pub fn best<S: AsRef<str>>(&self, preferred: &[S]) -> Option<&T> {
+        let tags: Vec<Option<&str>> = self
+            .0
+            .keys()
+            .map(|tag| {
+                if tag.is_empty() {
+                    None
+                } else {
+                    Some(tag.as_str())
+                }
+            })
+            .collect();
+        lookup(preferred, &tags).and_then(|i| self.0.values().nth(i))
+    }
+
+    /// Returns the number of language variants.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns true if there are no language variants.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<T> FromXml for Localized<T>
+where
+    T: FromXml,
+{
+    fn from_xml<'d>(reader: &'d Reader<'d>) -> FromXmlResult<Self> {
+        let mut values = BTreeMap::new();
+        for n in reader.anchor_nodeset().document_order() {
+            let tag = node::inherited_values(n, (XML_NAMESPACE, "lang").into())
+                .into_iter()
+                .next()
+                .unwrap_or("");
+            if values.contains_key(tag) {
+                continue;
+            }
+            let value = T::from_xml(&reader.with_nodes(Some(n)))?;
+            values.insert(tag.to_string(), value);
+        }
+        Ok(Localized(values))
+    }
+}
+
+/// Finds the index of the tag best matching the preferred languages,
+/// following the lookup scheme of RFC 4647 and falling back to the first
+/// untagged entry.
+pub(crate) fn lookup<S: AsRef<str>>(preferred: &[S], tags: &[Option<&str>]) -> Option<usize> {
+    for lang in preferred {
+        let mut range = lang.as_ref();
+        loop {
+            let found = tags.iter().position(|tag| match *tag {
+                Some(tag) => tag.eq_ignore_ascii_case(range),
+                None => false,
+            });
+            if found.is_some() {
+                return found;
+            }
+            range = match truncate(range) {
+                Some(r) => r,
+                None => break,
+            };
+        }
+    }
+    tags.iter().position(Option::is_none)
+}
+
+/// Removes the last subtag of a language range, including a preceding
+/// single character subtag (e.g. the `x` of private use subtags).
+fn truncate(range: &str) -> Option<&str> {
+    let mut truncated = &range[..range.rfind('-')?];
+    if let Some(pos) = truncated.rfind('-') {
+        if truncated.len() - pos == 2 {
+            truncated = &truncated[..pos];
+        }
+    }
+    Some(truncated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_fallback() {
+        let tags = [Some("en"), Some("de"), None, Some("de-CH-x-zh")];
+        assert_eq!(lookup(&["de-CH"], &tags), Some(1));
+        assert_eq!(lookup(&["DE-ch-X-ZH"], &tags), Some(3));
+        assert_eq!(lookup(&["fr", "en-US"], &tags), Some(0));
+        assert_eq!(lookup(&["fr"], &tags), Some(2));
+        assert_eq!(lookup(&["fr"], &tags[..2]), None);
+        assert_eq!(lookup::<&str>(&[], &tags), Some(2));
+        assert_eq!(truncate("zh-Hant-CN-x-private"), Some("zh-Hant-CN"));
+        assert_eq!(truncate("en"), None);
+    }
+
+    #[test]
+    fn localized_from_xml() {
+        let xml = r#"<?xml version="1.0"?>
+                     <catalog xml:lang="en">
+                       <item><title>Chair</title><title xml:lang="de">Stuhl</title></item>
+                       <item><title xml:lang="de-CH">Stuel</title><title xml:lang="de">Stuhl</title></item>
+                     </catalog>"#;
+        let reader = Reader::from_str(xml, None).unwrap();
+
+        let first: Localized = reader.read("//item[1]/title").unwrap();
+        assert_eq!(first.len(), 2);
+        assert_eq!(first.get("en"), Some(&"Chair".to_string()));
+        assert_eq!(first.untagged(), None);
+        assert_eq!(first.best(&["de-AT"]), Some(&"Stuhl".to_string()));
+
+        let second: Localized = reader.read("//item[2]/title").unwrap();
+        assert_eq!(second.best(&["de-CH"]), Some(&"Stuel".to_string()));
+        assert_eq!(second.best(&["en"]), None);
+
+        let missing: Localized = reader.read("//missing").unwrap();
+        assert!(missing.is_empty());
+    }
+
+    #[test]
+    fn preferred_languages() {
+        let xml = r#"<?xml version="1.0"?>
+                     <book>
+                       <title>Untitled</title>
+                       <title xml:lang="en">Hello</title>
+                       <title xml:lang="de">Hallo</title>
+                       <price xml:lang="de">10</price><price xml:lang="fr">12</price>
+                     </book>"#;
+        let mut reader = Reader::from_str(xml, None).unwrap();
+
+        let title: String = reader.read("//title").unwrap();
+        assert_eq!(title, "Untitled");
+
+        reader.set_preferred_languages(vec!["de-CH", "en"]);
+        let title: String = reader.read("//title").unwrap();
+        assert_eq!(title, "Hallo");
+
+        reader.set_preferred_languages(vec!["fr"]);
+        let title: Option<String> = reader.read("//title").unwrap();
+        assert_eq!(title, Some("Untitled".to_string()));
+        let price: u32 = reader.read("//price").unwrap();
+        assert_eq!(price, 12);
+
+        let book = reader.with_nodeset_eval("/book").unwrap();
+        let prices: Vec<u32> = book.read("price").unwrap();
+        assert_eq!(prices, vec![10, 12]);
+        assert_eq!(book.preferred_languages(), &["fr".to_string()]);
+    }
+}
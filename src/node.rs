@@ -0,0 +1,200 @@
+// Copyright 2017-2019 Leonardo Schwarz <mail@leoschwarz.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Node metadata.
+//!
+//! Helpers to inspect nodes without matching on the variants of
+//! `sxd_xpath::nodeset::Node`.
+
+use sxd_document::QName;
+use sxd_xpath::nodeset::Node;
+
+/// The namespace URI bound to the `xml` prefix, e.g. for `xml:lang`.
+pub const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// The kind of a node in the document tree.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum NodeKind {
+    /// The document root.
+    Root,
+    /// An element.
+    Element,
+    /// An attribute of an element.
+    Attribute,
+    /// A text node.
+    Text,
+    /// A comment.
+    Comment,
+    /// A processing instruction.
+    ProcessingInstruction,
+    /// A namespace node.
+    Namespace,
+}
+
+impl NodeKind {
+    /// Returns the kind of `node`.
+    pub fn of(node: Node) -> Self {
+        match node {
+            Node::Root(_) => NodeKind::Root,
+            Node::Element(_) => NodeKind::Element,
+            Node::Attribute(_) => NodeKind::Attribute,
+            Node::Text(_) => NodeKind::Text,
+            Node::Comment(_) => NodeKind::Comment,
+            Node::ProcessingInstruction(_) => NodeKind::ProcessingInstruction,
+            Node::Namespace(_) => NodeKind::Namespace,
+        }
+    }
+}
+
+/// The local part of the name of elements and attributes, the target of
+/// processing instructions and the prefix of namespace nodes.
+pub(crate) fn local_name<'d>(node: Node<'d>) -> Option<&'d str> {
+    match node {
+        Node::Element(e) => Some(e.name().local_part()),
+        Node::Attribute(a) => Some(a.name().local_part()),
+        Node::ProcessingInstruction(pi) => Some(pi.target()),
+        Node::Namespace(ns) => Some(ns.prefix()),
+        _ => None,
+    }
+}
+
+pub(crate) fn namespace_uri<'d>(node: Node<'d>) -> Option<&'d str> {
+    match node {
+        Node::Element(e) => e.name().namespace_uri(),
+        Node::Attribute(a) => a.name().namespace_uri(),
+        _ => None,
+    }
+}
+
+/// The prefix bound to the namespace of an element or attribute.
+pub(crate) fn prefix<'d>(node: Node<'d>) -> Option<&'d str> {
+    let (element, uri, preferred) = match node {
+        Node::Element(e) => (e, e.name().namespace_uri()?, e.preferred_prefix()),
+        Node::Attribute(a) => (a.parent()?, a.name().namespace_uri()?, a.preferred_prefix()),
+        _ => return None,
+    };
+    if let Node::Element(_) = node {
+        // Elements in the default namespace have no prefix.
+        if preferred.is_none() && element.recursive_default_namespace_uri() == Some(uri) {
+            return None;
+        }
+    }
+    element.prefix_for_namespace_uri(uri, preferred)
+}
+
+/// The name of an element or attribute as it would appear in the document.
+pub(crate) fn qualified_name(node: Node) -> Option<String> {
+    let local = local_name(node)?;
+    Some(match prefix(node) {
+        Some(p) => format!("{}:{}", p, local),
+        None => local.to_string(),
+    })
+}
+
+/// The values of the attribute `name` on the element `node` (or the element
+/// of an attribute node) and its ancestors, innermost first.
+pub(crate) fn inherited_values<'d>(node: Node<'d>, name: QName) -> Vec<&'d str> {
+    let mut values = Vec::new();
+    let mut current = match node {
+        Node::Attribute(a) => a.parent().map(Node::Element),
+        _ => Some(node),
+    };
+    while let Some(n) = current {
+        if let Node::Element(e) = n {
+            if let Some(value) = e.attribute_value(name) {
+                values.push(value);
+            }
+        }
+        current = n.parent();
+    }
+    values
+}
+
+/// The index of `node` in document order, the root having index 0.
+///
+/// Attributes of an element are ordered directly after the element and
+/// before its children. Namespace nodes are not counted.
+pub(crate) fn document_position(node: Node) -> Option<usize> {
+    let mut stack: Vec<Node> = vec![node.document().root().into()];
+    let mut idx = 0;
+    while let Some(n) = stack.pop() {
+        if n == node {
+            return Some(idx);
+        }
+        idx += 1;
+        stack.extend(n.children().into_iter().rev());
+        if let Node::Element(e) = n {
+            stack.extend(e.attributes().into_iter().rev().map(Node::Attribute));
+        }
+    }
+    None
+}
+
+/// A location path uniquely identifying `node` in its document, intended
+/// for diagnostics, e.g. `/catalog[1]/item[3]/@id`.
+///
+/// Elements are written with their qualified name as used in the document,
+/// so the path can only be evaluated if the same prefixes are registered in
+/// the evaluation context.
+pub(crate) fn node_path(node: Node) -> String {
+    let mut steps = Vec::new();
+    let mut current = node;
+    loop {
+        let step = match current {
+            Node::Root(_) => break,
+            Node::Attribute(_) => format!("@{}", qualified_name(current).unwrap_or_default()),
+            Node::Namespace(ns) => format!("namespace::{}", ns.prefix()),
+            _ => {
+                let (test, same) = step_test(current);
+                let index = current
+                    .preceding_siblings()
+                    .into_iter()
+                    .filter(|sibling| same(*sibling, current))
+                    .count()
+                    + 1;
+                format!("{}[{}]", test, index)
+            }
+        };
+        steps.push(step);
+        current = match current.parent() {
+            Some(parent) => parent,
+            None => break,
+        };
+    }
+
+    if steps.is_empty() {
+        return "/".to_string();
+    }
+    steps.reverse();
+    format!("/{}", steps.join("/"))
+}
+
+type SameTest = fn(Node, Node) -> bool;
+
+fn step_test(node: Node) -> (String, SameTest) {
+    match node {
+        Node::Element(_) => (qualified_name(node).unwrap_or_default(), |a, b| {
+            NodeKind::of(a) == NodeKind::Element && a.expanded_name() == b.expanded_name()
+        }),
+        Node::Text(_) => ("text()".to_string(), |a, _| {
+            NodeKind::of(a) == NodeKind::Text
+        }),
+        Node::Comment(_) => ("comment()".to_string(), |a, _| {
+            NodeKind::of(a) == NodeKind::Comment
+        }),
+        _ => ("processing-instruction()".to_string(), |a, _| {
+            NodeKind::of(a) == NodeKind::ProcessingInstruction
+        }),
+    }
+}
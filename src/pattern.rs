@@ -0,0 +1,587 @@
+// Copyright 2017-2019 Leonardo Schwarz <mail@leoschwarz.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Regular expressions of XML Schema `pattern` facets.
+//!
+//! Supports branches, groups, quantifiers, character classes with ranges
+//! and negation, the multi character escapes and the general categories of
+//! `\p{..}`. Patterns always match the complete value. Character class
+//! subtraction and Unicode block escapes are not supported and rejected.
+//!
+//! The categories are looked up in the Unicode 16 character database. `\i`
+//! and `\c` match the name characters of XML 1.0 (Fifth Edition), as in
+//! XML Schema 1.1, rather than the older tables of XML Schema 1.0.
+//!
+//! Patterns are compiled to a nondeterministic automaton which is simulated
+//! on all paths at once, so matching takes time linear in the length of the
+//! value and does not recurse.
+
+use errors::Error;
+use std::mem;
+use unicode_general_category::get_general_category;
+
+/// The maximum number of instructions of a compiled pattern, bounding the
+/// expansion of counted repetitions.
+const MAX_PROGRAM: usize = 100_000;
+
+/// A compiled pattern.
+#[derive(Clone, Debug)]
+pub(crate) struct Pattern {
+    source: String,
+    program: Vec<Inst>,
+}
+
+#[derive(Clone, Debug)]
+struct Alternation(Vec<Sequence>);
+
+#[derive(Clone, Debug)]
+struct Sequence(Vec<Piece>);
+
+#[derive(Clone, Debug)]
+struct Piece {
+    atom: Atom,
+    min: usize,
+    max: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+enum Atom {
+    Class(Class),
+    Group(Alternation),
+}
+
+#[derive(Clone, Debug)]
+struct Class {
+    negated: bool,
+    items: Vec<Item>,
+}
+
+#[derive(Clone, Debug)]
+enum Item {
+    Range(char, char),
+    /// A multi character escape like `\d`, uppercase letters negating.
+    Escape(char),
+    /// A general category of `\p{..}` or `\P{..}`.
+    Category(String, bool),
+    /// The wildcard `.`.
+    Any,
+}
+
+impl Pattern {
+    pub(crate) fn new(source: &str) -> Result<Self, Error> {
+        let mut parser = Parser {
+            chars: source.chars().collect(),
+            pos: 0,
+        };
+        let regex = parser.alternation()?;
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("unbalanced parenthesis"));
+        }
+        let mut compiler = Compiler {
+            program: Vec::new(),
+        };
+        compiler
+            .alternation(&regex)
+            .and_then(|_| compiler.push(Inst::Match))
+            .map_err(|_| parser.error("pattern is too large"))?;
+        Ok(Pattern {
+            source: source.to_string(),
+            program: compiler.program,
+        })
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns true if the pattern matches the complete `value`.
+    pub(crate) fn is_match(&self, value: &str) -> bool {
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+        let mut stack = Vec::new();
+        current.add(&self.program, 0, &mut stack);
+        for c in value.chars() {
+            next.clear();
+            for &pc in &current.list {
+                if let Inst::Class(ref class) = self.program[pc] {
+                    if class.matches(c) {
+                        next.add(&self.program, pc + 1, &mut stack);
+                    }
+                }
+            }
+            mem::swap(&mut current, &mut next);
+            if current.list.is_empty() {
+                return false;
+            }
+        }
+        current
+            .list
+            .iter()
+            .any(|&pc| matches!(self.program[pc], Inst::Match))
+    }
+}
+
+/// An instruction of a compiled pattern.
+#[derive(Clone, Debug)]
+enum Inst {
+    /// Consumes a character of the class.
+    Class(Class),
+    /// Continues at both instructions.
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+struct Compiler {
+    program: Vec<Inst>,
+}
+
+/// The program exceeded `MAX_PROGRAM` instructions.
+struct TooLarge;
+
+impl Compiler {
+    fn push(&mut self, inst: Inst) -> Result<usize, TooLarge> {
+        if self.program.len() >= MAX_PROGRAM {
+            return Err(TooLarge);
+        }
+        self.program.push(inst);
+        Ok(self.program.len() - 1)
+    }
+
+    fn alternation(&mut self, alternation: &Alternation) -> Result<(), TooLarge> {
+        let (last, branches) = alternation.0.split_last().unwrap();
+        let mut jumps = Vec::new();
+        for sequence in branches {
+            let split = self.push(Inst::Split(0, 0))?;
+            self.sequence(sequence)?;
+            jumps.push(self.push(Inst::Jump(0))?);
+            self.program[split] = Inst::Split(split + 1, self.program.len());
+        }
+        self.sequence(last)?;
+        for jump in jumps {
+            self.program[jump] = Inst::Jump(self.program.len());
+        }
+        Ok(())
+    }
+
+    fn sequence(&mut self, sequence: &Sequence) -> Result<(), TooLarge> {
+        sequence.0.iter().try_for_each(|piece| self.piece(piece))
+    }
+
+    fn piece(&mut self, piece: &Piece) -> Result<(), TooLarge> {
+        // Bounds the work for atoms which compile to no instructions.
+        if piece.min > MAX_PROGRAM || piece.max.is_some_and(|max| max > MAX_PROGRAM) {
+            return Err(TooLarge);
+        }
+        for _ in 0..piece.min {
+            self.atom(&piece.atom)?;
+        }
+        match piece.max {
+            None => {
+                let split = self.push(Inst::Split(0, 0))?;
+                self.atom(&piece.atom)?;
+                self.push(Inst::Jump(split))?;
+                self.program[split] = Inst::Split(split + 1, self.program.len());
+            }
+            Some(max) => {
+                let mut splits = Vec::new();
+                for _ in piece.min..max {
+                    splits.push(self.push(Inst::Split(0, 0))?);
+                    self.atom(&piece.atom)?;
+                }
+                for split in splits {
+                    self.program[split] = Inst::Split(split + 1, self.program.len());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn atom(&mut self, atom: &Atom) -> Result<(), TooLarge> {
+        match *atom {
+            Atom::Class(ref class) => self.push(Inst::Class(class.clone())).map(|_| ()),
+            Atom::Group(ref group) => self.alternation(group),
+        }
+    }
+}
+
+/// The set of instructions the simulation is at.
+struct Threads {
+    /// The `Class` and `Match` instructions, in insertion order.
+    list: Vec<usize>,
+    seen: Vec<bool>,
+}
+
+impl Threads {
+    fn new(len: usize) -> Self {
+        Threads {
+            list: Vec::new(),
+            seen: vec![false; len],
+        }
+    }
+
+    fn clear(&mut self) {
+        self.list.clear();
+        self.seen.iter_mut().for_each(|seen| *seen = false);
+    }
+
+    /// Adds `pc` and the instructions reachable from it without consuming
+    /// a character.
+    fn add(&mut self, program: &[Inst], pc: usize, stack: &mut Vec<usize>) {
+        stack.push(pc);
+        while let Some(pc) = stack.pop() {
+            if mem::replace(&mut self.seen[pc], true) {
+                continue;
+            }
+            match program[pc] {
+                Inst::Jump(to) => stack.push(to),
+                Inst::Split(first, second) => {
+                    stack.push(second);
+                    stack.push(first);
+                }
+                Inst::Class(_) | Inst::Match => self.list.push(pc),
+            }
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn error(&self, msg: &str) -> Error {
+        let source: String = self.chars.iter().collect();
+        Error::custom_msg(format!(
+            "Invalid pattern '{}' at {}: {}",
+            source, self.pos, msg
+        ))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn next(&mut self) -> Result<char, Error> {
+        let c = self
+            .peek()
+            .ok_or_else(|| self.error("unexpected end of pattern"))?;
+        self.pos += 1;
+        Ok(c)
+    }
+
+    fn alternation(&mut self) -> Result<Alternation, Error> {
+        let mut branches = vec![self.sequence()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            branches.push(self.sequence()?);
+        }
+        Ok(Alternation(branches))
+    }
+
+    fn sequence(&mut self) -> Result<Sequence, Error> {
+        let mut pieces = Vec::new();
+        while let Some(c) = self.peek() {
+            let atom = match c {
+                '|' | ')' => break,
+                '(' => {
+                    self.pos += 1;
+                    let group = self.alternation()?;
+                    if self.next()? != ')' {
+                        return Err(self.error("expected ')'"));
+                    }
+                    Atom::Group(group)
+                }
+                '[' => {
+                    self.pos += 1;
+                    Atom::Class(self.class()?)
+                }
+                '.' => {
+                    self.pos += 1;
+                    Atom::Class(Class::single(Item::Any))
+                }
+                '\\' => {
+                    self.pos += 1;
+                    Atom::Class(Class::single(self.escape()?))
+                }
+                '*' | '+' | '?' | '{' | ']' => return Err(self.error("unexpected character")),
+                c => {
+                    self.pos += 1;
+                    Atom::Class(Class::single(Item::Range(c, c)))
+                }
+            };
+            let (min, max) = self.quantifier()?;
+            pieces.push(Piece { atom, min, max });
+        }
+        Ok(Sequence(pieces))
+    }
+
+    fn quantifier(&mut self) -> Result<(usize, Option<usize>), Error> {
+        let quantifier = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => {
+                self.pos += 1;
+                let min = self.number()?;
+                let max = if self.peek() == Some(',') {
+                    self.pos += 1;
+                    if self.peek() == Some('}') {
+                        None
+                    } else {
+                        Some(self.number()?)
+                    }
+                } else {
+                    Some(min)
+                };
+                if self.peek() != Some('}') || max.is_some_and(|max| max < min) {
+                    return Err(self.error("invalid quantity"));
+                }
+                (min, max)
+            }
+            _ => return Ok((1, Some(1))),
+        };
+        self.pos += 1;
+        Ok(quantifier)
+    }
+
+    fn number(&mut self) -> Result<usize, Error> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        digits.parse().map_err(|_| self.error("expected a number"))
+    }
+
+    fn class(&mut self) -> Result<Class, Error> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut items = Vec::new();
+        loop {
+            let c = self.next()?;
+            let item = match c {
+                ']' if !items.is_empty() => break,
+                '[' => return Err(self.error("class subtraction is not supported")),
+                '\\' => self.escape()?,
+                c => Item::Range(c, c),
+            };
+            let item = match item {
+                Item::Range(from, _)
+                    if self.peek() == Some('-')
+                        && self.chars.get(self.pos + 1).is_some_and(|&c| c != ']') =>
+                {
+                    self.pos += 1;
+                    let to = match self.next()? {
+                        '\\' => match self.escape()? {
+                            Item::Range(to, _) => to,
+                            _ => return Err(self.error("invalid range")),
+                        },
+                        '[' => return Err(self.error("class subtraction is not supported")),
+                        to => to,
+                    };
+                    if to < from {
+                        return Err(self.error("invalid range"));
+                    }
+                    Item::Range(from, to)
+                }
+                item => item,
+            };
+            items.push(item);
+        }
+        Ok(Class { negated, items })
+    }
+
+    fn escape(&mut self) -> Result<Item, Error> {
+        let c = self.next()?;
+        Ok(match c {
+            'n' => Item::Range('\n', '\n'),
+            'r' => Item::Range('\r', '\r'),
+            't' => Item::Range('\t', '\t'),
+            'd' | 'D' | 's' | 'S' | 'w' | 'W' | 'i' | 'I' | 'c' | 'C' => Item::Escape(c),
+            'p' | 'P' => {
+                if self.next()? != '{' {
+                    return Err(self.error("expected '{'"));
+                }
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c != '}') {
+                    self.pos += 1;
+                }
+                let name: String = self.chars[start..self.pos].iter().collect();
+                self.next()?;
+                if !CATEGORIES.contains(&name.as_str()) {
+                    return Err(self.error("unsupported category"));
+                }
+                Item::Category(name, c == 'P')
+            }
+            '\\' | '|' | '.' | '-' | '^' | '?' | '*' | '+' | '{' | '}' | '(' | ')' | '[' | ']' => {
+                Item::Range(c, c)
+            }
+            _ => return Err(self.error("unknown escape")),
+        })
+    }
+}
+
+impl Class {
+    fn single(item: Item) -> Self {
+        Class {
+            negated: false,
+            items: vec![item],
+        }
+    }
+
+    fn matches(&self, c: char) -> bool {
+        self.items.iter().any(|item| item.matches(c)) != self.negated
+    }
+}
+
+impl Item {
+    fn matches(&self, c: char) -> bool {
+        match *self {
+            Item::Range(from, to) => from <= c && c <= to,
+            Item::Any => c != '\n' && c != '\r',
+            Item::Category(ref name, negated) => in_category(name, c) != negated,
+            Item::Escape(e) => {
+                let matched = match e.to_ascii_lowercase() {
+                    'd' => in_category("Nd", c),
+                    's' => c == ' ' || c == '\t' || c == '\n' || c == '\r',
+                    'w' => !["P", "Z", "C"].iter().any(|name| in_category(name, c)),
+                    'i' => is_name_start_char(c),
+                    _ => is_name_start_char(c) || is_name_char(c),
+                };
+                matched != e.is_ascii_uppercase()
+            }
+        }
+    }
+}
+
+/// The general categories supported by `\p{..}`.
+const CATEGORIES: &[&str] = &[
+    "L", "Lu", "Ll", "Lt", "Lm", "Lo", "M", "Mn", "Mc", "Me", "N", "Nd", "Nl", "No", "P", "Pc",
+    "Pd", "Ps", "Pe", "Pi", "Pf", "Po", "Z", "Zs", "Zl", "Zp", "S", "Sm", "Sc", "Sk", "So", "C",
+    "Cc", "Cf", "Co", "Cn",
+];
+
+/// Returns true if `c` is in the general category `name`, a single letter
+/// naming all categories starting with it.
+fn in_category(name: &str, c: char) -> bool {
+    let category = get_general_category(c).abbreviation();
+    if name.len() == 1 {
+        category.starts_with(name)
+    } else {
+        category == name
+    }
+}
+
+/// The `NameStartChar` production of XML 1.0 (Fifth Edition).
+fn is_name_start_char(c: char) -> bool {
+    matches!(c,
+        ':' | 'A'..='Z' | '_' | 'a'..='z' | '\u{c0}'..='\u{d6}' | '\u{d8}'..='\u{f6}'
+        | '\u{f8}'..='\u{2ff}' | '\u{370}'..='\u{37d}' | '\u{37f}'..='\u{1fff}'
+        | '\u{200c}'..='\u{200d}' | '\u{2070}'..='\u{218f}' | '\u{2c00}'..='\u{2fef}'
+        | '\u{3001}'..='\u{d7ff}' | '\u{f900}'..='\u{fdcf}' | '\u{fdf0}'..='\u{fffd}'
+        | '\u{10000}'..='\u{effff}')
+}
+
+/// The characters added to `NameStartChar` by the `NameChar` production of
+/// XML 1.0 (Fifth Edition).
+fn is_name_char(c: char) -> bool {
+    matches!(c,
+        '-' | '.' | '0'..='9' | '\u{b7}' | '\u{300}'..='\u{36f}' | '\u{203f}'..='\u{2040}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, value: &str) -> bool {
+        Pattern::new(pattern).unwrap().is_match(value)
+    }
+
+    #[test]
+    fn pattern_matching() {
+        assert!(matches("[A-Z]{2}\\d{3}", "CH123"));
+        assert!(!matches("[A-Z]{2}\\d{3}", "CH1234"));
+        assert!(matches("(ab|c)*d?", "abcab"));
+        assert!(matches("(ab|c)*d?", ""));
+        assert!(!matches("(ab|c)*d?", "abd d"));
+        assert!(matches("[^\\s]+@[\\w.\\-]+", "a@example.org"));
+        assert!(matches("\\p{Lu}\\p{Ll}*", "Zürich"));
+        assert!(matches("a{2,}b{0,1}", "aaab"));
+        assert!(matches("[+-]?\\d+(\\.\\d+)?", "-1.50"));
+        assert!(!matches("[+-]?\\d+(\\.\\d+)?", "1."));
+        assert!(matches("(a*)*b", "aaab"));
+        assert!(matches("(|a)+", "aa"));
+        assert!(matches("a{0}b", "b"));
+    }
+
+    #[test]
+    fn unicode_classes() {
+        // Only decimal digits, not other numbers like superscripts.
+        assert!(matches("\\d+", "12\u{661}"));
+        assert!(!matches("\\d", "\u{b2}"));
+        assert!(matches("\\p{N}", "\u{b2}"));
+        // Punctuation and separators beyond ASCII.
+        assert!(matches("\\p{P}+", "¿«»—"));
+        assert!(!matches("\\p{P}", "+"));
+        assert!(matches("\\p{Z}\\p{Zl}", "\u{a0}\u{2028}"));
+        assert!(!matches("\\p{Z}", "\t"));
+        // `\w` excludes punctuation, separators and other characters.
+        assert!(matches("\\w+", "a+€\u{1f600}"));
+        assert!(!matches("\\w", "¿"));
+        assert!(!matches("\\w", "\u{a0}"));
+        assert!(!matches("\\w", "\u{200b}"));
+        assert!(matches("\\p{Lt}\\p{Sc}\\p{Mn}", "\u{1c5}$\u{301}"));
+        // Name characters.
+        assert!(matches("\\i\\c*", "_a-1.\u{b7}"));
+        assert!(!matches("\\i", "1"));
+        assert!(!matches("\\c", " "));
+    }
+
+    #[test]
+    fn linear_matching() {
+        let long = "a".repeat(50_000);
+        assert!(matches("[a-z]*", &long));
+        assert!(!matches("[a-z]*", &(long.clone() + "0")));
+
+        let value = "a".repeat(24);
+        assert!(!matches("(a|a)*b", &value));
+        assert!(matches("(a|a)*b", &(value + "b")));
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        for pattern in &[
+            "(a",
+            "a)",
+            "[a",
+            "a{2,1}",
+            "*a",
+            "\\q",
+            "[a-[b]]",
+            "[z-a]",
+            "a{100001}",
+            "(a{1000}){1000}",
+            "(){1000000000}",
+            "\\p{IsBasicLatin}",
+            "\\p{Lx}",
+            "\\p{Cs}",
+        ] {
+            assert!(Pattern::new(pattern).is_err(), "{}", pattern);
+        }
+    }
+}
@@ -0,0 +1,429 @@
+// Copyright 2017-2019 Leonardo Schwarz <mail@leoschwarz.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Raw XML serialization of matched nodes.
+//!
+//! `RawXml` contains the anchor node itself including its descendants,
+//! while `InnerXml` only contains the descendants of the anchor node.
+//! In both cases the namespace declarations in scope of the anchor node
+//! are repeated on the top level elements, so the result can be parsed
+//! as a standalone fragment.
+//!
+//! # Examples
+//! ```
+//! use xpath_reader::{Context, Reader};
+//! use xpath_reader::raw::{InnerXml, RawXml};
+//!
+//! let xml = r#"<?xml version="1.0"?>
+//! <item xmlns:h="http://www.w3.org/1999/xhtml"><desc><h:p>Hello <h:b>World</h:b></h:p></desc></item>"#;
+//! let reader = Reader::from_str(xml, None).unwrap();
+//!
+//! let outer: RawXml = reader.read("//desc").unwrap();
+//! let inner: InnerXml = reader.read("//desc").unwrap();
+//! assert_eq!(
+//!     outer.0,
+//!     r#"<desc xmlns:h="http://www.w3.org/1999/xhtml"><h:p>Hello <h:b>World</h:b></h:p></desc>"#
+//! );
+//! assert_eq!(
+//!     inner.0,
+//!     r#"<h:p xmlns:h="http://www.w3.org/1999/xhtml">Hello <h:b>World</h:b></h:p>"#
+//! );
+//! ```
+
+use errors::Error;
+use reader::{FromXml, FromXmlResult, Reader};
+use sxd_document::dom::Element;
+use sxd_document::QName;
+use sxd_xpath::nodeset::Node;
+
+/// The XML serialization of the anchor node and its descendants.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct RawXml(pub String);
+
+/// The XML serialization of the descendants of the anchor node.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct InnerXml(pub String);
+
+impl FromXml for RawXml {
+    fn from_xml<'d>(reader: &'d Reader<'d>) -> FromXmlResult<Self> {
+        reader
+            .anchor_node()
+            .ok_or_else(|| Error::custom_msg("Missing (anchor) node."))
+            .map(|node| RawXml(outer_xml(node)))
+    }
+}
+
+impl FromXml for Option<RawXml> {
+    fn from_xml<'d>(reader: &'d Reader<'d>) -> FromXmlResult<Self> {
+        Ok(reader.anchor_node().map(|node| RawXml(outer_xml(node))))
+    }
+}
+
+impl FromXml for InnerXml {
+    fn from_xml<'d>(reader: &'d Reader<'d>) -> FromXmlResult<Self> {
+        reader
+            .anchor_node()
+            .ok_or_else(|| Error::custom_msg("Missing (anchor) node."))
+            .map(|node| InnerXml(inner_xml(node)))
+    }
+}
+
+impl FromXml for Option<InnerXml> {
+    fn from_xml<'d>(reader: &'d Reader<'d>) -> FromXmlResult<Self> {
+        Ok(reader.anchor_node().and_then(|node| {
+            let xml = inner_xml(node);
+            if xml.is_empty() {
+                None
+            } else {
+                Some(InnerXml(xml))
+            }
+        }))
+    }
+}
+
+/// Serializes `node` including its descendants.
+pub(crate) fn outer_xml(node: Node) -> String {
+    let mut out = String::new();
+    match node {
+        Node::Root(_) => return inner_xml(node),
+        Node::Element(e) => {
+            let mut scopes = vec![Scope::default()];
+            write_element(e, true, &mut scopes, &mut out);
+        }
+        Node::Attribute(a) => {
+            let name = a.name();
+            let prefix = name.namespace_uri().and_then(|uri| {
+                a.preferred_prefix().or_else(|| {
+                    a.parent()
+                        .and_then(|p| p.prefix_for_namespace_uri(uri, None))
+                })
+            });
+            if let Some(prefix) = prefix {
+                out.push_str(prefix);
+                out.push(':');
+            }
+            out.push_str(name.local_part());
+            out.push_str("=\"");
+            escape_attribute(a.value(), &mut out);
+            out.push('"');
+        }
+        Node::Text(t) => escape_text(t.text(), &mut out),
+        Node::Comment(c) => write_comment(c.text(), &mut out),
+        Node::ProcessingInstruction(pi) => write_pi(pi.target(), pi.value(), &mut out),
+        Node::Namespace(ns) => {
+            out.push_str("xmlns:");
+            out.push_str(ns.prefix());
+            out.push_str("=\"");
+            escape_attribute(ns.uri(), &mut out);
+            out.push('"');
+        }
+    }
+    out
+}
+
+/// Serializes the descendants of `node`.
+pub(crate) fn inner_xml(node: Node) -> String {
+    let mut out = String::new();
+    match node {
+        Node::Root(_) | Node::Element(_) => {
+            for child in node.children() {
+                out.push_str(&outer_xml(child));
+            }
+        }
+        _ => escape_text(&node.string_value(), &mut out),
+    }
+    out
+}
+
+/// Namespace bindings of one element during serialization.
+#[derive(Clone, Debug, Default)]
+struct Scope {
+    default_ns: Option<String>,
+    prefixes: Vec<(String, String)>,
+}
+
+fn lookup_prefix<'s>(scopes: &'s [Scope], prefix: &str) -> Option<&'s str> {
+    scopes
+        .iter()
+        .rev()
+        .filter_map(|s| {
+            s.prefixes
+                .iter()
+                .find(|&(p, _)| p == prefix)
+                .map(|(_, uri)| uri.as_str())
+        })
+        .next()
+}
+
+fn default_ns(scopes: &[Scope]) -> Option<&str> {
+    scopes.last().and_then(|s| s.default_ns.as_deref())
+}
+
+/// Finds (or declares) a prefix bound to `uri` in the current scope.
+fn prefix_for(
+    uri: &str,
+    preferred: Option<&str>,
+    scopes: &mut [Scope],
+    decls: &mut Vec<(String, String)>,
+) -> String {
+    if let Some(p) = preferred {
+        if lookup_prefix(scopes, p) == Some(uri) {
+            return p.to_string();
+        }
+        if lookup_prefix(scopes, p).is_none() {
+            declare(p, uri, scopes, decls);
+            return p.to_string();
+        }
+    }
+
+    let existing = scopes
+        .iter()
+        .rev()
+        .flat_map(|s| s.prefixes.iter().rev())
+        .find(|&(p, u)| u == uri && lookup_prefix(scopes, p) == Some(uri))
+        .map(|(p, _)| p.clone());
+    if let Some(p) = existing {
+        return p;
+    }
+
+    let mut i = 0;
+    loop {
+        let p = format!("ns{}", i);
+        if lookup_prefix(scopes, &p).is_none() {
+            declare(&p, uri, scopes, decls);
+            return p;
+        }
+        i += 1;
+    }
+}
+
+fn declare(prefix: &str, uri: &str, scopes: &mut [Scope], decls: &mut Vec<(String, String)>) {
+    let binding = (prefix.to_string(), uri.to_string());
+    scopes.last_mut().unwrap().prefixes.push(binding.clone());
+    decls.push(binding);
+}
+
+fn write_element(e: Element, top: bool, scopes: &mut Vec<Scope>, out: &mut String) {
+    let inherited_default = default_ns(scopes).map(String::from);
+    scopes.push(Scope {
+        default_ns: inherited_default,
+        prefixes: Vec::new(),
+    });
+
+    let mut decls: Vec<(String, String)> = Vec::new();
+    let mut default_decl: Option<String> = None;
+    if top {
+        if let Some(uri) = e.recursive_default_namespace_uri() {
+            scopes.last_mut().unwrap().default_ns = Some(uri.to_string());
+            default_decl = Some(uri.to_string());
+        }
+    }
+
+    // Namespaces registered on the element itself, or all in scope
+    // namespaces for the top level element.
+    let mut namespaces = e.namespaces_in_scope();
+    namespaces.sort_by_key(|ns| ns.prefix());
+    for ns in namespaces {
+        if ns.prefix() == "xml" {
+            continue;
+        }
+        if lookup_prefix(scopes, ns.prefix()) != Some(ns.uri()) {
+            declare(ns.prefix(), ns.uri(), scopes, &mut decls);
+        }
+    }
+
+    let name = e.name();
+    let element_name = match name.namespace_uri() {
+        Some(uri) => {
+            let use_default =
+                e.preferred_prefix().is_none() || e.default_namespace_uri() == Some(uri);
+            if use_default && default_ns(scopes) == Some(uri) {
+                qualified(None, name)
+            } else if use_default {
+                scopes.last_mut().unwrap().default_ns = Some(uri.to_string());
+                default_decl = Some(uri.to_string());
+                qualified(None, name)
+            } else {
+                let prefix = prefix_for(uri, e.preferred_prefix(), scopes, &mut decls);
+                qualified(Some(&prefix), name)
+            }
+        }
+        None => {
+            if default_ns(scopes).is_some() {
+                scopes.last_mut().unwrap().default_ns = None;
+                default_decl = Some(String::new());
+            }
+            qualified(None, name)
+        }
+    };
+
+    let mut attributes = Vec::new();
+    for attr in e.attributes() {
+        let name = attr.name();
+        let prefix = name
+            .namespace_uri()
+            .map(|uri| prefix_for(uri, attr.preferred_prefix(), scopes, &mut decls));
+        attributes.push((qualified(prefix.as_deref(), name), attr.value()));
+    }
+
+    out.push('<');
+    out.push_str(&element_name);
+    if let Some(uri) = default_decl {
+        out.push_str(" xmlns=\"");
+        escape_attribute(&uri, out);
+        out.push('"');
+    }
+    for (prefix, uri) in decls {
+        out.push_str(" xmlns:");
+        out.push_str(&prefix);
+        out.push_str("=\"");
+        escape_attribute(&uri, out);
+        out.push('"');
+    }
+    for (name, value) in attributes {
+        out.push(' ');
+        out.push_str(&name);
+        out.push_str("=\"");
+        escape_attribute(value, out);
+        out.push('"');
+    }
+
+    let children = e.children();
+    if children.is_empty() {
+        out.push_str("/>");
+    } else {
+        out.push('>');
+        for child in children {
+            let child: Node = child.into();
+            match child {
+                Node::Element(child) => write_element(child, false, scopes, out),
+                Node::Text(t) => escape_text(t.text(), out),
+                Node::Comment(c) => write_comment(c.text(), out),
+                Node::ProcessingInstruction(pi) => write_pi(pi.target(), pi.value(), out),
+                _ => {}
+            }
+        }
+        out.push_str("</");
+        out.push_str(&element_name);
+        out.push('>');
+    }
+
+    scopes.pop();
+}
+
+fn qualified(prefix: Option<&str>, name: QName) -> String {
+    match prefix {
+        Some(p) => format!("{}:{}", p, name.local_part()),
+        None => name.local_part().to_string(),
+    }
+}
+
+fn write_comment(text: &str, out: &mut String) {
+    out.push_str("<!--");
+    out.push_str(text);
+    out.push_str("-->");
+}
+
+fn write_pi(target: &str, value: Option<&str>, out: &mut String) {
+    out.push_str("<?");
+    out.push_str(target);
+    if let Some(v) = value {
+        out.push(' ');
+        out.push_str(v);
+    }
+    out.push_str("?>");
+}
+
+pub(crate) fn escape_text(s: &str, out: &mut String) {
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            c => out.push(c),
+        }
+    }
+}
+
+pub(crate) fn escape_attribute(s: &str, out: &mut String) {
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '"' => out.push_str("&quot;"),
+            '\t' => out.push_str("&#9;"),
+            '\n' => out.push_str("&#10;"),
+            '\r' => out.push_str("&#13;"),
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sxd_xpath::Context;
+
+    #[test]
+    fn raw_xml_namespaces() {
+        let xml = r#"<?xml version="1.0"?>
+            <feed xmlns="urn:feed" xmlns:x="urn:x" xmlns:unused="urn:unused">
+              <entry x:id="1"><title type="html">A &amp; B</title><x:extra/><plain xmlns=""/></entry>
+            </feed>"#;
+        let mut context = Context::new();
+        context.set_namespace("f", "urn:feed");
+        context.set_namespace("x", "urn:x");
+        let reader = Reader::from_str(xml, Some(&context)).unwrap();
+
+        let entry: RawXml = reader.read("//f:entry").unwrap();
+        assert_eq!(
+            entry.0,
+            r#"<entry xmlns="urn:feed" xmlns:unused="urn:unused" xmlns:x="urn:x" x:id="1"><title type="html">A &amp; B</title><x:extra/><plain xmlns=""/></entry>"#
+        );
+
+        let inner: InnerXml = reader.read("//f:title").unwrap();
+        assert_eq!(inner.0, "A &amp; B");
+
+        let attr: RawXml = reader.read("//f:entry/@x:id").unwrap();
+        assert_eq!(attr.0, r#"x:id="1""#);
+    }
+
+    #[test]
+    fn raw_xml_roundtrip() {
+        let xml = r#"<?xml version="1.0"?><a xmlns:p="urn:p"><p:b c="&quot;q&quot;"><!--note--><?pi data?></p:b></a>"#;
+        let reader = Reader::from_str(xml, None).unwrap();
+
+        let b: RawXml = reader.read("//*[local-name()='b']").unwrap();
+        let reparsed = Reader::from_str(&b.0, None).unwrap();
+        let again: RawXml = reparsed.read("/*").unwrap();
+        assert_eq!(b, again);
+        assert_eq!(
+            b.0,
+            r#"<p:b xmlns:p="urn:p" c="&quot;q&quot;"><!--note--><?pi data?></p:b>"#
+        );
+    }
+
+    #[test]
+    fn raw_xml_absent() {
+        let reader = Reader::from_str("<a><empty/></a>", None).unwrap();
+
+        let missing: Option<RawXml> = reader.read("//missing").unwrap();
+        assert_eq!(missing, None);
+        let empty: Option<InnerXml> = reader.read("//empty").unwrap();
+        assert_eq!(empty, None);
+        let empty: RawXml = reader.read("//empty").unwrap();
+        assert_eq!(empty.0, "<empty/>");
+        assert!(reader.read::<InnerXml, _>("//missing").is_err());
+    }
+}
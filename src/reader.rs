@@ -14,13 +14,32 @@
 
 //! XPath based document parsing.
 
+use budget::{Budget, EvalBudget};
+use compression;
+use dtd::{self, DtdOptions};
 use errors::{Error, ErrorKind};
-use expression::XPathExpression;
+use expression::{SimplePath, XPathExpression};
+#[cfg(feature = "html")]
+use html;
+use limits::Limits;
+use localized;
+use node::{self, NodeKind, XML_NAMESPACE};
+use recover::{self, Warning};
 use std::borrow::{Borrow, Cow};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+#[cfg(feature = "async")]
+use stream::FromAsyncRead;
+use strict::Tracker;
 use sxd_document::parser::parse as sxd_parse;
-use sxd_document::Package;
+use sxd_document::{Package, QName};
 use sxd_xpath::nodeset::{Node, Nodeset};
 use sxd_xpath::{Context, Value, XPath};
+#[cfg(feature = "async")]
+use tokio::io::AsyncRead;
 use util::Refable;
 
 /// Convenience redefinition of the FromXml result type.
@@ -80,7 +99,7 @@ where
 
 enum Anchor<'d> {
     Nodeset(Nodeset<'d>),
-    Root(Package),
+    Root(Box<Package>),
 }
 
 /// XML element tree reader using XPath expressions.
@@ -98,6 +117,9 @@ enum Anchor<'d> {
 ///    multiple nodes into a single target value.
 pub struct Reader<'d> {
     context: Refable<'d, Context<'d>>,
+    languages: Refable<'d, Vec<String>>,
+    tracker: Option<Refable<'d, Tracker>>,
+    budget: Option<Refable<'d, Budget>>,
     anchor: Anchor<'d>,
 }
 
@@ -112,24 +134,246 @@ impl<'d> Reader<'d> {
         V::from_xml(&reader)
     }
 
+    /// Read the result of the XPath expression into a value of type `V`,
+    /// failing if any element or attribute in the selected subtrees was not
+    /// read by `V::from_xml`.
+    ///
+    /// An element counts as read if it or one of its descendants or
+    /// attributes was selected by an expression. Reading the string value
+    /// of an element does not mark its child elements as read.
+    ///
+    /// # Examples
+    /// ```
+    /// use xpath_reader::{FromXml, FromXmlResult, Reader};
+    ///
+    /// struct Book {
+    ///     title: String,
+    /// }
+    ///
+    /// impl FromXml for Book {
+    ///     fn from_xml<'d>(reader: &'d Reader<'d>) -> FromXmlResult<Self> {
+    ///         Ok(Book {
+    ///             title: reader.read("title")?,
+    ///         })
+    ///     }
+    /// }
+    ///
+    /// let xml = r#"<book isbn="1"><title>Neuromancer</title><author/></book>"#;
+    /// let reader = Reader::from_str(xml, None).unwrap();
+    ///
+    /// let error = reader.read_strict::<Book, _>("/book").err().unwrap();
+    /// assert!(error
+    ///     .to_string()
+    ///     .contains("Unread content: /book[1]/@isbn, /book[1]/author[1]"));
+    /// ```
+    pub fn read_strict<'a, V, X>(&'d self, xpath_expr: X) -> Result<V, Error>
+    where
+        V: FromXml,
+        X: Into<XPathExpression<'a>>,
+    {
+        let mut reader = self.with_nodeset_eval(xpath_expr)?;
+        if reader.tracker.is_none() {
+            let tracker = Tracker::default();
+            tracker.mark(&reader.anchor_nodeset());
+            reader.tracker = Some(Refable::Owned(tracker));
+        }
+        let value = V::from_xml(&reader)?;
+
+        let unread = reader.unread();
+        if unread.is_empty() {
+            Ok(value)
+        } else {
+            Err(Error::custom_msg(format!(
+                "Unread content: {}",
+                unread.join(", ")
+            )))
+        }
+    }
+
+    /// Enables or disables strict mode, in which the nodes selected by this
+    /// reader and the readers created from it are tracked.
+    ///
+    /// After reading, `unread` lists the content which was never selected.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.tracker = if strict {
+            Some(Refable::Owned(Tracker::default()))
+        } else {
+            None
+        };
+    }
+
+    /// Sets the budget for each evaluation of this reader and the readers
+    /// created from it, `None` removing the limits.
+    ///
+    /// See the `budget` module for details.
+    pub fn set_budget(&mut self, budget: Option<EvalBudget>) {
+        self.budget = budget.map(|b| Refable::Owned(Budget::new(b)));
+    }
+
+    /// Returns the paths of the elements and attributes in the subtrees of
+    /// the anchor nodeset which were not read since strict mode was enabled.
+    ///
+    /// For unread elements only the element itself is listed, not its
+    /// content. Without strict mode nothing is tracked and the result is
+    /// empty.
+    pub fn unread(&'d self) -> Vec<String> {
+        let tracker: &Tracker = match self.tracker {
+            Some(ref t) => t.borrow(),
+            None => return Vec::new(),
+        };
+        self.anchor_nodeset()
+            .document_order()
+            .into_iter()
+            .flat_map(|n| tracker.unread(n))
+            .collect()
+    }
+
     /// Construct a new reader for the specified XML document.
     ///
     /// A context can be specified to define custom functions,
     /// variables and namespaces.
+    ///
+    /// No limits are applied while parsing, use `from_str_with_limits` for
+    /// untrusted documents.
     pub fn from_str(xml: &str, context: Option<&'d Context<'d>>) -> Result<Self, Error> {
         // TODO: Display all.
         let package =
             sxd_parse(xml).map_err(|e| Error::internal(format!("{}", e), ErrorKind::ParseXml))?;
+        Ok(Self::from_package(package, context))
+    }
+
+    /// Construct a new reader for the specified XML document, repairing
+    /// common faults before parsing it.
+    ///
+    /// Returns the reader and a warning for each repair, see the `recover`
+    /// module for details.
+    pub fn from_str_recovering(
+        xml: &str,
+        context: Option<&'d Context<'d>>,
+    ) -> Result<(Self, Vec<Warning>), Error> {
+        let (xml, warnings) = recover::repair(xml);
+        Self::from_str(&xml, context).map(|reader| (reader, warnings))
+    }
+
+    /// Construct a new reader for the specified XML document, processing
+    /// its document type declaration according to `options`.
+    ///
+    /// See the `dtd` module for details.
+    pub fn from_str_with_dtd(
+        xml: &str,
+        context: Option<&'d Context<'d>>,
+        options: &DtdOptions,
+    ) -> Result<Self, Error> {
+        let package = dtd::parse(xml, options)?;
+        Ok(Self::from_package(package, context))
+    }
+
+    /// Construct a new reader for the specified XML document, failing
+    /// with `ErrorKind::LimitExceeded` if the document exceeds `limits`.
+    ///
+    /// Like `from_str` this skips the document type declaration, so the
+    /// entity expansion limit does not apply. Use `from_str_with_dtd` with
+    /// `DtdOptions::limits` to expand entities within limits.
+    ///
+    /// See the `limits` module for details.
+    pub fn from_str_with_limits(
+        xml: &str,
+        context: Option<&'d Context<'d>>,
+        limits: &Limits,
+    ) -> Result<Self, Error> {
+        limits.check_input_size(xml.len())?;
+        limits.check_markup(xml)?;
+        Self::from_str(xml, context)
+    }
+
+    /// Construct a new reader for the XML document read from `read`,
+    /// decompressing it if necessary.
+    ///
+    /// The document is parsed with `Limits::default()`, which also bounds
+    /// the size of the decompressed input.
+    ///
+    /// See the `compression` module for the supported formats.
+    pub fn from_read<R: Read>(read: R, context: Option<&'d Context<'d>>) -> Result<Self, Error> {
+        Self::from_read_with_limits(read, context, &Limits::default())
+    }
+
+    /// Construct a new reader for the XML document read from `read`,
+    /// decompressing it if necessary and failing with
+    /// `ErrorKind::LimitExceeded` if the document exceeds `limits`.
+    pub fn from_read_with_limits<R: Read>(
+        read: R,
+        context: Option<&'d Context<'d>>,
+        limits: &Limits,
+    ) -> Result<Self, Error> {
+        let xml = compression::read_document(read, limits)?;
+        Self::from_str_with_limits(&xml, context, limits)
+    }
+
+    /// Construct a new reader for the XML document stored at `path`,
+    /// decompressing it if necessary.
+    ///
+    /// The document is parsed with `Limits::default()`, which also bounds
+    /// the size of the decompressed input.
+    ///
+    /// See the `compression` module for the supported formats.
+    pub fn from_path<P: AsRef<Path>>(
+        path: P,
+        context: Option<&'d Context<'d>>,
+    ) -> Result<Self, Error> {
+        Self::from_path_with_limits(path, context, &Limits::default())
+    }
+
+    /// Construct a new reader for the XML document stored at `path`,
+    /// decompressing it if necessary and failing with
+    /// `ErrorKind::LimitExceeded` if the document exceeds `limits`.
+    pub fn from_path_with_limits<P: AsRef<Path>>(
+        path: P,
+        context: Option<&'d Context<'d>>,
+        limits: &Limits,
+    ) -> Result<Self, Error> {
+        let file = File::open(path).map_err(|e| Error::internal(e, ErrorKind::Other))?;
+        Self::from_read_with_limits(file, context, limits)
+    }
 
+    /// Returns a future reading the XML document from `read` and
+    /// constructing a reader for it, decompressing it if necessary.
+    ///
+    /// The document is parsed with `Limits::default()`. Reading stops
+    /// with `ErrorKind::LimitExceeded` as soon as the input read so far,
+    /// before or after decompressing it, exceeds the maximum input size.
+    ///
+    /// See the `stream` module for reading large documents record by
+    /// record.
+    #[cfg(feature = "async")]
+    pub fn from_async_read<R: AsyncRead + Unpin>(
+        read: R,
+        context: Option<&'d Context<'d>>,
+    ) -> FromAsyncRead<'d, R> {
+        FromAsyncRead::new(read, context, Limits::default())
+    }
+
+    /// Construct a new reader for the specified HTML document, recovering
+    /// from any syntax errors.
+    ///
+    /// See the `html` module for the resulting document structure.
+    #[cfg(feature = "html")]
+    pub fn from_html(html: &str, context: Option<&'d Context<'d>>) -> Self {
+        Self::from_package(html::parse(html), context)
+    }
+
+    fn from_package(package: Package, context: Option<&'d Context<'d>>) -> Self {
         let context_refable = match context {
             Some(c) => Refable::Borrowed(c),
             None => Refable::Owned(Context::default()),
         };
 
-        Ok(Reader {
+        Reader {
             context: context_refable,
-            anchor: Anchor::Root(package),
-        })
+            languages: Refable::Owned(Vec::new()),
+            tracker: None,
+            budget: None,
+            anchor: Anchor::Root(Box::new(package)),
+        }
     }
 
     /// Construct a new reader for the specified nodeset.
@@ -152,6 +396,9 @@ impl<'d> Reader<'d> {
 
         Reader {
             context: context_refable,
+            languages: Refable::Owned(Vec::new()),
+            tracker: None,
+            budget: None,
             anchor: Anchor::Nodeset(nodeset),
         }
     }
@@ -176,30 +423,175 @@ impl<'d> Reader<'d> {
         match self.evaluate(&xpath)? {
             Value::Nodeset(nodeset) => Ok(Reader {
                 context: self.context.clone_ref(),
+                languages: self.languages.clone_ref(),
+                tracker: self.tracker.as_ref().map(|t| {
+                    let tracker: &Tracker = t.borrow();
+                    tracker.mark(&nodeset);
+                    t.clone_ref()
+                }),
+                budget: self.budget.as_ref().map(|b| b.clone_ref()),
                 anchor: Anchor::Nodeset(nodeset),
             }),
             _ => Err(Error::internal(
-                format!(
-                    "XPath expression did not evaluate to nodeset: '{}'",
-                    xpath.to_string()
-                ),
+                format!("XPath expression did not evaluate to nodeset: '{}'", xpath),
                 ErrorKind::EvalXPath,
             )),
         }
     }
 
+    /// Creates a new `Reader` anchored at the parent of the anchor node.
+    ///
+    /// The current context will be passed to the new reader. If the anchor
+    /// node has no parent (i.e. it is the document root) or the anchor
+    /// nodeset is empty, the new reader has an empty anchor nodeset.
+    pub fn parent(&'d self) -> Self {
+        let parent = self.anchor_node().and_then(|n| n.parent());
+        self.with_nodes(parent)
+    }
+
+    /// Creates a new `Reader` for each ancestor of the anchor node, nearest
+    /// ancestor first, ending with the document root.
+    ///
+    /// The current context will be passed to the new readers.
+    pub fn ancestors(&'d self) -> Vec<Self> {
+        let mut ancestors = Vec::new();
+        let mut current = self.anchor_node().and_then(|n| n.parent());
+        while let Some(node) = current {
+            ancestors.push(self.with_nodes(Some(node)));
+            current = node.parent();
+        }
+        ancestors
+    }
+
+    /// Creates a new `Reader` for each sibling following the anchor node,
+    /// in document order.
+    ///
+    /// The current context will be passed to the new readers.
+    pub fn following_siblings(&'d self) -> Vec<Self> {
+        self.anchor_node()
+            .map(|n| n.following_siblings())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|node| self.with_nodes(Some(node)))
+            .collect()
+    }
+
+    /// Creates a new `Reader` for each sibling preceding the anchor node,
+    /// in document order.
+    ///
+    /// The current context will be passed to the new readers.
+    pub fn preceding_siblings(&'d self) -> Vec<Self> {
+        self.anchor_node()
+            .map(|n| n.preceding_siblings())
+            .unwrap_or_default()
+            .into_iter()
+            .rev()
+            .map(|node| self.with_nodes(Some(node)))
+            .collect()
+    }
+
+    /// Creates a new `Reader` anchored at the root of the document.
+    ///
+    /// The current context will be passed to the new reader. If the anchor
+    /// nodeset is empty, so is the anchor nodeset of the new reader.
+    pub fn root(&'d self) -> Self {
+        let root = self.anchor_node().map(|n| Node::Root(n.document().root()));
+        self.with_nodes(root)
+    }
+
+    /// Creates a new `Reader` anchored at `nodes`, sharing the context and
+    /// preferred languages of this reader.
+    pub(crate) fn with_nodes<I>(&'d self, nodes: I) -> Self
+    where
+        I: IntoIterator<Item = Node<'d>>,
+    {
+        Reader {
+            context: self.context.clone_ref(),
+            languages: self.languages.clone_ref(),
+            tracker: self.tracker.as_ref().map(|t| t.clone_ref()),
+            budget: self.budget.as_ref().map(|b| b.clone_ref()),
+            anchor: Anchor::Nodeset(nodes.into_iter().collect()),
+        }
+    }
+
+    /// Sets the preferred languages as BCP 47 language tags, most preferred
+    /// first.
+    ///
+    /// If the anchor nodeset contains multiple nodes, `String::from_xml`
+    /// (and all types built on it) will read the node whose `xml:lang`
+    /// best matches the preferred languages instead of the first one.
+    /// Tags are matched with the lookup scheme of RFC 4647, i.e. `de-CH`
+    /// falls back to `de` and finally to nodes without language.
+    ///
+    /// Readers created from this reader share its preferred languages.
+    ///
+    /// # Examples
+    /// ```
+    /// use xpath_reader::Reader;
+    ///
+    /// let xml = r#"<book><title xml:lang="en">Hello</title><title xml:lang="de">Hallo</title></book>"#;
+    /// let mut reader = Reader::from_str(xml, None).unwrap();
+    /// reader.set_preferred_languages(vec!["de-CH", "en"]);
+    ///
+    /// let title: String = reader.read("//title").unwrap();
+    /// assert_eq!(title, "Hallo");
+    /// ```
+    pub fn set_preferred_languages<I, S>(&mut self, languages: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.languages = Refable::Owned(languages.into_iter().map(Into::into).collect());
+    }
+
+    /// Returns the preferred languages, most preferred first.
+    pub fn preferred_languages(&'d self) -> &'d [String] {
+        let languages: &Vec<String> = self.languages.borrow();
+        languages
+    }
+
+    /// Returns the node of the anchor nodeset which is read by
+    /// `String::from_xml`.
+    ///
+    /// This is the first node in document order, unless preferred languages
+    /// are set, in which case it is the node whose `xml:lang` matches them
+    /// best. Nodes without a matching language are only chosen if there is
+    /// no node with a matching or without language.
+    pub fn preferred_node(&'d self) -> Option<Node<'d>> {
+        let nodeset = match self.anchor {
+            Anchor::Nodeset(ref nodeset) if !self.preferred_languages().is_empty() => nodeset,
+            _ => return self.anchor_node(),
+        };
+        let nodes = nodeset.document_order();
+        if nodes.len() < 2 {
+            return nodes.first().cloned();
+        }
+
+        let tags: Vec<Option<&str>> = nodes
+            .iter()
+            .map(|n| {
+                node::inherited_values(*n, (XML_NAMESPACE, "lang").into())
+                    .into_iter()
+                    .next()
+                    .filter(|lang| !lang.is_empty())
+            })
+            .collect();
+        let index = localized::lookup(self.preferred_languages(), &tags).unwrap_or(0);
+        Some(nodes[index])
+    }
+
     /// References the evaluation context of this Reader.
     pub fn context(&'d self) -> &'d Context<'d> {
         self.context.borrow()
     }
 
     /// Returns the anchor nodeset of the current reader.
-    pub fn anchor_nodeset(&'d self) -> Cow<Nodeset<'d>> {
+    pub fn anchor_nodeset(&'d self) -> Cow<'d, Nodeset<'d>> {
         match self.anchor {
             Anchor::Nodeset(ref nodeset) => Cow::Borrowed(nodeset),
             Anchor::Root(ref package) => {
                 let mut nodeset = Nodeset::new();
-                let root = package.as_document().root().clone();
+                let root = package.as_document().root();
                 nodeset.add(Node::Root(root));
                 Cow::Owned(nodeset)
             }
@@ -212,37 +604,162 @@ impl<'d> Reader<'d> {
     pub fn anchor_node(&'d self) -> Option<Node<'d>> {
         match self.anchor {
             Anchor::Nodeset(ref nodeset) => nodeset.document_order_first(),
-            Anchor::Root(ref package) => Some(package.as_document().root().clone().into()),
+            Anchor::Root(ref package) => Some(package.as_document().root().into()),
         }
     }
 
-    fn evaluate<'a, X>(&'d self, xpath_expr: X) -> Result<Value<'d>, Error>
+    /// Returns the value of the attribute `name` on the anchor node or its
+    /// nearest ancestor declaring it.
+    ///
+    /// This is useful for attributes which apply to all descendants of an
+    /// element, like `xml:lang`, or schema specific ones like `currency`.
+    /// If the anchor node is an attribute, the lookup starts at its element.
+    ///
+    /// # Examples
+    /// ```
+    /// use xpath_reader::Reader;
+    /// use xpath_reader::node::XML_NAMESPACE;
+    ///
+    /// let xml = r#"<order currency="CHF" xml:lang="de"><line amount="5"/></order>"#;
+    /// let reader = Reader::from_str(xml, None).unwrap();
+    /// let line = reader.with_nodeset_eval("//line/@amount").unwrap();
+    ///
+    /// assert_eq!(line.inherited("currency"), Some("CHF"));
+    /// assert_eq!(line.inherited((XML_NAMESPACE, "lang")), Some("de"));
+    /// assert_eq!(line.inherited("unit"), None);
+    /// ```
+    pub fn inherited<'n, N>(&'d self, name: N) -> Option<&'d str>
+    where
+        N: Into<QName<'n>>,
+    {
+        let name = name.into();
+        self.anchor_node()
+            .and_then(|n| node::inherited_values(n, name).into_iter().next())
+    }
+
+    /// Returns the kind of the anchor node.
+    pub fn anchor_kind(&'d self) -> Option<NodeKind> {
+        self.anchor_node().map(NodeKind::of)
+    }
+
+    /// Returns the local name of the anchor node.
+    ///
+    /// This is the local part of the name for elements and attributes, the
+    /// target for processing instructions and the prefix for namespace
+    /// nodes. Other nodes have no name.
+    pub fn anchor_local_name(&'d self) -> Option<&'d str> {
+        self.anchor_node().and_then(node::local_name)
+    }
+
+    /// Returns the namespace URI of the anchor element or attribute.
+    pub fn anchor_namespace_uri(&'d self) -> Option<&'d str> {
+        self.anchor_node().and_then(node::namespace_uri)
+    }
+
+    /// Returns the prefix of the anchor element or attribute, as bound in
+    /// the document.
+    ///
+    /// Elements in the default namespace and nodes without namespace have
+    /// no prefix.
+    pub fn anchor_prefix(&'d self) -> Option<&'d str> {
+        self.anchor_node().and_then(node::prefix)
+    }
+
+    /// Returns the attributes of the anchor element, keyed by their
+    /// qualified name (e.g. `xml:lang`).
+    ///
+    /// If the anchor node is not an element, the map is empty.
+    pub fn anchor_attributes(&'d self) -> BTreeMap<String, String> {
+        match self.anchor_node() {
+            Some(Node::Element(e)) => e
+                .attributes()
+                .into_iter()
+                .filter_map(|attr| {
+                    node::qualified_name(Node::Attribute(attr))
+                        .map(|name| (name, attr.value().to_string()))
+                })
+                .collect(),
+            _ => BTreeMap::new(),
+        }
+    }
+
+    /// Returns the local names of the child elements of the anchor node,
+    /// in document order.
+    pub fn anchor_child_names(&'d self) -> Vec<&'d str> {
+        self.anchor_node()
+            .map(|n| {
+                n.children()
+                    .into_iter()
+                    .filter_map(|child| child.element())
+                    .map(|e| e.name().local_part())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the index of the anchor node in document order, with the
+    /// document root having index 0.
+    ///
+    /// Attributes are ordered directly after their element and before its
+    /// children.
+    pub fn anchor_position(&'d self) -> Option<usize> {
+        self.anchor_node().and_then(node::document_position)
+    }
+
+    /// Returns a location path identifying the anchor node in its document,
+    /// e.g. `/catalog[1]/item[3]/@id`, which is useful for diagnostics.
+    pub fn anchor_path(&'d self) -> Option<String> {
+        self.anchor_node().map(node::node_path)
+    }
+
+    pub(crate) fn evaluate<'a, X>(&'d self, xpath_expr: X) -> Result<Value<'d>, Error>
     where
         X: Into<XPathExpression<'a>>,
     {
         let xpath_expr = xpath_expr.into();
-        let xpath = xpath_expr.parsed()?;
-        // TODO: Error message.
+        let simple = xpath_expr.simple_path();
+        let xpath = match simple {
+            Some(_) => None,
+            None => Some(xpath_expr.parsed()?),
+        };
         let anchor = self.anchor_node().ok_or_else(|| {
-            let xpath_ref: &XPath = xpath.borrow();
             Error::internal(
-                format!("Anchor node not found when evaluating: {:?}", xpath_ref),
+                format!("Anchor node not found when evaluating: {}", xpath_expr),
                 ErrorKind::EvalXPath,
             )
         })?;
 
-        // Note: This is very ugly but otherwise does not compile.
-        let xpath_ref: &XPath = xpath.borrow();
-        xpath_ref
-            .evaluate(self.context.borrow(), anchor)
-            .map_err(|e| Error::internal(format!("{}", e), ErrorKind::EvalXPath))
+        let budget: Option<&Budget> = self.budget.as_ref().map(|b| b.borrow());
+        if let Some(budget) = budget {
+            let xpath_ref: Option<&XPath> = xpath.as_ref().map(|x| x.borrow());
+            budget.start(&xpath_expr, xpath_ref, anchor)?;
+        }
+
+        let value = match (simple, xpath) {
+            (Some(path), _) => {
+                let path: &SimplePath = path.borrow();
+                Value::Nodeset(path.evaluate(anchor))
+            }
+            (None, Some(xpath)) => {
+                // Note: This is very ugly but otherwise does not compile.
+                let xpath_ref: &XPath = xpath.borrow();
+                xpath_ref
+                    .evaluate(self.context.borrow(), anchor)
+                    .map_err(|e| Error::internal(format!("{}", e), ErrorKind::EvalXPath))?
+            }
+            (None, None) => unreachable!(),
+        };
+        if let Some(budget) = budget {
+            budget.finish(&value)?;
+        }
+        Ok(value)
     }
 }
 
 impl FromXml for String {
     fn from_xml<'d>(reader: &'d Reader<'d>) -> Result<Self, Error> {
         reader
-            .anchor_node()
+            .preferred_node()
             .ok_or(Error::custom_msg("Missing (anchor) node."))
             .map(|n| n.string_value())
     }
@@ -250,7 +767,7 @@ impl FromXml for String {
 
 impl FromXml for Option<String> {
     fn from_xml<'d>(reader: &'d Reader<'d>) -> Result<Self, Error> {
-        Ok(reader.anchor_node().and_then(|node| {
+        Ok(reader.preferred_node().and_then(|node| {
             let s = node.string_value();
             if s.is_empty() {
                 None
@@ -271,7 +788,7 @@ where
             .document_order()
             .iter()
             .map(|node| {
-                let reader = Reader::from_node(*node, Some(reader.context()));
+                let reader = reader.with_nodes(Some(*node));
                 T::from_xml(&reader)
             })
             .collect()
@@ -304,6 +821,14 @@ macro_rules! from_parse_str {
 }
 
 from_parse_str!(f32, f64, u8, u16, u32, u64, i8, i16, i32, i64, bool);
+from_parse_str!(IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr);
+
+#[cfg(feature = "rust_decimal")]
+from_parse_str!(::rust_decimal::Decimal);
+#[cfg(feature = "semver")]
+from_parse_str!(::semver::Version);
+#[cfg(feature = "uuid")]
+from_parse_str!(::uuid::Uuid);
 
 #[cfg(test)]
 mod tests {
@@ -386,8 +911,216 @@ mod tests {
         let t = reader.with_nodeset_eval("//t").unwrap();
         let f = reader.with_nodeset_eval("//f").unwrap();
 
-        assert_eq!(bool::from_xml(&t).unwrap(), true);
-        assert_eq!(bool::from_xml(&f).unwrap(), false);
+        assert!(bool::from_xml(&t).unwrap());
+        assert!(!bool::from_xml(&f).unwrap());
+    }
+
+    #[test]
+    fn net_from_xml() {
+        let xml = r#"<?xml version="1.0"?><root><v4>127.0.0.1</v4><v6>::1</v6><sock>10.0.0.1:80</sock></root>"#;
+        let reader = Reader::from_str(xml, None).unwrap();
+
+        let v4: IpAddr = reader.read("//v4").unwrap();
+        let v6: Ipv6Addr = reader.read("//v6").unwrap();
+        let sock: SocketAddr = reader.read("//sock").unwrap();
+        let missing: Option<IpAddr> = reader.read("//missing").unwrap();
+        assert_eq!(v4, IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)));
+        assert_eq!(v6, Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1));
+        assert_eq!(sock, "10.0.0.1:80".parse().unwrap());
+        assert_eq!(missing, None);
+        assert!(reader.read::<IpAddr, _>("//sock").is_err());
+    }
+
+    #[cfg(feature = "url")]
+    #[test]
+    fn url_from_xml() {
+        let xml = r#"<?xml version="1.0"?>
+                     <root xml:base="http://example.com/a/">
+                       <sub xml:base="b/"><link href="c.html"/></sub>
+                       <abs href="https://example.org/x"/>
+                       <plain href="d.html"/>
+                     </root>"#;
+        let reader = Reader::from_str(xml, None).unwrap();
+
+        let link: ::url::Url = reader.read("//link/@href").unwrap();
+        let abs: ::url::Url = reader.read("//abs/@href").unwrap();
+        let plain: ::url::Url = reader.read("//plain/@href").unwrap();
+        let missing: Option<::url::Url> = reader.read("//missing/@href").unwrap();
+        assert_eq!(link.as_str(), "http://example.com/a/b/c.html");
+        assert_eq!(abs.as_str(), "https://example.org/x");
+        assert_eq!(plain.as_str(), "http://example.com/a/d.html");
+        assert_eq!(missing, None);
+
+        let reader = Reader::from_str(r#"<a href="rel.html"/>"#, None).unwrap();
+        assert!(reader.read::<::url::Url, _>("//@href").is_err());
+    }
+
+    #[cfg(all(feature = "uuid", feature = "rust_decimal", feature = "semver"))]
+    #[test]
+    fn optional_types_from_xml() {
+        let xml = r#"<?xml version="1.0"?>
+                     <root id="67e55044-10b1-426f-9247-bb680e5fe0c8" amount="10.05" version="1.2.3-beta"/>"#;
+        let reader = Reader::from_str(xml, None).unwrap();
+
+        let id: ::uuid::Uuid = reader.read("//@id").unwrap();
+        let amount: ::rust_decimal::Decimal = reader.read("//@amount").unwrap();
+        let version: ::semver::Version = reader.read("//@version").unwrap();
+        let missing: Option<::semver::Version> = reader.read("//@missing").unwrap();
+        assert_eq!(id.to_string(), "67e55044-10b1-426f-9247-bb680e5fe0c8");
+        assert_eq!(amount, ::rust_decimal::Decimal::new(1005, 2));
+        assert_eq!(version, ::semver::Version::parse("1.2.3-beta").unwrap());
+        assert_eq!(missing, None);
+        assert!(reader.read::<::uuid::Uuid, _>("//@amount").is_err());
+    }
+
+    #[test]
+    fn anchor_metadata() {
+        let xml = r#"<?xml version="1.0"?>
+                     <catalog xmlns="urn:default" xmlns:c="urn:catalog">
+                       <c:item id="1" xml:lang="en"><title/><price/></c:item>
+                       <c:item id="2"/><!-- comment -->
+                     </catalog>"#;
+        let mut context = Context::new();
+        context.set_namespace("c", "urn:catalog");
+        context.set_namespace("d", "urn:default");
+        let reader = Reader::from_str(xml, Some(&context)).unwrap();
+
+        assert_eq!(reader.anchor_kind(), Some(NodeKind::Root));
+        assert_eq!(reader.anchor_local_name(), None);
+        assert_eq!(reader.anchor_position(), Some(0));
+        assert_eq!(reader.anchor_path(), Some("/".to_string()));
+
+        let catalog = reader.with_nodeset_eval("/d:catalog").unwrap();
+        assert_eq!(catalog.anchor_kind(), Some(NodeKind::Element));
+        assert_eq!(catalog.anchor_local_name(), Some("catalog"));
+        assert_eq!(catalog.anchor_namespace_uri(), Some("urn:default"));
+        assert_eq!(catalog.anchor_prefix(), None);
+        assert_eq!(catalog.anchor_child_names(), vec!["item", "item"]);
+        assert_eq!(catalog.anchor_position(), Some(1));
+
+        let item = reader.with_nodeset_eval("//c:item[1]").unwrap();
+        assert_eq!(item.anchor_namespace_uri(), Some("urn:catalog"));
+        assert_eq!(item.anchor_prefix(), Some("c"));
+        assert_eq!(item.anchor_child_names(), vec!["title", "price"]);
+        let attributes = item.anchor_attributes();
+        assert_eq!(attributes.len(), 2);
+        assert_eq!(attributes["id"], "1");
+        assert_eq!(attributes["xml:lang"], "en");
+
+        let id = reader.with_nodeset_eval("//c:item[2]/@id").unwrap();
+        assert_eq!(id.anchor_kind(), Some(NodeKind::Attribute));
+        assert_eq!(id.anchor_local_name(), Some("id"));
+        assert_eq!(id.anchor_namespace_uri(), None);
+        assert!(id.anchor_attributes().is_empty());
+        assert_eq!(
+            id.anchor_path(),
+            Some("/catalog[1]/c:item[2]/@id".to_string())
+        );
+
+        let comment = reader.with_nodeset_eval("//comment()").unwrap();
+        assert_eq!(comment.anchor_kind(), Some(NodeKind::Comment));
+        assert_eq!(
+            comment.anchor_path(),
+            Some("/catalog[1]/comment()[1]".to_string())
+        );
+        assert!(comment.anchor_position() > id.anchor_position());
+
+        let missing = reader.with_nodeset_eval("//missing").unwrap();
+        assert_eq!(missing.anchor_kind(), None);
+        assert!(missing.anchor_child_names().is_empty());
+    }
+
+    #[test]
+    fn navigation() {
+        let xml = r#"<?xml version="1.0"?>
+                     <order currency="CHF"><line id="1"/><line id="2"/><line id="3"/></order>"#;
+        let reader = Reader::from_str(xml, None).unwrap();
+        let line = reader.with_nodeset_eval("//line[@id='2']").unwrap();
+
+        let currency: String = line.parent().read("@currency").unwrap();
+        assert_eq!(currency, "CHF");
+
+        let ancestors = line.ancestors();
+        assert_eq!(ancestors.len(), 2);
+        assert_eq!(ancestors[0].anchor_local_name(), Some("order"));
+        assert_eq!(ancestors[1].anchor_kind(), Some(NodeKind::Root));
+
+        let following: Vec<String> = line
+            .following_siblings()
+            .iter()
+            .map(|r| r.read("@id").unwrap())
+            .collect();
+        assert_eq!(following, vec!["3".to_string()]);
+        let preceding: Vec<String> = line
+            .preceding_siblings()
+            .iter()
+            .map(|r| r.read("@id").unwrap())
+            .collect();
+        assert_eq!(preceding, vec!["1".to_string()]);
+
+        let root = line.root();
+        assert_eq!(root.anchor_kind(), Some(NodeKind::Root));
+        let ids: Vec<u32> = root.read("//line/@id").unwrap();
+        assert_eq!(ids, vec![1, 2, 3]);
+
+        assert_eq!(reader.parent().anchor_node(), None);
+        let missing = reader.with_nodeset_eval("//missing").unwrap();
+        assert_eq!(missing.root().anchor_node(), None);
+        assert!(missing.ancestors().is_empty());
+    }
+
+    #[test]
+    fn strict_mode() {
+        let xml = r#"<?xml version="1.0"?>
+                     <order id="7" currency="CHF"><line id="1"><note/></line><line id="2"/><total>3</total></order>"#;
+        let mut reader = Reader::from_str(xml, None).unwrap();
+        assert!(reader.unread().is_empty());
+
+        reader.set_strict(true);
+        let ids: Vec<u32> = reader.read("//line/@id").unwrap();
+        assert_eq!(ids, vec![1, 2]);
+        assert_eq!(
+            reader.unread(),
+            vec![
+                "/order[1]/@currency",
+                "/order[1]/@id",
+                "/order[1]/line[1]/note[1]",
+                "/order[1]/total[1]",
+            ]
+        );
+
+        let total: Result<u32, Error> = reader.read_strict("//total");
+        assert_eq!(total.unwrap(), 3);
+        let line = reader.read_strict::<Option<String>, _>("//line[1]");
+        assert!(line.is_err());
+    }
+
+    #[test]
+    fn strict_mode_same_qualified_names() {
+        let xml = r#"<a><p:b xmlns:p="u1"/><p:b xmlns:p="u2"/></a>"#;
+        let mut context = Context::new();
+        context.set_namespace("u2", "u2");
+        let mut reader = Reader::from_str(xml, Some(&context)).unwrap();
+        reader.set_strict(true);
+
+        let _: Vec<String> = reader.read("//u2:b").unwrap();
+        assert_eq!(reader.unread(), vec!["/a[1]/p:b[1]"]);
+    }
+
+    #[test]
+    fn strict_mode_derived_readers() {
+        let xml = r#"<a><x/>text<b><c k="1"/><!-- c --><c k="2" l="3"/></b></a>"#;
+        let mut reader = Reader::from_str(xml, None).unwrap();
+        reader.set_strict(true);
+
+        let b = reader.with_nodeset_eval("/a/b").unwrap();
+        let k: u32 = b.read("c[2]/@k").unwrap();
+        assert_eq!(k, 2);
+        assert_eq!(b.unread(), vec!["/a[1]/b[1]/c[1]", "/a[1]/b[1]/c[2]/@l"]);
+        assert_eq!(
+            reader.unread(),
+            vec!["/a[1]/x[1]", "/a[1]/b[1]/c[1]", "/a[1]/b[1]/c[2]/@l"]
+        );
     }
 
     #[test]
@@ -0,0 +1,400 @@
+// Copyright 2017-2019 Leonardo Schwarz <mail@leoschwarz.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Repairing common faults of almost well-formed documents.
+//!
+//! `Reader::from_str_recovering` repairs the input before parsing it:
+//!
+//! - characters not allowed in XML, and character references to them,
+//!   are removed,
+//! - references to undefined entities and stray `&` characters are
+//!   escaped, as are `<` characters not starting markup,
+//! - end tags without a matching start tag are removed, and elements left
+//!   open by a mismatched end tag or at the end of the input are closed.
+//!
+//! Every repair is reported as a `Warning`. Faults which cannot be
+//! repaired still fail with `ErrorKind::ParseXml`.
+//!
+//! # Examples
+//! ```
+//! use xpath_reader::Reader;
+//!
+//! let xml = "<feed><item>Fish &chips;\u{1}</item><item>Salt & vinegar</feed>";
+//! let (reader, warnings) = Reader::from_str_recovering(xml, None).unwrap();
+//!
+//! let items: Vec<String> = reader.read("//item").unwrap();
+//! assert_eq!(items, vec!["Fish &chips;", "Salt & vinegar"]);
+//!
+//! let warnings: Vec<String> = warnings.iter().map(|w| w.to_string()).collect();
+//! assert_eq!(
+//!     warnings,
+//!     vec![
+//!         "1:18: Escaped reference to undefined entity 'chips'.",
+//!         "1:25: Removed invalid character U+0001.",
+//!         "1:44: Escaped '&' not starting a reference.",
+//!         "1:53: Closed element 'item' left open.",
+//!     ]
+//! );
+//! ```
+
+use std::fmt;
+
+/// A repair made to the input.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Warning {
+    /// The line of the fault, starting at 1.
+    pub line: usize,
+    /// The column of the fault in characters, starting at 1.
+    pub column: usize,
+    /// A description of the repair.
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+fn is_xml_char(c: char) -> bool {
+    match c {
+        '\t' | '\n' | '\r' => true,
+        '\u{0}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}' => false,
+        _ => true,
+    }
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_' || c == ':'
+}
+
+struct Repairer<'a> {
+    src: &'a str,
+    pos: usize,
+    out: String,
+    /// The offsets and messages of the repairs, not necessarily in order.
+    warnings: Vec<(usize, String)>,
+    open: Vec<&'a str>,
+}
+
+impl<'a> Repairer<'a> {
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn warn(&mut self, offset: usize, message: String) {
+        self.warnings.push((offset, message));
+    }
+
+    /// Returns the repaired document and the warnings, ordered by offset.
+    fn finish(mut self) -> (String, Vec<Warning>) {
+        self.warnings.sort_by_key(|&(offset, _)| offset);
+        let (mut last, mut line, mut column) = (0, 1, 1);
+        let mut warnings = Vec::with_capacity(self.warnings.len());
+        for (offset, message) in self.warnings {
+            for c in self.src[last..offset].chars() {
+                if c == '\n' {
+                    line += 1;
+                    column = 1;
+                } else {
+                    column += 1;
+                }
+            }
+            last = offset;
+            warnings.push(Warning {
+                line,
+                column,
+                message,
+            });
+        }
+        (self.out, warnings)
+    }
+
+    /// Copies the input up to `end`, removing invalid characters.
+    fn copy_until(&mut self, end: usize) {
+        while self.pos < end {
+            let c = self.rest().chars().next().unwrap();
+            if is_xml_char(c) {
+                self.out.push(c);
+            } else {
+                let pos = self.pos;
+                self.warn(
+                    pos,
+                    format!("Removed invalid character U+{:04X}.", c as u32),
+                );
+            }
+            self.pos += c.len_utf8();
+        }
+    }
+
+    /// Copies a construct ending with `close`, adding `close` if missing.
+    fn copy_through(&mut self, close: &str) {
+        match self.rest().find(close) {
+            Some(end) => {
+                let end = self.pos + end + close.len();
+                self.copy_until(end);
+            }
+            None => {
+                let (start, end) = (self.pos, self.src.len());
+                self.copy_until(end);
+                self.out.push_str(close);
+                self.warn(start, format!("Added missing '{}'.", close));
+            }
+        }
+    }
+
+    /// Copies a reference at the current `&`, escaping it if it is not
+    /// valid.
+    fn reference(&mut self) {
+        let rest = self.rest();
+        let end = rest[1..]
+            .find(|c: char| c == ';' || c == '&' || c == '<' || c.is_whitespace())
+            .map(|e| e + 1)
+            .filter(|&e| rest[e..].starts_with(';'));
+        let start = self.pos;
+        let end = match end {
+            Some(end) => end,
+            None => {
+                self.out.push_str("&amp;");
+                self.pos += 1;
+                self.warn(start, "Escaped '&' not starting a reference.".to_string());
+                return;
+            }
+        };
+
+        let name = &rest[1..end];
+        if let Some(number) = name.strip_prefix('#') {
+            let value = match number.strip_prefix('x') {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => number.parse().ok(),
+            };
+            match value.and_then(::std::char::from_u32) {
+                Some(c) if is_xml_char(c) => self.out.push_str(&rest[..=end]),
+                _ => self.warn(
+                    start,
+                    format!("Removed invalid character reference '&{};'.", name),
+                ),
+            }
+        } else if ["lt", "gt", "amp", "apos", "quot"].contains(&name) {
+            self.out.push_str(&rest[..=end]);
+        } else {
+            self.out.push_str("&amp;");
+            self.out.push_str(name);
+            self.out.push(';');
+            self.warn(
+                start,
+                format!("Escaped reference to undefined entity '{}'.", name),
+            );
+        }
+        self.pos += end + 1;
+    }
+
+    fn start_tag(&mut self) {
+        let rest = self.rest();
+        let name_len = rest[1..]
+            .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+            .map_or(rest.len(), |e| e + 1);
+        let name = &rest[1..name_len];
+        self.out.push_str(&rest[..name_len]);
+        self.pos += name_len;
+
+        let mut quote = None;
+        loop {
+            let c = match self.rest().chars().next() {
+                Some(c) => c,
+                None => {
+                    let pos = self.pos;
+                    if !self.out.ends_with('/') {
+                        self.open.push(name);
+                    }
+                    self.out.push('>');
+                    self.warn(pos, format!("Closed start tag of '{}'.", name));
+                    break;
+                }
+            };
+            match (quote, c) {
+                (Some(q), _) if q == c => quote = None,
+                (Some(_), '&') => {
+                    self.reference();
+                    continue;
+                }
+                (Some(_), '<') => {
+                    let pos = self.pos;
+                    self.out.push_str("&lt;");
+                    self.pos += 1;
+                    self.warn(pos, "Escaped '<' in attribute value.".to_string());
+                    continue;
+                }
+                (None, '"') | (None, '\'') => quote = Some(c),
+                (None, '>') => {
+                    if !self.out.ends_with('/') {
+                        self.open.push(name);
+                    }
+                    self.out.push('>');
+                    self.pos += 1;
+                    break;
+                }
+                _ => {}
+            }
+            let end = self.pos + c.len_utf8();
+            self.copy_until(end);
+        }
+    }
+
+    fn end_tag(&mut self) {
+        let start = self.pos;
+        let rest = self.rest();
+        let end = rest.find('>').map_or(rest.len(), |e| e + 1);
+        let name = rest[2..end].trim_end_matches('>').trim();
+        self.pos += end;
+
+        match self.open.iter().rposition(|open| *open == name) {
+            Some(i) => {
+                while self.open.len() > i + 1 {
+                    let unclosed = self.open.pop().unwrap();
+                    self.out.push_str(&format!("</{}>", unclosed));
+                    self.warn(start, format!("Closed element '{}' left open.", unclosed));
+                }
+                self.open.pop();
+                self.out.push_str(&format!("</{}>", name));
+            }
+            None => self.warn(start, format!("Removed unmatched end tag '{}'.", name)),
+        }
+    }
+
+    fn run(&mut self) {
+        while let Some(c) = self.rest().chars().next() {
+            let rest = self.rest();
+            if rest.starts_with("<!--") {
+                self.copy_through("-->");
+            } else if rest.starts_with("<![CDATA[") {
+                self.copy_through("]]>");
+            } else if rest.starts_with("<?") {
+                self.copy_through("?>");
+            } else if rest.starts_with("<!") {
+                let end = self.pos + declaration_end(rest);
+                self.copy_until(end);
+            } else if rest.starts_with("</") {
+                self.end_tag();
+            } else if c == '<' && rest[1..].starts_with(is_name_start) {
+                self.start_tag();
+            } else if c == '<' {
+                let pos = self.pos;
+                self.out.push_str("&lt;");
+                self.pos += 1;
+                self.warn(pos, "Escaped '<' not starting markup.".to_string());
+            } else if c == '&' {
+                self.reference();
+            } else {
+                let end = self.pos + c.len_utf8();
+                self.copy_until(end);
+            }
+        }
+        let end = self.src.len();
+        while let Some(unclosed) = self.open.pop() {
+            self.out.push_str(&format!("</{}>", unclosed));
+            self.warn(end, format!("Closed element '{}' left open.", unclosed));
+        }
+    }
+}
+
+/// Returns the length of a document type declaration, including its
+/// internal subset.
+fn declaration_end(declaration: &str) -> usize {
+    let mut quote = None;
+    let mut brackets = 0;
+    for (i, c) in declaration.char_indices() {
+        match (quote, c) {
+            (Some(q), _) if q == c => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '[') => brackets += 1,
+            (None, ']') => brackets -= 1,
+            (None, '>') if brackets == 0 => return i + 1,
+            _ => {}
+        }
+    }
+    declaration.len()
+}
+
+/// Repairs `xml`, returning the repaired document and the repairs made.
+pub(crate) fn repair(xml: &str) -> (String, Vec<Warning>) {
+    let mut repairer = Repairer {
+        src: xml,
+        pos: 0,
+        out: String::with_capacity(xml.len()),
+        warnings: Vec::new(),
+        open: Vec::new(),
+    };
+    repairer.run();
+    repairer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use errors::ErrorKind;
+    use reader::Reader;
+
+    #[test]
+    fn repairs() {
+        let (xml, warnings) = repair(
+            "<?xml version=\"1.0\"?>\n<a x=\"1 < 2 &foo;\">\n  <b>&#0;&#65;&lt;</c> 3 < 4<!-- \u{b} --></a>",
+        );
+        assert_eq!(
+            xml,
+            "<?xml version=\"1.0\"?>\n<a x=\"1 &lt; 2 &amp;foo;\">\n  <b>&#65;&lt; 3 &lt; 4<!--  --></b></a>"
+        );
+        let warnings: Vec<String> = warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            warnings,
+            vec![
+                "2:9: Escaped '<' in attribute value.",
+                "2:13: Escaped reference to undefined entity 'foo'.",
+                "3:6: Removed invalid character reference '&#0;'.",
+                "3:19: Removed unmatched end tag 'c'.",
+                "3:26: Escaped '<' not starting markup.",
+                "3:34: Removed invalid character U+000B.",
+                "3:39: Closed element 'b' left open.",
+            ]
+        );
+    }
+
+    #[test]
+    fn recovering_reader() {
+        let (reader, warnings) = Reader::from_str_recovering("<a><b>text</b></a>", None).unwrap();
+        assert!(warnings.is_empty());
+        let text: String = reader.read("//b").unwrap();
+        assert_eq!(text, "text");
+
+        let (reader, warnings) = Reader::from_str_recovering("<a><b>text</b><c", None).unwrap();
+        assert_eq!(warnings.len(), 3);
+        let text: String = reader.read("/a/b").unwrap();
+        assert_eq!(text, "text");
+
+        let (_, warnings) = Reader::from_str_recovering("<a><!-- \u{1}", None).unwrap();
+        let warnings: Vec<String> = warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            warnings,
+            vec![
+                "1:4: Added missing '-->'.",
+                "1:9: Removed invalid character U+0001.",
+                "1:10: Closed element 'a' left open.",
+            ]
+        );
+
+        let error = Reader::from_str_recovering("<a/><b/>", None).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::ParseXml);
+    }
+}
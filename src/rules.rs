@@ -0,0 +1,215 @@
+// Copyright 2017-2019 Leonardo Schwarz <mail@leoschwarz.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Declarative validation with XPath assertions.
+//!
+//! Similar to Schematron, a rule selects context nodes and asserts a test
+//! expression for each of them. The expressions are evaluated with the
+//! context of the reader, so namespaces registered there can be used.
+//!
+//! # Examples
+//! ```
+//! use xpath_reader::Reader;
+//! use xpath_reader::rules::RuleSet;
+//!
+//! let mut rules = RuleSet::new();
+//! rules
+//!     .assert("//item", "@id", "Every item needs an id.")
+//!     .unwrap()
+//!     .assert("/order", "sum(item/@amount) = total", "The total is wrong.")
+//!     .unwrap();
+//!
+//! let xml = r#"<order><item id="1" amount="2"/><item amount="3"/><total>6</total></order>"#;
+//! let reader = Reader::from_str(xml, None).unwrap();
+//!
+//! let report = rules.validate(&reader).unwrap();
+//! assert!(!report.is_valid());
+//! assert_eq!(report.failures[0].path, "/order[1]/item[2]");
+//! assert_eq!(report.failures[1].message, "The total is wrong.");
+//! ```
+
+use errors::Error;
+use expression::{self, XPathExpression};
+use node;
+use reader::Reader;
+use std::fmt;
+
+/// An assertion evaluated for each node selected by a context expression.
+#[derive(Debug)]
+struct Rule {
+    context_source: String,
+    context: XPathExpression<'static>,
+    test_source: String,
+    test: XPathExpression<'static>,
+    message: String,
+}
+
+/// A set of rules validated together.
+#[derive(Debug, Default)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+}
+
+/// A failed assertion for one context node.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Failure {
+    /// The message of the rule.
+    pub message: String,
+    /// The location path of the context node.
+    pub path: String,
+    /// The context expression of the rule.
+    pub context: String,
+    /// The test expression of the rule.
+    pub test: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+/// The result of validating a document against a rule set.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Report {
+    /// The failed assertions, in the order of the rules and the context
+    /// nodes in document order.
+    pub failures: Vec<Failure>,
+}
+
+impl Report {
+    /// Returns true if no assertion failed.
+    pub fn is_valid(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl RuleSet {
+    /// Creates an empty rule set.
+    pub fn new() -> Self {
+        RuleSet::default()
+    }
+
+    /// Adds a rule asserting that `test`, converted to a boolean, is true
+    /// for each node selected by `context`.
+    ///
+    /// Both expressions are parsed immediately, `context` has to evaluate
+    /// to a nodeset and `test` is evaluated relative to each of its nodes.
+    pub fn assert<S: Into<String>>(
+        &mut self,
+        context: &str,
+        test: &str,
+        message: S,
+    ) -> Result<&mut Self, Error> {
+        self.rules.push(Rule {
+            context_source: context.to_string(),
+            context: expression::parse(context)?,
+            test_source: test.to_string(),
+            test: expression::parse(test)?,
+            message: message.into(),
+        });
+        Ok(self)
+    }
+
+    /// Returns the number of rules.
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Returns true if there are no rules.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Validates the document of `reader`, relative expressions being
+    /// evaluated relative to its anchor node.
+    ///
+    /// Errors are only returned if an expression cannot be evaluated.
+    pub fn validate<'d>(&self, reader: &'d Reader<'d>) -> Result<Report, Error> {
+        let mut report = Report::default();
+        for rule in &self.rules {
+            let context = reader.with_nodeset_eval(&rule.context)?;
+            for node in context.anchor_nodeset().document_order() {
+                let node_reader = reader.with_nodes(Some(node));
+                if !node_reader.evaluate(&rule.test)?.boolean() {
+                    report.failures.push(Failure {
+                        message: rule.message.clone(),
+                        path: node::node_path(node),
+                        context: rule.context_source.clone(),
+                        test: rule.test_source.clone(),
+                    });
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use errors::ErrorKind;
+    use sxd_xpath::Context;
+
+    #[test]
+    fn validate_namespaced() {
+        let xml = r#"<i:invoice xmlns:i="urn:invoice"><i:line amount="5"/><i:line amount="-1"/><i:line/></i:invoice>"#;
+        let mut context = Context::new();
+        context.set_namespace("inv", "urn:invoice");
+        let reader = Reader::from_str(xml, Some(&context)).unwrap();
+
+        let mut rules = RuleSet::new();
+        rules
+            .assert(
+                "//inv:line",
+                "@amount >= 0",
+                "Amounts must not be negative.",
+            )
+            .unwrap()
+            .assert("/inv:invoice", "count(inv:line) > 0", "Empty invoice.")
+            .unwrap();
+        assert_eq!(rules.len(), 2);
+
+        let report = rules.validate(&reader).unwrap();
+        let failures: Vec<String> = report.failures.iter().map(|f| f.to_string()).collect();
+        assert_eq!(
+            failures,
+            vec![
+                "/i:invoice[1]/i:line[2]: Amounts must not be negative.",
+                "/i:invoice[1]/i:line[3]: Amounts must not be negative.",
+            ]
+        );
+        assert_eq!(report.failures[0].test, "@amount >= 0");
+
+        let line = reader.with_nodeset_eval("//inv:line[1]").unwrap();
+        assert_eq!(rules.validate(&line).unwrap().failures.len(), 2);
+    }
+
+    #[test]
+    fn invalid_rules() {
+        let mut rules = RuleSet::new();
+        let error = rules.assert("//a", "@id = ", "Broken.").err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::ParseXPath);
+        assert!(rules.is_empty());
+
+        rules
+            .assert("count(//a)", "true()", "Not a nodeset.")
+            .unwrap();
+        let reader = Reader::from_str("<a/>", None).unwrap();
+        assert_eq!(
+            rules.validate(&reader).unwrap_err().kind(),
+            ErrorKind::EvalXPath
+        );
+    }
+}
@@ -0,0 +1,651 @@
+// Copyright 2017-2019 Leonardo Schwarz <mail@leoschwarz.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reading documents from asynchronous byte streams.
+//!
+//! `Reader::from_async_read` returns a future reading a whole document
+//! from a `tokio::io::AsyncRead`, decompressing it like
+//! `Reader::from_read`.
+//!
+//! A `RecordStream` instead splits the incoming bytes into records, the
+//! elements with a given name, and parses each record as soon as it is
+//! complete. Only the current record is buffered, so arbitrarily long
+//! payloads of small records can be processed. Namespace declarations and
+//! the `xml:lang` of the ancestors of a record are copied onto it, so
+//! prefixed names and languages keep working. `xml:base` is not copied, so
+//! relative references in records resolve against the record alone.
+//! Records nested in other records are part of the outer record. Ending
+//! the input before all elements are closed yields an error.
+//!
+//! Unlike `Reader::from_async_read`, a `RecordStream` does not decompress
+//! its input.
+//!
+//! This module requires the `async` feature.
+//!
+//! # Examples
+//! ```edition2018
+//! # extern crate futures_core;
+//! # extern crate tokio;
+//! # extern crate xpath_reader;
+//! use futures_core::Stream;
+//! use std::future::poll_fn;
+//! use std::pin::Pin;
+//! use xpath_reader::stream::RecordStream;
+//! use xpath_reader::{FromXml, FromXmlResult, Reader};
+//!
+//! struct Book {
+//!     title: String,
+//! }
+//!
+//! impl FromXml for Book {
+//!     fn from_xml<'d>(reader: &'d Reader<'d>) -> FromXmlResult<Self> {
+//!         Ok(Book {
+//!             title: reader.read("title")?,
+//!         })
+//!     }
+//! }
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() {
+//! let body = &b"<books><book><title>Neuromancer</title></book><book><title>Count Zero</title></book></books>"[..];
+//!
+//! let mut books = RecordStream::<_, Book>::new(body, "book", None);
+//! let mut titles = Vec::new();
+//! while let Some(book) = poll_fn(|cx| Pin::new(&mut books).poll_next(cx)).await {
+//!     titles.push(book.unwrap().title);
+//! }
+//! assert_eq!(titles, vec!["Neuromancer", "Count Zero"]);
+//! # }
+//! ```
+
+use errors::{Error, ErrorKind};
+use futures_core::Stream;
+use limits::Limits;
+use reader::{FromXml, Reader};
+use std::future::Future;
+use std::io;
+use std::marker::PhantomData;
+use std::mem;
+use std::pin::Pin;
+use std::task::{self, Poll};
+use sxd_xpath::Context;
+use tokio::io::{AsyncRead, ReadBuf};
+
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Reads the next chunk of `read` into `data`, returning its length.
+fn poll_chunk<R: AsyncRead + Unpin>(
+    read: &mut R,
+    cx: &mut task::Context,
+    data: &mut Vec<u8>,
+) -> Poll<Result<usize, Error>> {
+    let mut chunk = [0u8; CHUNK_SIZE];
+    let mut buf = ReadBuf::new(&mut chunk);
+    match Pin::new(read).poll_read(cx, &mut buf) {
+        Poll::Pending => Poll::Pending,
+        Poll::Ready(Err(e)) => Poll::Ready(Err(io_error(e))),
+        Poll::Ready(Ok(())) => {
+            data.extend_from_slice(buf.filled());
+            Poll::Ready(Ok(buf.filled().len()))
+        }
+    }
+}
+
+fn io_error(e: io::Error) -> Error {
+    Error::internal(e, ErrorKind::Other)
+}
+
+/// A future reading a whole document, returned by
+/// `Reader::from_async_read`.
+pub struct FromAsyncRead<'d, R> {
+    read: R,
+    data: Vec<u8>,
+    context: Option<&'d Context<'d>>,
+    limits: Limits,
+}
+
+impl<'d, R> FromAsyncRead<'d, R> {
+    pub(crate) fn new(read: R, context: Option<&'d Context<'d>>, limits: Limits) -> Self {
+        FromAsyncRead {
+            read,
+            data: Vec::new(),
+            context,
+            limits,
+        }
+    }
+}
+
+impl<'d, R: AsyncRead + Unpin> Future for FromAsyncRead<'d, R> {
+    type Output = Result<Reader<'d>, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+        loop {
+            match poll_chunk(&mut this.read, cx, &mut this.data) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Ready(Ok(0)) => {
+                    let data = mem::take(&mut this.data);
+                    return Poll::Ready(Reader::from_read_with_limits(
+                        &data[..],
+                        this.context,
+                        &this.limits,
+                    ));
+                }
+                Poll::Ready(Ok(_)) => {
+                    if let Err(e) = this.limits.check_input_size(this.data.len()) {
+                        return Poll::Ready(Err(e));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A stream of the records of a document read from an `AsyncRead`,
+/// parsed as values of type `T`.
+///
+/// Each record is parsed as a document of its own, and `T` is read from
+/// its root element. A record failing to parse yields an error and the
+/// stream continues with the next record, while errors reading the input
+/// end the stream.
+pub struct RecordStream<'c, R, T> {
+    read: R,
+    splitter: Splitter,
+    context: Option<&'c Context<'c>>,
+    eof: bool,
+    done: bool,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<'c, R, T> RecordStream<'c, R, T> {
+    /// Creates a stream of the elements named `record` in the document
+    /// read from `read`.
+    ///
+    /// `record` is compared with the qualified names of elements as they
+    /// appear in the document, including any prefix. A context can be
+    /// specified for reading the records.
+    pub fn new(read: R, record: &str, context: Option<&'c Context<'c>>) -> Self {
+        RecordStream {
+            read,
+            splitter: Splitter::new(record),
+            context,
+            eof: false,
+            done: false,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'c, R: AsyncRead + Unpin, T: FromXml> Stream for RecordStream<'c, R, T> {
+    type Item = Result<T, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut task::Context) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        while !this.done {
+            let result = match this.splitter.next_record(this.eof) {
+                Ok(Some(record)) => Some(Ok(record)),
+                Ok(None) if this.eof => None,
+                Ok(None) => match poll_chunk(&mut this.read, cx, &mut this.splitter.data) {
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(Ok(len)) => {
+                        this.eof = len == 0;
+                        continue;
+                    }
+                    Poll::Ready(Err(e)) => Some(Err(e)),
+                },
+                Err(e) => Some(Err(e)),
+            };
+            match result {
+                Some(Ok(record)) => {
+                    let reader = match Reader::from_str(&record, this.context) {
+                        Ok(reader) => reader,
+                        Err(e) => return Poll::Ready(Some(Err(e))),
+                    };
+                    return Poll::Ready(Some(reader.read("/*")));
+                }
+                Some(Err(e)) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(e)));
+                }
+                None => this.done = true,
+            }
+        }
+        Poll::Ready(None)
+    }
+}
+
+/// The kinds of markup distinguished when splitting records.
+enum Token {
+    StartTag { name_end: usize, empty: bool },
+    EndTag,
+    Other,
+}
+
+/// Splits a document into records incrementally.
+struct Splitter {
+    record: String,
+    data: Vec<u8>,
+    /// The scan position in `data`.
+    pos: usize,
+    /// The start of the current record, the end of its name and the end of
+    /// its start tag.
+    start: Option<(usize, usize, usize)>,
+    /// The depth of elements within the current record.
+    depth: usize,
+    /// The inherited attributes of the open ancestors of records.
+    inherited: Vec<Vec<(String, String)>>,
+}
+
+impl Splitter {
+    fn new(record: &str) -> Self {
+        Splitter {
+            record: record.to_string(),
+            data: Vec::new(),
+            pos: 0,
+            start: None,
+            depth: 0,
+            inherited: Vec::new(),
+        }
+    }
+
+    /// Returns the next complete record, if any. At the end of the input
+    /// an incomplete record is an error.
+    fn next_record(&mut self, eof: bool) -> Result<Option<String>, Error> {
+        loop {
+            if self.start.is_none() && self.pos > 0 {
+                self.data.drain(..self.pos);
+                self.pos = 0;
+            }
+            let offset = match self.data[self.pos..].iter().position(|&b| b == b'<') {
+                Some(offset) => offset,
+                None => {
+                    self.pos = self.data.len();
+                    return self.incomplete(eof);
+                }
+            };
+            let start = self.pos + offset;
+            let (end, token) = match token(&self.data[start..]) {
+                Some((len, token)) => (start + len, token),
+                None => {
+                    self.pos = start;
+                    return self.incomplete(eof);
+                }
+            };
+            self.pos = end;
+
+            match (token, self.start) {
+                (Token::StartTag { name_end, empty }, None) => {
+                    let name = &self.data[start + 1..start + name_end];
+                    if name == self.record.as_bytes() {
+                        self.start = Some((start, start + name_end, end));
+                        self.depth = 0;
+                        if empty {
+                            return self.emit(end).map(Some);
+                        }
+                        self.depth = 1;
+                    } else if !empty {
+                        let tag = String::from_utf8_lossy(&self.data[start..end]);
+                        self.inherited.push(inherited_attributes(&tag));
+                    }
+                }
+                (Token::EndTag, None) => {
+                    self.inherited.pop();
+                }
+                (Token::StartTag { empty: false, .. }, Some(_)) => self.depth += 1,
+                (Token::EndTag, Some(_)) => {
+                    self.depth -= 1;
+                    if self.depth == 0 {
+                        return self.emit(end).map(Some);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn incomplete(&self, eof: bool) -> Result<Option<String>, Error> {
+        if !eof {
+            Ok(None)
+        } else if self.start.is_some() {
+            Err(Error::internal(
+                format!("Unexpected end of input in record '{}'.", self.record),
+                ErrorKind::ParseXml,
+            ))
+        } else if !self.inherited.is_empty() || self.pos < self.data.len() {
+            Err(Error::internal(
+                format!(
+                    "Unexpected end of input with {} open elements.",
+                    self.inherited.len()
+                ),
+                ErrorKind::ParseXml,
+            ))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Removes the current record ending at `end` from the buffer,
+    /// copying the inherited attributes of its ancestors onto it.
+    fn emit(&mut self, end: usize) -> Result<String, Error> {
+        let (start, name_end, tag_end) = self.start.take().unwrap();
+        let record: Vec<u8> = self.data.drain(..end).skip(start).collect();
+        self.pos = 0;
+        let record =
+            String::from_utf8(record).map_err(|e| Error::internal(e, ErrorKind::ParseXml))?;
+
+        let name_end = name_end - start;
+        let own = inherited_attributes(&record[..tag_end - start]);
+        let mut declarations: Vec<&(String, String)> = Vec::new();
+        for declaration in self.inherited.iter().rev().flatten() {
+            let declared = |d: &(String, String)| d.0 == declaration.0;
+            if !own.iter().any(declared) && !declarations.iter().any(|d| declared(d)) {
+                declarations.push(declaration);
+            }
+        }
+
+        let mut xml = String::with_capacity(record.len());
+        xml.push_str(&record[..name_end]);
+        for (name, value) in declarations {
+            xml.push_str(&format!(" {}=\"{}\"", name, value));
+        }
+        xml.push_str(&record[name_end..]);
+        Ok(xml)
+    }
+}
+
+/// Returns the length and kind of the markup starting at the beginning of
+/// `data`, or `None` if it is incomplete.
+fn token(data: &[u8]) -> Option<(usize, Token)> {
+    let find = |pattern: &[u8], from: usize| {
+        data.get(from..)?
+            .windows(pattern.len())
+            .position(|w| w == pattern)
+            .map(|p| (from + p + pattern.len(), Token::Other))
+    };
+    match *data.get(1)? {
+        b'!' if data.starts_with(b"<!--") => find(b"-->", 4),
+        b'!' if data.starts_with(b"<![CDATA[") => find(b"]]>", 9),
+        b'!' if data.len() < 9 => None,
+        b'!' => {
+            let mut quote = None;
+            let mut brackets = 0;
+            for (i, &b) in data.iter().enumerate() {
+                match (quote, b) {
+                    (Some(q), _) if q == b => quote = None,
+                    (Some(_), _) => {}
+                    (None, b'"') | (None, b'\'') => quote = Some(b),
+                    (None, b'[') => brackets += 1,
+                    (None, b']') => brackets -= 1,
+                    (None, b'>') if brackets == 0 => return Some((i + 1, Token::Other)),
+                    _ => {}
+                }
+            }
+            None
+        }
+        b'?' => find(b"?>", 2),
+        b'/' => {
+            let end = data.iter().position(|&b| b == b'>')?;
+            Some((end + 1, Token::EndTag))
+        }
+        _ => {
+            let name_end = data
+                .iter()
+                .position(|&b| b.is_ascii_whitespace() || b == b'/' || b == b'>')?;
+            let mut quote = None;
+            for (i, &b) in data.iter().enumerate().skip(name_end) {
+                match (quote, b) {
+                    (Some(q), _) if q == b => quote = None,
+                    (Some(_), _) => {}
+                    (None, b'"') | (None, b'\'') => quote = Some(b),
+                    (None, b'>') => {
+                        let empty = data[i - 1] == b'/';
+                        return Some((i + 1, Token::StartTag { name_end, empty }));
+                    }
+                    _ => {}
+                }
+            }
+            None
+        }
+    }
+}
+
+/// Returns the attributes of a start tag inherited by records, namespace
+/// declarations and `xml:lang`, as pairs of name and value escaped for
+/// double quotes.
+fn inherited_attributes(tag: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let name_end = tag
+        .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+        .unwrap_or(tag.len());
+    let mut rest = &tag[name_end..];
+    loop {
+        rest = rest.trim_start();
+        let name_len = rest
+            .find(|c: char| c == '=' || c == '/' || c == '>' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        if name_len == 0 {
+            break;
+        }
+        let name = &rest[..name_len];
+        rest = rest[name_len..].trim_start();
+        let value = match rest.strip_prefix('=') {
+            Some(value) => value.trim_start(),
+            None => continue,
+        };
+        let quote = match value.chars().next() {
+            Some(q) if q == '"' || q == '\'' => q,
+            _ => break,
+        };
+        let end = match value[1..].find(quote) {
+            Some(end) => end + 1,
+            None => break,
+        };
+        if name == "xmlns" || name.starts_with("xmlns:") || name == "xml:lang" {
+            attributes.push((name.to_string(), value[1..end].replace('"', "&quot;")));
+        }
+        rest = &value[end + 1..];
+    }
+    attributes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::poll_fn;
+    use tokio::runtime::Builder;
+
+    /// Yields `size` bytes per read, returning `Pending` before each read.
+    struct Chunked {
+        data: Vec<u8>,
+        size: usize,
+        pending: bool,
+    }
+
+    impl Chunked {
+        fn new(data: &str, size: usize) -> Self {
+            Chunked {
+                data: data.as_bytes().to_vec(),
+                size,
+                pending: true,
+            }
+        }
+    }
+
+    impl AsyncRead for Chunked {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut task::Context,
+            buf: &mut ReadBuf,
+        ) -> Poll<io::Result<()>> {
+            self.pending = !self.pending;
+            if !self.pending {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let len = self.size.min(self.data.len()).min(buf.remaining());
+            buf.put_slice(&self.data[..len]);
+            self.data.drain(..len);
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    fn collect<T: FromXml>(xml: &str, record: &str, size: usize) -> Vec<Result<T, Error>> {
+        let mut stream = RecordStream::new(Chunked::new(xml, size), record, None);
+        let mut items = Vec::new();
+        while let Some(item) = block_on(poll_fn(|cx| Pin::new(&mut stream).poll_next(cx))) {
+            items.push(item);
+        }
+        items
+    }
+
+    fn records(xml: &str, record: &str) -> Vec<String> {
+        let mut splitter = Splitter::new(record);
+        splitter.data = xml.as_bytes().to_vec();
+        let mut records = Vec::new();
+        while let Some(record) = splitter.next_record(true).unwrap() {
+            records.push(record);
+        }
+        records
+    }
+
+    #[test]
+    fn from_async_read() {
+        let xml = "<?xml version=\"1.0\"?><book><title>Neuromancer</title></book>";
+        let reader = block_on(Reader::from_async_read(Chunked::new(xml, 7), None)).unwrap();
+        let title: String = reader.read("//title").unwrap();
+        assert_eq!(title, "Neuromancer");
+
+        let err = block_on(Reader::from_async_read(Chunked::new("<a><b></a>", 3), None))
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::ParseXml);
+
+        let mut body = Chunked::new(&format!("<a>{}</a>", "x".repeat(1000)), 10);
+        let limits = Limits::default().max_input_size(100);
+        let err = block_on(FromAsyncRead::new(&mut body, None, limits))
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), ErrorKind::LimitExceeded);
+        assert!(body.data.len() > 800);
+    }
+
+    #[test]
+    fn split_records() {
+        let xml = r#"<?xml version="1.0"?>
+            <!DOCTYPE feed [ <!ENTITY x "<entry>"> ]>
+            <feed>
+                <!-- <entry>commented</entry> -->
+                <entry id="1"><title>One</title></entry>
+                <entry id='2' note="a > b"/>
+                <entry><![CDATA[</entry>]]><entry>nested</entry></entry>
+                <?pi <entry>?>
+            </feed>"#;
+        assert_eq!(
+            records(xml, "entry"),
+            vec![
+                r#"<entry id="1"><title>One</title></entry>"#,
+                r#"<entry id='2' note="a > b"/>"#,
+                "<entry><![CDATA[</entry>]]><entry>nested</entry></entry>",
+            ]
+        );
+    }
+
+    #[test]
+    fn copy_namespaces() {
+        let xml = r#"<a:feed xmlns:a="urn:a" xmlns="urn:default">
+                <group xmlns:b='urn:b' xmlns:a="urn:a2">
+                    <a:entry xmlns="urn:own" b:x="1"/>
+                </group>
+                <a:entry/>
+            </a:feed>"#;
+        assert_eq!(
+            records(xml, "a:entry"),
+            vec![
+                r#"<a:entry xmlns:b="urn:b" xmlns:a="urn:a2" xmlns="urn:own" b:x="1"/>"#,
+                r#"<a:entry xmlns:a="urn:a" xmlns="urn:default"/>"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn quoted_markup_in_tags() {
+        let xml = r#"<feed xmlns:a="urn:x" xml:lang='say "hi"'><entry note="a>b" xmlns:a="urn:y"><a:n/></entry></feed>"#;
+        assert_eq!(
+            records(xml, "entry"),
+            vec![
+                r#"<entry xml:lang="say &quot;hi&quot;" note="a>b" xmlns:a="urn:y"><a:n/></entry>"#
+            ]
+        );
+        let items = collect::<String>(xml, "entry", 3);
+        assert_eq!(items.len(), 1);
+        assert!(items[0].is_ok());
+    }
+
+    #[test]
+    fn copy_language() {
+        let xml = r#"<feed xml:lang="en"><group xml:lang='de' title="xml:lang='fr'"><entry/><entry xml:lang="it"/></group><entry/></feed>"#;
+        assert_eq!(
+            records(xml, "entry"),
+            vec![
+                r#"<entry xml:lang="de"/>"#,
+                r#"<entry xml:lang="it"/>"#,
+                r#"<entry xml:lang="en"/>"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn stream_values() {
+        let xml = r#"<feed xmlns:a="urn:a"><entry><a:n>1</a:n></entry><other><a:n>9</a:n></other><entry><a:n>2</a:n></entry></feed>"#;
+        for &size in &[1, 5, 64] {
+            let items = collect::<u32>(xml, "entry", size);
+            let values: Vec<u32> = items.into_iter().map(Result::unwrap).collect();
+            assert_eq!(values, vec![1, 2]);
+        }
+    }
+
+    #[test]
+    fn stream_errors() {
+        let items = collect::<u32>("<feed><entry>x</entry><entry>2</entry></feed>", "entry", 4);
+        assert_eq!(items.len(), 2);
+        assert!(items[0].is_err());
+        assert_eq!(*items[1].as_ref().unwrap(), 2);
+
+        let items = collect::<u32>("<feed><entry>1</entry><entry>2</ent", "entry", 4);
+        assert_eq!(items.len(), 2);
+        assert_eq!(*items[0].as_ref().unwrap(), 1);
+        let err = items[1].as_ref().err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::ParseXml);
+        assert!(err.to_string().contains("Unexpected end of input"));
+
+        for xml in &[
+            "<feed><group><entry>1</entry></group>",
+            "<feed><entry>1</entry></fe",
+        ] {
+            let items = collect::<u32>(xml, "entry", 4);
+            assert_eq!(items.len(), 2);
+            assert_eq!(*items[0].as_ref().unwrap(), 1);
+            let err = items[1].as_ref().err().unwrap();
+            assert!(err.to_string().contains("open elements"), "{}", err);
+        }
+    }
+}
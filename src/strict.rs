@@ -0,0 +1,163 @@
+// Copyright 2017-2019 Leonardo Schwarz <mail@leoschwarz.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tracking of the nodes read in strict mode.
+//!
+//! An element counts as read if it or one of its descendants or attributes
+//! was selected by an expression, an attribute only if it was selected
+//! itself. Reading the string value of an element does not mark its child
+//! elements as read.
+
+use node;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use sxd_xpath::nodeset::{Node, Nodeset};
+
+/// The elements and attributes selected by the readers sharing a tracker.
+#[derive(Debug, Default)]
+pub(crate) struct Tracker {
+    read: RefCell<HashSet<Key>>,
+}
+
+impl Tracker {
+    pub(crate) fn mark(&self, nodeset: &Nodeset) {
+        let mut positions = Positions::default();
+        let mut read = self.read.borrow_mut();
+        for node in nodeset.iter() {
+            let key = match node {
+                Node::Element(_) => Key {
+                    path: positions.path(node),
+                    attribute: None,
+                },
+                Node::Attribute(a) => match a.parent() {
+                    Some(parent) => Key {
+                        path: positions.path(Node::Element(parent)),
+                        attribute: parent.attributes().iter().position(|&b| b == a),
+                    },
+                    None => continue,
+                },
+                _ => continue,
+            };
+            read.insert(key);
+        }
+    }
+
+    /// The paths of the unread elements and attributes in the subtree of
+    /// `node`, only listing the topmost unread element of a subtree.
+    pub(crate) fn unread(&self, node: Node) -> Vec<String> {
+        let read = self.read.borrow();
+        let mut positions = Positions::default();
+        let mut unread = Vec::new();
+        let elements = match node {
+            Node::Root(_) => node.children(),
+            _ => vec![node],
+        };
+        for e in elements {
+            if let Node::Element(_) = e {
+                let mut path = positions.path(e);
+                if !visit(e, &mut path, &read, &mut unread) {
+                    unread.push(e);
+                }
+            }
+        }
+        unread.into_iter().map(node::node_path).collect()
+    }
+}
+
+/// The position of an element or attribute in its document.
+///
+/// Unlike `Node` it has no lifetime, so readers sharing a tracker stay
+/// covariant. Readers never modify their document, so positions stay
+/// valid while the tracker is used.
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct Key {
+    /// The indexes of the element and its ancestors among the children of
+    /// their parents, starting below the root.
+    path: Vec<usize>,
+    /// The index of the attribute among the attributes of the element.
+    attribute: Option<usize>,
+}
+
+/// Computes the paths of nodes, caching them for the ancestors and
+/// siblings of the nodes of one nodeset.
+#[derive(Default)]
+struct Positions<'d> {
+    paths: HashMap<Node<'d>, Vec<usize>>,
+    indexes: HashMap<Node<'d>, usize>,
+}
+
+impl<'d> Positions<'d> {
+    fn path(&mut self, node: Node<'d>) -> Vec<usize> {
+        if let Some(path) = self.paths.get(&node) {
+            return path.clone();
+        }
+        let path = match node.parent() {
+            Some(parent) => {
+                if !self.paths.contains_key(&parent) {
+                    let children = parent.children().into_iter().enumerate();
+                    self.indexes.extend(children.map(|(i, child)| (child, i)));
+                }
+                let mut path = self.path(parent);
+                path.push(self.indexes[&node]);
+                path
+            }
+            None => Vec::new(),
+        };
+        self.paths.insert(node, path.clone());
+        path
+    }
+}
+
+/// Collects the unread content of the element `node` at `path`, returning
+/// whether anything in its subtree was read.
+fn visit<'d>(
+    node: Node<'d>,
+    path: &mut Vec<usize>,
+    read: &HashSet<Key>,
+    unread: &mut Vec<Node<'d>>,
+) -> bool {
+    let mut key = Key {
+        path: path.clone(),
+        attribute: None,
+    };
+    let mut any = read.contains(&key);
+    let mut pending = Vec::new();
+    if let Node::Element(e) = node {
+        for (i, a) in e.attributes().into_iter().enumerate() {
+            key.attribute = Some(i);
+            if read.contains(&key) {
+                any = true;
+            } else {
+                pending.push(Node::Attribute(a));
+            }
+        }
+    }
+    for (i, child) in node.children().into_iter().enumerate() {
+        if let Node::Element(_) = child {
+            let mut child_unread = Vec::new();
+            path.push(i);
+            if visit(child, path, read, &mut child_unread) {
+                any = true;
+                pending.extend(child_unread);
+            } else {
+                pending.push(child);
+            }
+            path.pop();
+        }
+    }
+    if any {
+        unread.extend(pending);
+    }
+    any
+}
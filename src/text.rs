@@ -0,0 +1,199 @@
+// Copyright 2017-2019 Leonardo Schwarz <mail@leoschwarz.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Text extraction modes.
+//!
+//! `String::from_xml` returns the XPath string value of the anchor node,
+//! which is the concatenation of all descendant text, including any
+//! indentation whitespace. The types in this module provide cleaner
+//! alternatives for pretty-printed and mixed content documents.
+//!
+//! The `Option<T>` variants of these types return `None` if there is no
+//! anchor node or the extracted text is empty.
+//!
+//! # Examples
+//! ```
+//! use xpath_reader::Reader;
+//! use xpath_reader::text::{DirectText, Normalized, Trimmed};
+//!
+//! let xml = r#"<?xml version="1.0"?>
+//! <p>
+//!     Hello   <b>bold</b>
+//!     world
+//! </p>"#;
+//! let reader = Reader::from_str(xml, None).unwrap();
+//!
+//! let trimmed: Trimmed = reader.read("//b").unwrap();
+//! let normalized: Normalized = reader.read("//p").unwrap();
+//! let direct: DirectText = reader.read("//p").unwrap();
+//! assert_eq!(trimmed.0, "bold");
+//! assert_eq!(normalized.0, "Hello bold world");
+//! assert_eq!(direct.normalized(), "Hello world");
+//! ```
+
+use errors::Error;
+use reader::{FromXml, FromXmlResult, Reader};
+use sxd_xpath::nodeset::Node;
+
+/// The string value with leading and trailing XML whitespace (space, tab,
+/// carriage return and line feed) removed.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Trimmed(pub String);
+
+/// The string value with XPath `normalize-space` semantics applied.
+///
+/// Leading and trailing whitespace is removed and every other sequence
+/// of whitespace characters is replaced by a single space.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Normalized(pub String);
+
+/// The concatenated text of the direct text children of the anchor node.
+///
+/// Text inside of nested elements is excluded. For anchor nodes which
+/// are not elements (e.g. attributes) this is their string value.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct DirectText(pub String);
+
+impl DirectText {
+    /// Returns the text with `normalize-space` semantics applied.
+    pub fn normalized(&self) -> String {
+        normalize_space(&self.0)
+    }
+}
+
+macro_rules! impl_text_type {
+    ( $( $type:ident => $extract:expr ),* ) => {
+        $(
+            impl $type {
+                /// Returns the extracted text.
+                pub fn into_inner(self) -> String {
+                    self.0
+                }
+            }
+
+            impl From<$type> for String {
+                fn from(t: $type) -> String {
+                    t.0
+                }
+            }
+
+            impl FromXml for $type {
+                fn from_xml<'d>(reader: &'d Reader<'d>) -> FromXmlResult<Self> {
+                    let extract: fn(Node) -> String = $extract;
+                    reader
+                        .preferred_node()
+                        .ok_or_else(|| Error::custom_msg("Missing (anchor) node."))
+                        .map(|node| $type(extract(node)))
+                }
+            }
+
+            impl FromXml for Option<$type> {
+                fn from_xml<'d>(reader: &'d Reader<'d>) -> FromXmlResult<Self> {
+                    let extract: fn(Node) -> String = $extract;
+                    Ok(reader.preferred_node().map(extract).and_then(|s| {
+                        if s.is_empty() {
+                            None
+                        } else {
+                            Some($type(s))
+                        }
+                    }))
+                }
+            }
+        )*
+    }
+}
+
+impl_text_type!(
+    Trimmed => |node| node.string_value().trim_matches(is_xml_whitespace).to_string(),
+    Normalized => |node| normalize_space(&node.string_value()),
+    DirectText => direct_text
+);
+
+pub(crate) fn is_xml_whitespace(c: char) -> bool {
+    c == ' ' || c == '\t' || c == '\n' || c == '\r'
+}
+
+/// Applies XPath `normalize-space` semantics to `s`.
+pub(crate) fn normalize_space(s: &str) -> String {
+    s.split(is_xml_whitespace)
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn direct_text(node: Node) -> String {
+    match node {
+        Node::Element(_) | Node::Root(_) => node
+            .children()
+            .into_iter()
+            .filter_map(|child| match child {
+                Node::Text(t) => Some(t.text()),
+                _ => None,
+            })
+            .collect(),
+        _ => node.string_value(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_space_semantics() {
+        assert_eq!(normalize_space(""), "");
+        assert_eq!(normalize_space(" \t\r\n "), "");
+        assert_eq!(normalize_space("  a \n\t b  c "), "a b c");
+    }
+
+    #[test]
+    fn text_modes() {
+        let xml = r#"<?xml version="1.0"?>
+                     <root>
+                        <title>
+                            Hello
+                            World
+                        </title>
+                        <p>Some <em>mixed</em> content.</p>
+                        <blank>   </blank>
+                        <nbsp>&#xa0;x&#xa0;</nbsp>
+                     </root>"#;
+        let reader = Reader::from_str(xml, None).unwrap();
+
+        let trimmed: Trimmed = reader.read("//title").unwrap();
+        assert!(trimmed.0.starts_with("Hello\n"));
+        assert!(trimmed.0.ends_with("World"));
+
+        let normalized: Normalized = reader.read("//title").unwrap();
+        assert_eq!(normalized.0, "Hello World");
+
+        let direct: DirectText = reader.read("//p").unwrap();
+        assert_eq!(direct.0, "Some  content.");
+        assert_eq!(direct.normalized(), "Some content.");
+
+        let blank: Option<Trimmed> = reader.read("//blank").unwrap();
+        assert_eq!(blank, None);
+        let blank: Trimmed = reader.read("//blank").unwrap();
+        assert_eq!(blank.0, "");
+
+        let nbsp: Trimmed = reader.read("//nbsp").unwrap();
+        assert_eq!(nbsp.0, "\u{a0}x\u{a0}");
+        let nbsp: Normalized = reader.read("//nbsp").unwrap();
+        assert_eq!(nbsp.0, "\u{a0}x\u{a0}");
+
+        let missing: Option<Normalized> = reader.read("//missing").unwrap();
+        assert_eq!(missing, None);
+        assert!(reader.read::<DirectText, _>("//missing").is_err());
+    }
+}
@@ -10,9 +10,9 @@ pub(crate) enum Refable<'a, T: 'a> {
 
 impl<'a, T> Borrow<T> for Refable<'a, T> {
     fn borrow(&self) -> &T {
-        match self {
-            &Refable::Owned(ref v) => &v,
-            &Refable::Borrowed(v) => v,
+        match *self {
+            Refable::Owned(ref v) => v,
+            Refable::Borrowed(v) => v,
         }
     }
 }
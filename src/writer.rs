@@ -0,0 +1,823 @@
+// Copyright 2017-2019 Leonardo Schwarz <mail@leoschwarz.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! XML document building.
+//!
+//! `ToXml` is the counterpart of `FromXml`: values write themselves into a
+//! `Writer`, addressing their location with XPath-like paths. Missing
+//! intermediate elements and attributes are created as needed.
+//!
+//! Paths consist of steps separated by `/`. A path starting with `/` is
+//! resolved from the document root, otherwise from the writer's node.
+//! The supported steps are:
+//!
+//! - `name`, `prefix:name`: the first child element with this name.
+//! - `name[n]`: the n-th (1-based) child element with this name.
+//! - `@name`, `@prefix:name`: an attribute, only as last step.
+//! - `text()`: the text content, only as last step.
+//! - `.`: the current node.
+//!
+//! # Examples
+//! ```
+//! use xpath_reader::{Error, FromXml, FromXmlResult, Reader};
+//! use xpath_reader::writer::{ToXml, Writer};
+//!
+//! #[derive(Debug, PartialEq)]
+//! struct Book {
+//!     title: String,
+//!     tags: Vec<String>,
+//! }
+//!
+//! impl FromXml for Book {
+//!     fn from_xml<'d>(reader: &'d Reader<'d>) -> FromXmlResult<Self> {
+//!         Ok(Book {
+//!             title: reader.read("./@title")?,
+//!             tags: reader.read("./tags/tag")?,
+//!         })
+//!     }
+//! }
+//!
+//! impl ToXml for Book {
+//!     fn to_xml<'d>(&self, writer: &'d Writer<'d>) -> Result<(), Error> {
+//!         writer.write("@title", &self.title)?;
+//!         writer.write("tags/tag", &self.tags)
+//!     }
+//! }
+//!
+//! let book = Book {
+//!     title: "Neuromancer".to_string(),
+//!     tags: vec!["cyberpunk".to_string(), "sci-fi".to_string()],
+//! };
+//!
+//! let writer = Writer::new();
+//! writer.write("/book", &book).unwrap();
+//! let xml = writer.to_string();
+//! assert_eq!(
+//!     xml,
+//!     r#"<?xml version="1.0"?><book title="Neuromancer"><tags><tag>cyberpunk</tag><tag>sci-fi</tag></tags></book>"#
+//! );
+//!
+//! let reader = Reader::from_str(&xml, None).unwrap();
+//! let read: Book = reader.read("/book").unwrap();
+//! assert_eq!(read, book);
+//! ```
+
+use errors::{Error, ErrorKind};
+use node;
+use raw;
+use std::borrow::Borrow;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use sxd_document::dom::{Document, Element, ParentOfChild, Text};
+use sxd_document::{Package, QName};
+use sxd_xpath::nodeset::Node;
+use util::Refable;
+
+/// A value that can be written into a XML writer.
+pub trait ToXml {
+    /// Write `self` into the node referenced by `writer`.
+    ///
+    /// Implementors for simple values set the text of the node with
+    /// `Writer::set_text`, implementors for structured values write their
+    /// fields relative to the node with `Writer::write`.
+    fn to_xml<'d>(&self, writer: &'d Writer<'d>) -> Result<(), Error>;
+}
+
+/// Namespace bindings used to resolve the prefixes in paths.
+#[derive(Debug, Default)]
+struct Namespaces {
+    default: Option<String>,
+    prefixes: BTreeMap<String, String>,
+}
+
+/// XML document builder using XPath-like paths.
+///
+/// A `Writer` either owns a complete document, or references a node of a
+/// document owned by another writer. Nodes referenced by a path are only
+/// created when something is written into them.
+pub struct Writer<'d> {
+    namespaces: Refable<'d, Namespaces>,
+    target: Target<'d>,
+}
+
+enum Target<'d> {
+    Document(Box<Package>),
+    Node {
+        parent: ParentOfChild<'d>,
+        step: Step,
+        /// Whether a new element has to be created instead of reusing an
+        /// existing one.
+        fresh: bool,
+        /// The index of the element among the child elements of `parent`,
+        /// once it was created.
+        created: Cell<Option<usize>>,
+    },
+    /// An existing text node.
+    Text(Text<'d>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Step {
+    Current,
+    Element {
+        prefix: Option<String>,
+        local: String,
+        index: usize,
+    },
+    Attribute {
+        prefix: Option<String>,
+        local: String,
+    },
+    Text,
+}
+
+impl<'d> Default for Writer<'d> {
+    fn default() -> Self {
+        Writer::new()
+    }
+}
+
+impl<'d> Writer<'d> {
+    /// Creates a writer for a new, empty document.
+    pub fn new() -> Self {
+        Writer {
+            namespaces: Refable::Owned(Namespaces::default()),
+            target: Target::Document(Box::new(Package::new())),
+        }
+    }
+
+    /// Binds `prefix` to the namespace `uri` for the paths of this writer
+    /// and the writers created from it.
+    ///
+    /// Namespaces can only be bound on a writer created with `new`, on
+    /// other writers this has no effect.
+    pub fn set_namespace(&mut self, prefix: &str, uri: &str) {
+        if let Refable::Owned(ref mut ns) = self.namespaces {
+            ns.prefixes.insert(prefix.to_string(), uri.to_string());
+        }
+    }
+
+    /// Sets the namespace of elements without prefix in paths.
+    ///
+    /// Like `set_namespace` this only has an effect on a writer created
+    /// with `new`.
+    pub fn set_default_namespace(&mut self, uri: &str) {
+        if let Refable::Owned(ref mut ns) = self.namespaces {
+            ns.default = Some(uri.to_string());
+        }
+    }
+
+    /// Writes `value` into the node at `path`, creating it if needed.
+    pub fn write<V>(&'d self, path: &str, value: &V) -> Result<(), Error>
+    where
+        V: ToXml + ?Sized,
+    {
+        let (absolute, mut steps) = parse_path(path)?;
+        let last = steps.pop().unwrap_or(Step::Current);
+
+        let mut parent = if absolute {
+            ParentOfChild::Root(self.document().root())
+        } else {
+            self.base()?
+        };
+        for step in &steps {
+            parent = ParentOfChild::Element(self.find_or_create(parent, step, false)?);
+        }
+
+        let writer = match (last, parent) {
+            (Step::Current, ParentOfChild::Root(_)) => {
+                return Err(Error::custom_msg("Cannot write into the document root."))
+            }
+            (step, parent) => self.child(parent, step, false),
+        };
+        value.to_xml(&writer)
+    }
+
+    /// Sets the text of the node referenced by this writer, replacing all
+    /// of its children if it is an element.
+    pub fn set_text(&'d self, text: &str) -> Result<(), Error> {
+        match self.target {
+            Target::Document(_) => Err(Error::custom_msg(
+                "Cannot set the text of the document root.",
+            )),
+            Target::Node {
+                parent,
+                step:
+                    Step::Attribute {
+                        ref prefix,
+                        ref local,
+                    },
+                ..
+            } => {
+                let element = parent.element().ok_or_else(|| {
+                    Error::custom_msg("Cannot set an attribute on the document root.")
+                })?;
+                let name = self.qname(prefix.as_ref().map(|s| s.as_str()), local, false)?;
+                let attr = element.set_attribute_value(name, text);
+                if let Some(ref p) = *prefix {
+                    attr.set_preferred_prefix(Some(p));
+                }
+                Ok(())
+            }
+            Target::Node {
+                parent,
+                step: Step::Text,
+                ..
+            } => match parent.element() {
+                Some(e) => {
+                    e.set_text(text);
+                    Ok(())
+                }
+                None => Err(Error::custom_msg("Cannot add text to the document root.")),
+            },
+            Target::Node { .. } => {
+                let element = self.element()?;
+                element.set_text(text);
+                Ok(())
+            }
+            Target::Text(t) => {
+                t.set_text(text);
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns a writer for a new sibling of the element referenced by this
+    /// writer, which is used to write sequences of values.
+    ///
+    /// For other nodes (e.g. attributes) the same node is referenced.
+    pub fn next_sibling(&'d self) -> Writer<'d> {
+        match self.target {
+            Target::Node {
+                parent, ref step, ..
+            } => self.child(parent, step.clone(), true),
+            Target::Document(_) => Writer {
+                namespaces: self.namespaces.clone_ref(),
+                target: Target::Node {
+                    parent: ParentOfChild::Root(self.document().root()),
+                    step: Step::Current,
+                    fresh: false,
+                    created: Cell::new(None),
+                },
+            },
+            Target::Text(t) => Writer {
+                namespaces: self.namespaces.clone_ref(),
+                target: Target::Text(t),
+            },
+        }
+    }
+
+    /// Returns the document the writer writes into.
+    pub fn document(&'d self) -> Document<'d> {
+        match self.target {
+            Target::Document(ref package) => package.as_document(),
+            Target::Node { parent, .. } => match parent {
+                ParentOfChild::Root(r) => r.document(),
+                ParentOfChild::Element(e) => e.document(),
+            },
+            Target::Text(t) => t.document(),
+        }
+    }
+
+    /// Creates a writer referencing an existing element, attribute or text
+    /// node.
+    ///
+    /// The namespaces in scope of the node in its document are bound for
+    /// the paths of the writer.
+    pub(crate) fn for_node(node: Node<'d>) -> Result<Writer<'d>, Error> {
+        let (parent, step) = match node {
+            Node::Element(e) => (e, Step::Current),
+            Node::Attribute(a) => {
+                let parent = a
+                    .parent()
+                    .ok_or_else(|| Error::custom_msg("Attribute without element."))?;
+                let prefix = a.name().namespace_uri().map(|_| match node::prefix(node) {
+                    Some(prefix) => prefix.to_string(),
+                    None => unbound_prefix(parent),
+                });
+                let local = a.name().local_part().to_string();
+                (parent, Step::Attribute { prefix, local })
+            }
+            Node::Text(t) => (
+                t.parent()
+                    .ok_or_else(|| Error::custom_msg("Text node without element."))?,
+                Step::Text,
+            ),
+            _ => {
+                return Err(Error::custom_msg(
+                    "Only elements, attributes and text nodes can be written.",
+                ))
+            }
+        };
+
+        let mut namespaces = Namespaces {
+            default: parent.recursive_default_namespace_uri().map(String::from),
+            prefixes: parent
+                .namespaces_in_scope()
+                .into_iter()
+                .map(|ns| (ns.prefix().to_string(), ns.uri().to_string()))
+                .collect(),
+        };
+        if let Step::Attribute {
+            prefix: Some(ref prefix),
+            ..
+        } = step
+        {
+            if let Some(uri) = node::namespace_uri(node) {
+                namespaces.prefixes.insert(prefix.clone(), uri.to_string());
+            }
+        }
+
+        let target = match node {
+            Node::Text(t) => Target::Text(t),
+            _ => Target::Node {
+                parent: ParentOfChild::Element(parent),
+                step,
+                fresh: false,
+                created: Cell::new(None),
+            },
+        };
+        Ok(Writer {
+            namespaces: Refable::Owned(namespaces),
+            target,
+        })
+    }
+
+    fn child(&'d self, parent: ParentOfChild<'d>, step: Step, fresh: bool) -> Writer<'d> {
+        Writer {
+            namespaces: self.namespaces.clone_ref(),
+            target: Target::Node {
+                parent,
+                step,
+                fresh,
+                created: Cell::new(None),
+            },
+        }
+    }
+
+    /// The node relative paths are resolved against.
+    fn base(&'d self) -> Result<ParentOfChild<'d>, Error> {
+        match self.target {
+            Target::Document(ref package) => Ok(ParentOfChild::Root(package.as_document().root())),
+            Target::Node {
+                parent,
+                step: Step::Current,
+                ..
+            } => Ok(parent),
+            Target::Node { .. } => self.element().map(ParentOfChild::Element),
+            Target::Text(_) => Err(Error::custom_msg("Cannot write into a text node.")),
+        }
+    }
+
+    /// The element referenced by this writer, created on first use.
+    fn element(&'d self) -> Result<Element<'d>, Error> {
+        match self.target {
+            Target::Node {
+                parent,
+                step: Step::Current,
+                ..
+            } => parent
+                .element()
+                .ok_or_else(|| Error::custom_msg("The document root is not an element.")),
+            Target::Node {
+                parent,
+                ref step,
+                fresh,
+                ref created,
+            } => {
+                if !matches!(*step, Step::Element { .. }) {
+                    return Err(Error::custom_msg(
+                        "Cannot write elements into attributes or text nodes.",
+                    ));
+                }
+                let elements = child_elements(parent);
+                if let Some(e) = created.get().and_then(|i| elements.get(i)) {
+                    return Ok(*e);
+                }
+                let e = self.find_or_create(parent, step, fresh)?;
+                created.set(child_elements(parent).iter().position(|c| *c == e));
+                Ok(e)
+            }
+            Target::Document(_) => Err(Error::custom_msg("The document root is not an element.")),
+            Target::Text(_) => Err(Error::custom_msg("Cannot write elements into text nodes.")),
+        }
+    }
+
+    fn find_or_create(
+        &'d self,
+        parent: ParentOfChild<'d>,
+        step: &Step,
+        fresh: bool,
+    ) -> Result<Element<'d>, Error> {
+        let (prefix, local, index) = match *step {
+            Step::Element {
+                ref prefix,
+                ref local,
+                index,
+            } => (prefix.as_ref().map(|s| s.as_str()), local.as_str(), index),
+            Step::Current => {
+                return parent
+                    .element()
+                    .ok_or_else(|| Error::custom_msg("The document root is not an element."))
+            }
+            _ => {
+                return Err(Error::internal(
+                    "Attributes and text() are only allowed as last step.",
+                    ErrorKind::ParseXPath,
+                ))
+            }
+        };
+        let name = self.qname(prefix, local, true)?;
+
+        let mut existing: Vec<Element> = match parent {
+            ParentOfChild::Root(r) => r
+                .children()
+                .into_iter()
+                .filter_map(|c| c.element())
+                .collect(),
+            ParentOfChild::Element(e) => e
+                .children()
+                .into_iter()
+                .filter_map(|c| c.element())
+                .collect(),
+        };
+        existing.retain(|e| e.name() == name);
+        if !fresh {
+            if let Some(e) = existing.get(index - 1) {
+                return Ok(*e);
+            }
+        }
+
+        let doc = self.document();
+        let missing = if fresh { 1 } else { index - existing.len() };
+        let mut created = None;
+        for _ in 0..missing {
+            let e = doc.create_element(name);
+            match prefix {
+                Some(p) => e.set_preferred_prefix(Some(p)),
+                None => {
+                    let inherited = parent
+                        .element()
+                        .and_then(|p| p.recursive_default_namespace_uri());
+                    if name.namespace_uri().is_some() && name.namespace_uri() != inherited {
+                        e.set_default_namespace_uri(name.namespace_uri());
+                    }
+                }
+            }
+            match parent {
+                ParentOfChild::Root(r) => {
+                    if r.children().iter().any(|c| c.element().is_some()) {
+                        return Err(Error::custom_msg(
+                            "The document already has a root element.",
+                        ));
+                    }
+                    r.append_child(e)
+                }
+                ParentOfChild::Element(p) => p.append_child(e),
+            }
+            created = Some(e);
+        }
+        Ok(created.expect("at least one element is created"))
+    }
+
+    fn qname<'n>(
+        &'n self,
+        prefix: Option<&str>,
+        local: &'n str,
+        element: bool,
+    ) -> Result<QName<'n>, Error> {
+        let namespaces: &Namespaces = self.namespaces.borrow();
+        let uri = match prefix {
+            Some(p) => Some(namespaces.prefixes.get(p).ok_or_else(|| {
+                Error::custom_msg(format!("Namespace prefix '{}' is not bound.", p))
+            })?),
+            None if element => namespaces.default.as_ref(),
+            None => None,
+        };
+        Ok(QName::with_namespace_uri(uri.map(|s| s.as_str()), local))
+    }
+}
+
+impl<'d> fmt::Display for Writer<'d> {
+    /// Formats the whole document the writer writes into.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let doc = match self.target {
+            Target::Document(ref package) => package.as_document(),
+            Target::Node { parent, .. } => match parent {
+                ParentOfChild::Root(r) => r.document(),
+                ParentOfChild::Element(e) => e.document(),
+            },
+            Target::Text(t) => t.document(),
+        };
+        write!(
+            f,
+            "<?xml version=\"1.0\"?>{}",
+            raw::inner_xml(doc.root().into())
+        )
+    }
+}
+
+/// Returns the first of the prefixes `ns0`, `ns1`, ... which is not bound
+/// in scope of `element`.
+fn unbound_prefix(element: Element) -> String {
+    let bound: Vec<String> = element
+        .namespaces_in_scope()
+        .into_iter()
+        .map(|ns| ns.prefix().to_string())
+        .collect();
+    (0..)
+        .map(|i| format!("ns{}", i))
+        .find(|prefix| !bound.contains(prefix))
+        .unwrap()
+}
+
+fn child_elements(parent: ParentOfChild) -> Vec<Element> {
+    match parent {
+        ParentOfChild::Root(r) => r
+            .children()
+            .into_iter()
+            .filter_map(|c| c.element())
+            .collect(),
+        ParentOfChild::Element(e) => e
+            .children()
+            .into_iter()
+            .filter_map(|c| c.element())
+            .collect(),
+    }
+}
+
+fn parse_path(path: &str) -> Result<(bool, Vec<Step>), Error> {
+    let invalid = |msg: &str| {
+        Error::internal(
+            format!("Invalid writer path '{}': {}", path, msg),
+            ErrorKind::ParseXPath,
+        )
+    };
+
+    let absolute = path.starts_with('/');
+    let rest = if absolute { &path[1..] } else { path };
+    let mut steps = Vec::new();
+    if rest.is_empty() {
+        return Ok((absolute, steps));
+    }
+
+    let parts: Vec<&str> = rest.split('/').collect();
+    for (i, part) in parts.iter().enumerate() {
+        let last = i == parts.len() - 1;
+        let step = if *part == "." {
+            Step::Current
+        } else if *part == "text()" {
+            Step::Text
+        } else if let Some(name) = part.strip_prefix('@') {
+            let (prefix, local) = split_name(name).ok_or_else(|| invalid("bad attribute name"))?;
+            Step::Attribute { prefix, local }
+        } else {
+            let (name, index) = match part.find('[') {
+                Some(pos) if part.ends_with(']') => {
+                    let index = part[pos + 1..part.len() - 1]
+                        .trim()
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|i| *i > 0)
+                        .ok_or_else(|| invalid("bad position predicate"))?;
+                    (&part[..pos], index)
+                }
+                Some(_) => return Err(invalid("unterminated predicate")),
+                None => (*part, 1),
+            };
+            let (prefix, local) = split_name(name).ok_or_else(|| invalid("bad element name"))?;
+            Step::Element {
+                prefix,
+                local,
+                index,
+            }
+        };
+
+        match step {
+            Step::Attribute { .. } | Step::Text if !last => {
+                return Err(invalid(
+                    "attributes and text() are only allowed as last step",
+                ))
+            }
+            _ => steps.push(step),
+        }
+    }
+    Ok((absolute, steps))
+}
+
+fn split_name(name: &str) -> Option<(Option<String>, String)> {
+    fn valid(s: &str) -> bool {
+        !s.is_empty()
+            && !s.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '.')
+            && s.chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.')
+    }
+
+    let mut parts = name.splitn(2, ':');
+    let first = parts.next()?;
+    match parts.next() {
+        Some(local) if valid(first) && valid(local) => {
+            Some((Some(first.to_string()), local.to_string()))
+        }
+        None if valid(first) => Some((None, first.to_string())),
+        _ => None,
+    }
+}
+
+impl<T> ToXml for &T
+where
+    T: ToXml + ?Sized,
+{
+    fn to_xml<'d>(&self, writer: &'d Writer<'d>) -> Result<(), Error> {
+        (**self).to_xml(writer)
+    }
+}
+
+impl ToXml for str {
+    fn to_xml<'d>(&self, writer: &'d Writer<'d>) -> Result<(), Error> {
+        writer.set_text(self)
+    }
+}
+
+impl<T> ToXml for Option<T>
+where
+    T: ToXml,
+{
+    /// Nothing is written for `None`.
+    fn to_xml<'d>(&self, writer: &'d Writer<'d>) -> Result<(), Error> {
+        match *self {
+            Some(ref value) => value.to_xml(writer),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T> ToXml for [T]
+where
+    T: ToXml,
+{
+    /// Each value is written into a new element, so for an element path
+    /// the values become siblings.
+    fn to_xml<'d>(&self, writer: &'d Writer<'d>) -> Result<(), Error> {
+        for value in self {
+            value.to_xml(&writer.next_sibling())?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> ToXml for Vec<T>
+where
+    T: ToXml,
+{
+    fn to_xml<'d>(&self, writer: &'d Writer<'d>) -> Result<(), Error> {
+        self.as_slice().to_xml(writer)
+    }
+}
+
+macro_rules! to_display_str {
+    ( $( $type:ty ),* ) => {
+        $(
+            impl ToXml for $type {
+                fn to_xml<'d>(&self, writer: &'d Writer<'d>) -> Result<(), Error> {
+                    writer.set_text(&self.to_string())
+                }
+            }
+        )*
+    }
+}
+
+to_display_str!(String, f32, f64, u8, u16, u32, u64, i8, i16, i32, i64, bool, char);
+to_display_str!(IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr);
+
+#[cfg(feature = "rust_decimal")]
+to_display_str!(::rust_decimal::Decimal);
+#[cfg(feature = "semver")]
+to_display_str!(::semver::Version);
+#[cfg(feature = "url")]
+to_display_str!(::url::Url);
+#[cfg(feature = "uuid")]
+to_display_str!(::uuid::Uuid);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reader::Reader;
+    use sxd_xpath::Context;
+
+    #[test]
+    fn path_parsing() {
+        let (absolute, steps) = parse_path("/a/b:c[2]/@d").unwrap();
+        assert!(absolute);
+        assert_eq!(
+            steps,
+            vec![
+                Step::Element {
+                    prefix: None,
+                    local: "a".to_string(),
+                    index: 1,
+                },
+                Step::Element {
+                    prefix: Some("b".to_string()),
+                    local: "c".to_string(),
+                    index: 2,
+                },
+                Step::Attribute {
+                    prefix: None,
+                    local: "d".to_string(),
+                },
+            ]
+        );
+        assert_eq!(parse_path(".").unwrap(), (false, vec![Step::Current]));
+        assert_eq!(parse_path("text()").unwrap(), (false, vec![Step::Text]));
+
+        assert!(parse_path("@a/b").is_err());
+        assert!(parse_path("a[0]").is_err());
+        assert!(parse_path("a[x]").is_err());
+        assert!(parse_path("a//b").is_err());
+        assert!(parse_path("a:b:c").is_err());
+    }
+
+    #[test]
+    fn write_paths() {
+        let mut writer = Writer::new();
+        writer.set_namespace("x", "urn:x");
+        writer.write("/root/a/b", "1").unwrap();
+        writer.write("/root/a/b[3]", &2u8).unwrap();
+        writer.write("/root/a/@id", &"a1".to_string()).unwrap();
+        writer.write("/root/x:c/@x:lang", "en").unwrap();
+        writer.write("/root/d", &Some(true)).unwrap();
+        writer.write("/root/e", &None::<String>).unwrap();
+        writer.write("/root/f/text()", "a < b").unwrap();
+
+        assert_eq!(
+            writer.to_string(),
+            r#"<?xml version="1.0"?><root><a id="a1"><b>1</b><b/><b>2</b></a><x:c xmlns:x="urn:x" x:lang="en"/><d>true</d><f>a &lt; b</f></root>"#
+        );
+
+        assert!(writer.write("/other", "x").is_err());
+        assert!(writer.write("/root/y:a", "x").is_err());
+        assert!(writer.write("/", "x").is_err());
+    }
+
+    #[test]
+    fn generated_prefixes() {
+        let package = Package::new();
+        let document = package.as_document();
+        let root = document.create_element("root");
+        root.register_prefix("ns0", "urn:other");
+        document.root().append_child(root);
+        let attr = root.set_attribute_value(QName::with_namespace_uri(Some("urn:x"), "a"), "1");
+
+        let writer = Writer::for_node(Node::Attribute(attr)).unwrap();
+        writer.set_text("2").unwrap();
+        assert_eq!(
+            writer.to_string(),
+            r#"<?xml version="1.0"?><root xmlns:ns0="urn:other" xmlns:ns1="urn:x" ns1:a="2"/>"#
+        );
+    }
+
+    #[test]
+    fn write_nested() {
+        struct Tag(&'static str, u32);
+
+        impl ToXml for Tag {
+            fn to_xml<'d>(&self, writer: &'d Writer<'d>) -> Result<(), Error> {
+                writer.write("@name", self.0)?;
+                writer.write(".", &self.1)
+            }
+        }
+
+        let mut writer = Writer::new();
+        writer.set_default_namespace("urn:tags");
+        let tags = vec![Tag("a", 1), Tag("b", 2)];
+        writer.write("/tags/tag", &tags).unwrap();
+
+        let xml = writer.to_string();
+        assert_eq!(
+            xml,
+            r#"<?xml version="1.0"?><tags xmlns="urn:tags"><tag name="a">1</tag><tag name="b">2</tag></tags>"#
+        );
+
+        let mut context = Context::new();
+        context.set_namespace("t", "urn:tags");
+        let reader = Reader::from_str(&xml, Some(&context)).unwrap();
+        let names: Vec<String> = reader.read("//t:tag/@name").unwrap();
+        let values: Vec<u32> = reader.read("//t:tag").unwrap();
+        assert_eq!(names, vec!["a", "b"]);
+        assert_eq!(values, vec![1, 2]);
+    }
+}
@@ -0,0 +1,1383 @@
+// Copyright 2017-2019 Leonardo Schwarz <mail@leoschwarz.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Validation against a subset of XML Schema.
+//!
+//! Supported are global and local element declarations and references,
+//! named and anonymous complex types with `sequence`, `choice`, `any`,
+//! attributes, `simpleContent` and `complexContent` extensions, and simple
+//! types restricting the built-in types with the `pattern`, `enumeration`,
+//! `minInclusive`, `maxInclusive`, `minExclusive`, `maxExclusive`,
+//! `length`, `minLength`, `maxLength`, `totalDigits`, `fractionDigits` and
+//! `whiteSpace` facets.
+//!
+//! Patterns support the full regular expression syntax of XML Schema except
+//! character class subtraction and block escapes like `\p{IsBasicLatin}`,
+//! which are rejected when loading the schema. The `\i` and `\c` escapes
+//! follow XML Schema 1.1 and match the name characters of XML 1.0 (Fifth
+//! Edition).
+//!
+//! Loading a schema with other constructs, like imports, `all` groups,
+//! list and union types, unknown built-in types or references to types and
+//! elements it does not declare, fails instead of silently accepting
+//! documents.
+//!
+//! # Examples
+//! ```
+//! use xpath_reader::Reader;
+//! use xpath_reader::xsd::Schema;
+//!
+//! let xsd = r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+//!   <xs:element name="order">
+//!     <xs:complexType>
+//!       <xs:sequence>
+//!         <xs:element name="item" type="xs:string" maxOccurs="unbounded"/>
+//!       </xs:sequence>
+//!       <xs:attribute name="id" type="xs:positiveInteger" use="required"/>
+//!     </xs:complexType>
+//!   </xs:element>
+//! </xs:schema>"#;
+//! let schema = Schema::parse(xsd).unwrap();
+//!
+//! let reader = Reader::from_str(r#"<order id="0"><item>A</item></order>"#, None).unwrap();
+//! let errors = schema.validate(&reader).unwrap();
+//! assert_eq!(errors.len(), 1);
+//! assert_eq!(errors[0].path, "/order[1]/@id");
+//! ```
+
+use errors::{Error, ErrorKind};
+use node;
+use pattern::Pattern;
+use reader::Reader;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::sync::OnceLock;
+use sxd_document::dom::Element;
+use sxd_document::parser::parse as sxd_parse;
+use sxd_xpath::nodeset::Node;
+use text::normalize_space;
+
+/// The namespace of XML Schema definitions.
+pub const XSD_NAMESPACE: &str = "http://www.w3.org/2001/XMLSchema";
+
+/// The namespace of the `xsi:*` attributes, which are never validated.
+pub const XSI_NAMESPACE: &str = "http://www.w3.org/2001/XMLSchema-instance";
+
+/// A loaded schema.
+#[derive(Clone, Debug)]
+pub struct Schema {
+    elements: BTreeMap<String, ElementDecl>,
+    complex_types: BTreeMap<String, ComplexType>,
+    simple_types: BTreeMap<String, SimpleType>,
+}
+
+/// A violation of the schema.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ValidationError {
+    /// The location path of the invalid element or attribute.
+    pub path: String,
+    /// A description of the violation.
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct ElementDecl {
+    name: String,
+    namespace: Option<String>,
+    ty: TypeRef,
+}
+
+#[derive(Clone, Debug)]
+enum TypeRef {
+    Builtin(String),
+    Named(String),
+    Complex(Box<ComplexType>),
+    Simple(Box<SimpleType>),
+}
+
+#[derive(Clone, Debug, Default)]
+struct ComplexType {
+    /// A named complex type extended by this type.
+    base: Option<String>,
+    mixed: bool,
+    content: Content,
+    attributes: Vec<AttributeDecl>,
+    any_attribute: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+enum Content {
+    #[default]
+    Empty,
+    Particle(Particle),
+    Simple(TypeRef),
+}
+
+#[derive(Clone, Debug)]
+struct Particle {
+    term: Term,
+    min: usize,
+    max: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+enum Term {
+    Element(ElementDecl),
+    Ref(String),
+    Sequence(Vec<Particle>),
+    Choice(Vec<Particle>),
+    Any,
+}
+
+#[derive(Clone, Debug)]
+struct AttributeDecl {
+    name: String,
+    namespace: Option<String>,
+    ty: TypeRef,
+    required: bool,
+    fixed: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+struct SimpleType {
+    base: TypeRef,
+    patterns: Vec<Pattern>,
+    enumeration: Vec<String>,
+    min_inclusive: Option<String>,
+    max_inclusive: Option<String>,
+    min_exclusive: Option<String>,
+    max_exclusive: Option<String>,
+    length: Option<usize>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    total_digits: Option<usize>,
+    fraction_digits: Option<usize>,
+    white_space: Option<WhiteSpace>,
+}
+
+/// The whitespace normalization applied to a value before it is checked.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum WhiteSpace {
+    Preserve,
+    /// Tabs, carriage returns and line feeds are replaced by spaces.
+    Replace,
+    /// As `Replace`, with runs of spaces collapsed and the value trimmed.
+    Collapse,
+}
+
+fn schema_error<S: Into<String>>(msg: S) -> Error {
+    Error::custom_msg(msg)
+}
+
+/// The child elements of a schema element, without annotations.
+fn xs_children<'d>(e: Element<'d>) -> Result<Vec<Element<'d>>, Error> {
+    let mut children = Vec::new();
+    for child in e.children() {
+        if let Some(c) = child.element() {
+            if c.name().namespace_uri() != Some(XSD_NAMESPACE) {
+                return Err(schema_error(format!(
+                    "Unexpected element '{}' in schema.",
+                    c.name().local_part()
+                )));
+            }
+            if c.name().local_part() != "annotation" {
+                children.push(c);
+            }
+        }
+    }
+    Ok(children)
+}
+
+fn unsupported(e: Element) -> Error {
+    schema_error(format!(
+        "Unsupported schema construct 'xs:{}'.",
+        e.name().local_part()
+    ))
+}
+
+fn required_attribute<'d>(e: Element<'d>, name: &str) -> Result<&'d str, Error> {
+    e.attribute_value(name).ok_or_else(|| {
+        schema_error(format!(
+            "Missing attribute '{}' on 'xs:{}'.",
+            name,
+            e.name().local_part()
+        ))
+    })
+}
+
+fn occurs(e: Element) -> Result<(usize, Option<usize>), Error> {
+    let parse = |value: &str| {
+        value
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| schema_error(format!("Invalid occurrence '{}'.", value)))
+    };
+    let min = match e.attribute_value("minOccurs") {
+        Some(v) => parse(v)?,
+        None => 1,
+    };
+    let max = match e.attribute_value("maxOccurs") {
+        Some("unbounded") => None,
+        Some(v) => Some(parse(v)?),
+        None => Some(1),
+    };
+    Ok((min, max))
+}
+
+/// Resolves the `QName` valued attribute `value` of `e` to a built-in type
+/// or a type of the schema.
+///
+/// Fails for unknown built-in types.
+fn type_name(e: Element, value: &str) -> Result<TypeRef, Error> {
+    let (prefix, local) = match value.find(':') {
+        Some(pos) => (Some(&value[..pos]), &value[pos + 1..]),
+        None => (None, value),
+    };
+    let namespace = match prefix {
+        Some(p) => e.namespace_uri_for_prefix(p),
+        None => e.recursive_default_namespace_uri(),
+    };
+    if namespace != Some(XSD_NAMESPACE) {
+        Ok(TypeRef::Named(local.to_string()))
+    } else if local == "anyType" || BUILTINS.iter().any(|&(name, _)| name == local) {
+        Ok(TypeRef::Builtin(local.to_string()))
+    } else {
+        Err(schema_error(format!(
+            "Unknown built-in type 'xs:{}'.",
+            local
+        )))
+    }
+}
+
+struct Loader {
+    target_namespace: Option<String>,
+    qualified_elements: bool,
+    qualified_attributes: bool,
+}
+
+impl Loader {
+    fn element(&self, e: Element, global: bool) -> Result<ElementDecl, Error> {
+        let name = required_attribute(e, "name")?.to_string();
+        let qualified = match e.attribute_value("form") {
+            Some(form) => form == "qualified",
+            None => global || self.qualified_elements,
+        };
+        let mut ty = match e.attribute_value("type") {
+            Some(t) => type_name(e, t)?,
+            None => TypeRef::Builtin("anyType".to_string()),
+        };
+        for child in xs_children(e)? {
+            match child.name().local_part() {
+                "complexType" => ty = TypeRef::Complex(Box::new(self.complex_type(child)?)),
+                "simpleType" => ty = TypeRef::Simple(Box::new(self.simple_type(child)?)),
+                "unique" | "key" | "keyref" => {}
+                _ => return Err(unsupported(child)),
+            }
+        }
+        Ok(ElementDecl {
+            name,
+            namespace: if qualified {
+                self.target_namespace.clone()
+            } else {
+                None
+            },
+            ty,
+        })
+    }
+
+    fn complex_type(&self, e: Element) -> Result<ComplexType, Error> {
+        let mut ct = ComplexType {
+            mixed: e.attribute_value("mixed") == Some("true"),
+            ..ComplexType::default()
+        };
+        self.complex_content(e, &mut ct)?;
+        Ok(ct)
+    }
+
+    /// Reads the particle and attributes of a complex type or one of its
+    /// derivations into `ct`.
+    fn complex_content(&self, e: Element, ct: &mut ComplexType) -> Result<(), Error> {
+        for child in xs_children(e)? {
+            match child.name().local_part() {
+                "sequence" | "choice" | "any" => {
+                    ct.content = Content::Particle(self.particle(child)?);
+                }
+                "attribute" => ct.attributes.push(self.attribute(child)?),
+                "anyAttribute" => ct.any_attribute = true,
+                "simpleContent" => {
+                    for derivation in xs_children(child)? {
+                        match derivation.name().local_part() {
+                            "extension" | "restriction" => {
+                                let base = required_attribute(derivation, "base")?;
+                                ct.content = Content::Simple(type_name(derivation, base)?);
+                                self.complex_content(derivation, ct)?;
+                            }
+                            _ => return Err(unsupported(derivation)),
+                        }
+                    }
+                }
+                "complexContent" => {
+                    if child.attribute_value("mixed") == Some("true") {
+                        ct.mixed = true;
+                    }
+                    for derivation in xs_children(child)? {
+                        let base = required_attribute(derivation, "base")?;
+                        match derivation.name().local_part() {
+                            "extension" => {
+                                if let TypeRef::Named(name) = type_name(derivation, base)? {
+                                    ct.base = Some(name);
+                                }
+                            }
+                            "restriction" => {}
+                            _ => return Err(unsupported(derivation)),
+                        }
+                        self.complex_content(derivation, ct)?;
+                    }
+                }
+                _ => return Err(unsupported(child)),
+            }
+        }
+        Ok(())
+    }
+
+    fn particle(&self, e: Element) -> Result<Particle, Error> {
+        let (min, max) = occurs(e)?;
+        let term = match e.name().local_part() {
+            "element" => match e.attribute_value("ref") {
+                Some(r) => match type_name(e, r)? {
+                    TypeRef::Named(name) => Term::Ref(name),
+                    _ => return Err(unsupported(e)),
+                },
+                None => Term::Element(self.element(e, false)?),
+            },
+            "sequence" | "choice" => {
+                let particles = xs_children(e)?
+                    .into_iter()
+                    .map(|child| self.particle(child))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                if e.name().local_part() == "sequence" {
+                    Term::Sequence(particles)
+                } else {
+                    Term::Choice(particles)
+                }
+            }
+            "any" => Term::Any,
+            _ => return Err(unsupported(e)),
+        };
+        Ok(Particle { term, min, max })
+    }
+
+    fn attribute(&self, e: Element) -> Result<AttributeDecl, Error> {
+        let (name, namespace) = match e.attribute_value("ref") {
+            Some("xml:lang") | Some("xml:space") | Some("xml:base") | Some("xml:id") => (
+                e.attribute_value("ref").unwrap()[4..].to_string(),
+                Some(node::XML_NAMESPACE.to_string()),
+            ),
+            Some(_) => return Err(unsupported(e)),
+            None => {
+                let qualified = match e.attribute_value("form") {
+                    Some(form) => form == "qualified",
+                    None => self.qualified_attributes,
+                };
+                (
+                    required_attribute(e, "name")?.to_string(),
+                    if qualified {
+                        self.target_namespace.clone()
+                    } else {
+                        None
+                    },
+                )
+            }
+        };
+        let mut ty = match e.attribute_value("type") {
+            Some(t) => type_name(e, t)?,
+            None => TypeRef::Builtin("anySimpleType".to_string()),
+        };
+        for child in xs_children(e)? {
+            match child.name().local_part() {
+                "simpleType" => ty = TypeRef::Simple(Box::new(self.simple_type(child)?)),
+                _ => return Err(unsupported(child)),
+            }
+        }
+        Ok(AttributeDecl {
+            name,
+            namespace,
+            ty,
+            required: e.attribute_value("use") == Some("required"),
+            fixed: e.attribute_value("fixed").map(String::from),
+        })
+    }
+
+    fn simple_type(&self, e: Element) -> Result<SimpleType, Error> {
+        let restriction = xs_children(e)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| schema_error("Empty simple type."))?;
+        if restriction.name().local_part() != "restriction" {
+            return Err(unsupported(restriction));
+        }
+        let mut base = match restriction.attribute_value("base") {
+            Some(b) => Some(type_name(restriction, b)?),
+            None => None,
+        };
+        let mut st = SimpleType {
+            base: TypeRef::Builtin("anySimpleType".to_string()),
+            patterns: Vec::new(),
+            enumeration: Vec::new(),
+            min_inclusive: None,
+            max_inclusive: None,
+            min_exclusive: None,
+            max_exclusive: None,
+            length: None,
+            min_length: None,
+            max_length: None,
+            total_digits: None,
+            fraction_digits: None,
+            white_space: None,
+        };
+        for facet in xs_children(restriction)? {
+            let name = facet.name().local_part();
+            if name == "simpleType" {
+                base = Some(TypeRef::Simple(Box::new(self.simple_type(facet)?)));
+                continue;
+            }
+            let value = required_attribute(facet, "value")?;
+            let length = || {
+                value
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| schema_error(format!("Invalid length '{}'.", value)))
+            };
+            match name {
+                "pattern" => st.patterns.push(Pattern::new(value)?),
+                "enumeration" => st.enumeration.push(value.to_string()),
+                "minInclusive" => st.min_inclusive = Some(value.to_string()),
+                "maxInclusive" => st.max_inclusive = Some(value.to_string()),
+                "minExclusive" => st.min_exclusive = Some(value.to_string()),
+                "maxExclusive" => st.max_exclusive = Some(value.to_string()),
+                "length" => st.length = Some(length()?),
+                "minLength" => st.min_length = Some(length()?),
+                "maxLength" => st.max_length = Some(length()?),
+                "totalDigits" => st.total_digits = Some(length()?),
+                "fractionDigits" => st.fraction_digits = Some(length()?),
+                "whiteSpace" => {
+                    st.white_space = Some(match value {
+                        "preserve" => WhiteSpace::Preserve,
+                        "replace" => WhiteSpace::Replace,
+                        "collapse" => WhiteSpace::Collapse,
+                        _ => return Err(schema_error(format!("Invalid whiteSpace '{}'.", value))),
+                    })
+                }
+                _ => return Err(unsupported(facet)),
+            }
+        }
+        st.base = base.ok_or_else(|| schema_error("Restriction without base type."))?;
+        Ok(st)
+    }
+}
+
+impl Schema {
+    /// Loads a schema from its XML source.
+    ///
+    /// Fails if the source is not well-formed or uses unsupported schema
+    /// constructs.
+    pub fn parse(xsd: &str) -> Result<Self, Error> {
+        let package =
+            sxd_parse(xsd).map_err(|e| Error::internal(format!("{}", e), ErrorKind::ParseXml))?;
+        let document = package.as_document();
+        let root = document
+            .root()
+            .children()
+            .into_iter()
+            .filter_map(|c| c.element())
+            .next()
+            .ok_or_else(|| schema_error("Empty schema document."))?;
+        if root.name().namespace_uri() != Some(XSD_NAMESPACE)
+            || root.name().local_part() != "schema"
+        {
+            return Err(schema_error("Root element is not 'xs:schema'."));
+        }
+
+        let loader = Loader {
+            target_namespace: root.attribute_value("targetNamespace").map(String::from),
+            qualified_elements: root.attribute_value("elementFormDefault") == Some("qualified"),
+            qualified_attributes: root.attribute_value("attributeFormDefault") == Some("qualified"),
+        };
+        let mut schema = Schema {
+            elements: BTreeMap::new(),
+            complex_types: BTreeMap::new(),
+            simple_types: BTreeMap::new(),
+        };
+        for child in xs_children(root)? {
+            match child.name().local_part() {
+                "element" => {
+                    let decl = loader.element(child, true)?;
+                    schema.elements.insert(decl.name.clone(), decl);
+                }
+                "complexType" => {
+                    let name = required_attribute(child, "name")?.to_string();
+                    schema
+                        .complex_types
+                        .insert(name, loader.complex_type(child)?);
+                }
+                "simpleType" => {
+                    let name = required_attribute(child, "name")?.to_string();
+                    schema.simple_types.insert(name, loader.simple_type(child)?);
+                }
+                _ => return Err(unsupported(child)),
+            }
+        }
+        schema.check_references()?;
+        Ok(schema)
+    }
+
+    /// Checks that all named types and element references are declared,
+    /// and that simple types are not derived from themselves.
+    fn check_references(&self) -> Result<(), Error> {
+        for decl in self.elements.values() {
+            self.check_element(decl)?;
+        }
+        for ct in self.complex_types.values() {
+            self.check_complex(ct)?;
+        }
+        for (name, st) in &self.simple_types {
+            self.check_type(&st.base)?;
+            let mut base = &st.base;
+            let mut steps = 0;
+            loop {
+                base = match *base {
+                    TypeRef::Named(ref name) => {
+                        steps += 1;
+                        &self.simple_types[name].base
+                    }
+                    TypeRef::Simple(ref st) => &st.base,
+                    _ => break,
+                };
+                if steps > self.simple_types.len() {
+                    return Err(schema_error(format!(
+                        "Simple type '{}' is derived from itself.",
+                        name
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_element(&self, decl: &ElementDecl) -> Result<(), Error> {
+        match decl.ty {
+            TypeRef::Named(ref name) if self.complex_types.contains_key(name) => Ok(()),
+            TypeRef::Named(ref name) if !self.simple_types.contains_key(name) => {
+                Err(schema_error(format!("Unknown type '{}'.", name)))
+            }
+            ref ty => self.check_type(ty),
+        }
+    }
+
+    fn check_complex(&self, ct: &ComplexType) -> Result<(), Error> {
+        if let Some(ref base) = ct.base {
+            if !self.complex_types.contains_key(base) {
+                return Err(schema_error(format!("Unknown complex type '{}'.", base)));
+            }
+        }
+        match ct.content {
+            Content::Empty => {}
+            Content::Particle(ref p) => self.check_particle(p)?,
+            Content::Simple(ref ty) => self.check_type(ty)?,
+        }
+        ct.attributes
+            .iter()
+            .try_for_each(|a| self.check_type(&a.ty))
+    }
+
+    fn check_particle(&self, particle: &Particle) -> Result<(), Error> {
+        match particle.term {
+            Term::Element(ref decl) => self.check_element(decl),
+            Term::Ref(ref name) if !self.elements.contains_key(name) => {
+                Err(schema_error(format!("Unknown element '{}'.", name)))
+            }
+            Term::Sequence(ref ps) | Term::Choice(ref ps) => {
+                ps.iter().try_for_each(|p| self.check_particle(p))
+            }
+            Term::Ref(_) | Term::Any => Ok(()),
+        }
+    }
+
+    /// Checks a type used for simple values.
+    fn check_type(&self, ty: &TypeRef) -> Result<(), Error> {
+        match *ty {
+            TypeRef::Builtin(_) => Ok(()),
+            TypeRef::Named(ref name) if !self.simple_types.contains_key(name) => {
+                Err(schema_error(format!("Unknown simple type '{}'.", name)))
+            }
+            TypeRef::Named(_) => Ok(()),
+            TypeRef::Simple(ref st) => self.check_type(&st.base),
+            TypeRef::Complex(ref ct) => self.check_complex(ct),
+        }
+    }
+
+    /// Validates the anchor node of `reader`, which has to be the document
+    /// root or an element declared globally in the schema.
+    ///
+    /// Returns all violations found, an empty list for valid documents.
+    pub fn validate<'d>(&self, reader: &'d Reader<'d>) -> Result<Vec<ValidationError>, Error> {
+        let anchor = reader
+            .anchor_node()
+            .ok_or_else(|| Error::custom_msg("No node to validate."))?;
+        let element = match anchor {
+            Node::Root(r) => r.children().into_iter().filter_map(|c| c.element()).next(),
+            Node::Element(e) => Some(e),
+            _ => None,
+        }
+        .ok_or_else(|| Error::custom_msg("Only documents and elements can be validated."))?;
+
+        let mut validator = Validator {
+            schema: self,
+            errors: Vec::new(),
+        };
+        let name = element.name();
+        match self.elements.get(name.local_part()) {
+            Some(decl) if decl.namespace.as_deref() == name.namespace_uri() => {
+                validator.element(element, decl)
+            }
+            _ => validator.error(
+                Node::Element(element),
+                "Element is not declared in the schema.",
+            ),
+        }
+        Ok(validator.errors)
+    }
+}
+
+struct Validator<'s> {
+    schema: &'s Schema,
+    errors: Vec<ValidationError>,
+}
+
+/// The expanded name of a child element.
+type Name<'d> = (Option<&'d str>, &'d str);
+
+impl<'s> Validator<'s> {
+    fn error<S: Into<String>>(&mut self, node: Node, message: S) {
+        self.errors.push(ValidationError {
+            path: node::node_path(node),
+            message: message.into(),
+        });
+    }
+
+    fn element(&mut self, e: Element, decl: &'s ElementDecl) {
+        match decl.ty {
+            TypeRef::Builtin(ref name) if name == "anyType" => {}
+            TypeRef::Complex(ref ct) => self.complex(e, ct),
+            TypeRef::Named(ref name) if self.schema.complex_types.contains_key(name) => {
+                self.complex(e, &self.schema.complex_types[name])
+            }
+            ref ty => {
+                self.attributes(e, &[], false);
+                if self.no_child_elements(e) {
+                    self.simple_value(Node::Element(e), &text(e), ty);
+                }
+            }
+        }
+    }
+
+    fn no_child_elements(&mut self, e: Element) -> bool {
+        match e.children().into_iter().filter_map(|c| c.element()).next() {
+            Some(child) => {
+                self.error(Node::Element(child), "Element content is not allowed.");
+                false
+            }
+            None => true,
+        }
+    }
+
+    /// Collects the content of `ct` and its base types, base type first.
+    fn derivation_chain(&self, ct: &'s ComplexType) -> Vec<&'s ComplexType> {
+        let mut chain = vec![ct];
+        let mut current = ct;
+        while let Some(base) = current
+            .base
+            .as_ref()
+            .and_then(|b| self.schema.complex_types.get(b))
+        {
+            if chain.iter().any(|c| ::std::ptr::eq(*c, base)) {
+                break;
+            }
+            chain.push(base);
+            current = base;
+        }
+        chain.reverse();
+        chain
+    }
+
+    fn complex(&mut self, e: Element, ct: &'s ComplexType) {
+        let chain = self.derivation_chain(ct);
+        let attributes: Vec<&'s AttributeDecl> =
+            chain.iter().flat_map(|c| c.attributes.iter()).collect();
+        let any_attribute = chain.iter().any(|c| c.any_attribute);
+        self.attributes(e, &attributes, any_attribute);
+
+        let mixed = chain.iter().any(|c| c.mixed);
+        let mut particles = Vec::new();
+        for c in &chain {
+            match c.content {
+                Content::Empty => {}
+                Content::Particle(ref p) => particles.push(p),
+                Content::Simple(ref ty) => {
+                    if self.no_child_elements(e) {
+                        self.simple_value(Node::Element(e), &text(e), ty);
+                    }
+                    return;
+                }
+            }
+        }
+
+        if !mixed {
+            let has_text = e
+                .children()
+                .into_iter()
+                .filter_map(|c| c.text())
+                .any(|t| !t.text().trim().is_empty());
+            if has_text {
+                self.error(Node::Element(e), "Text content is not allowed.");
+            }
+        }
+
+        let children: Vec<Element> = e
+            .children()
+            .into_iter()
+            .filter_map(|c| c.element())
+            .collect();
+        let names: Vec<Name> = children
+            .iter()
+            .map(|c| (c.name().namespace_uri(), c.name().local_part()))
+            .collect();
+
+        let mut furthest = 0;
+        let mut ends = BTreeSet::new();
+        ends.insert(0);
+        for p in &particles {
+            ends = self.match_particle(p, &names, &ends, &mut furthest);
+        }
+        if !ends.contains(&names.len()) {
+            match children.get(furthest) {
+                Some(child) => self.error(Node::Element(*child), "Element is not expected here."),
+                None => self.error(Node::Element(e), "Required child elements are missing."),
+            }
+            return;
+        }
+
+        for child in children {
+            let name = (child.name().namespace_uri(), child.name().local_part());
+            if let Some(decl) = particles.iter().filter_map(|p| self.find(p, name)).next() {
+                self.element(child, decl);
+            }
+        }
+    }
+
+    fn attributes(&mut self, e: Element, decls: &[&'s AttributeDecl], any_attribute: bool) {
+        for decl in decls {
+            let ns = decl.namespace.as_deref();
+            let attribute = e
+                .attributes()
+                .into_iter()
+                .find(|a| a.name().namespace_uri() == ns && a.name().local_part() == decl.name);
+            match attribute {
+                Some(a) => {
+                    if let Some(ref fixed) = decl.fixed {
+                        if a.value() != fixed {
+                            self.error(Node::Attribute(a), format!("Value must be '{}'.", fixed));
+                            continue;
+                        }
+                    }
+                    self.simple_value(Node::Attribute(a), a.value(), &decl.ty);
+                }
+                None if decl.required => self.error(
+                    Node::Element(e),
+                    format!("Missing required attribute '{}'.", decl.name),
+                ),
+                None => {}
+            }
+        }
+        if any_attribute {
+            return;
+        }
+        for a in e.attributes() {
+            let name = a.name();
+            if name.namespace_uri() == Some(XSI_NAMESPACE) {
+                continue;
+            }
+            let declared = decls.iter().any(|d| {
+                d.name == name.local_part() && d.namespace.as_deref() == name.namespace_uri()
+            });
+            if !declared {
+                self.error(Node::Attribute(a), "Attribute is not declared.");
+            }
+        }
+    }
+
+    /// Returns the positions after matching `particle` starting at any of
+    /// `starts`, recording the furthest position reached.
+    fn match_particle(
+        &self,
+        particle: &'s Particle,
+        names: &[Name],
+        starts: &BTreeSet<usize>,
+        furthest: &mut usize,
+    ) -> BTreeSet<usize> {
+        let mut result = BTreeSet::new();
+        if particle.min == 0 {
+            result.extend(starts.iter().cloned());
+        }
+        let mut seen = starts.clone();
+        let mut current = starts.clone();
+        let mut count = 0;
+        while particle.max.is_none_or(|max| count < max) && !current.is_empty() {
+            count += 1;
+            current = self.match_term(&particle.term, names, &current, furthest);
+            if count >= particle.min {
+                result.extend(current.iter().cloned());
+                // Further repetitions cannot reach new positions.
+                if current.is_subset(&seen) {
+                    break;
+                }
+            }
+            seen.extend(current.iter().cloned());
+        }
+        result
+    }
+
+    fn match_term(
+        &self,
+        term: &'s Term,
+        names: &[Name],
+        starts: &BTreeSet<usize>,
+        furthest: &mut usize,
+    ) -> BTreeSet<usize> {
+        let ends: BTreeSet<usize> = match *term {
+            Term::Element(_) | Term::Ref(_) | Term::Any => starts
+                .iter()
+                .filter(|&&i| i < names.len() && self.term_matches(term, names[i]))
+                .map(|i| i + 1)
+                .collect(),
+            Term::Sequence(ref particles) => {
+                let mut current = starts.clone();
+                for p in particles {
+                    current = self.match_particle(p, names, &current, furthest);
+                }
+                current
+            }
+            Term::Choice(ref particles) => particles
+                .iter()
+                .flat_map(|p| self.match_particle(p, names, starts, furthest))
+                .collect(),
+        };
+        if let Some(&max) = ends.iter().next_back() {
+            *furthest = (*furthest).max(max);
+        }
+        ends
+    }
+
+    fn term_matches(&self, term: &'s Term, name: Name) -> bool {
+        match *term {
+            Term::Any => true,
+            _ => self
+                .declaration(term)
+                .is_some_and(|decl| decl.name == name.1 && decl.namespace.as_deref() == name.0),
+        }
+    }
+
+    fn declaration(&self, term: &'s Term) -> Option<&'s ElementDecl> {
+        match *term {
+            Term::Element(ref decl) => Some(decl),
+            Term::Ref(ref name) => self.schema.elements.get(name),
+            _ => None,
+        }
+    }
+
+    /// Finds the declaration for the child element `name` in `particle`.
+    fn find(&self, particle: &'s Particle, name: Name) -> Option<&'s ElementDecl> {
+        match particle.term {
+            Term::Sequence(ref ps) | Term::Choice(ref ps) => {
+                ps.iter().filter_map(|p| self.find(p, name)).next()
+            }
+            Term::Any => None,
+            ref term => self
+                .declaration(term)
+                .filter(|_| self.term_matches(term, name)),
+        }
+    }
+
+    fn simple_value(&mut self, node: Node, value: &str, ty: &'s TypeRef) {
+        if let Err(message) = self.check_simple(value, ty) {
+            self.error(node, message);
+        }
+    }
+
+    fn check_simple(&self, value: &str, ty: &'s TypeRef) -> Result<(), String> {
+        match *ty {
+            TypeRef::Builtin(ref name) => check_builtin(name, value),
+            TypeRef::Named(ref name) => match self.schema.simple_types.get(name) {
+                Some(st) => self.check_restriction(value, st),
+                None => Err(format!("Unknown simple type '{}'.", name)),
+            },
+            TypeRef::Simple(ref st) => self.check_restriction(value, st),
+            TypeRef::Complex(_) => Err("Expected a simple type.".to_string()),
+        }
+    }
+
+    fn check_restriction(&self, value: &str, st: &'s SimpleType) -> Result<(), String> {
+        self.check_simple(value, &st.base)?;
+        let value = match st.white_space.unwrap_or_else(|| self.white_space(&st.base)) {
+            WhiteSpace::Preserve => value.to_string(),
+            WhiteSpace::Replace => value.replace(['\t', '\r', '\n'], " "),
+            WhiteSpace::Collapse => normalize_space(value),
+        };
+
+        if !st.patterns.is_empty() && !st.patterns.iter().any(|p| p.is_match(&value)) {
+            let patterns: Vec<&str> = st.patterns.iter().map(|p| p.as_str()).collect();
+            return Err(format!(
+                "Value '{}' does not match the pattern '{}'.",
+                value,
+                patterns.join("|")
+            ));
+        }
+        if !st.enumeration.is_empty() && !st.enumeration.contains(&value) {
+            return Err(format!(
+                "Value '{}' is not one of '{}'.",
+                value,
+                st.enumeration.join("', '")
+            ));
+        }
+
+        let bounds = [
+            (&st.min_inclusive, "at least", Ordering::Less, true),
+            (&st.max_inclusive, "at most", Ordering::Greater, true),
+            (&st.min_exclusive, "greater than", Ordering::Less, false),
+            (&st.max_exclusive, "less than", Ordering::Greater, false),
+        ];
+        for &(bound, description, invalid, inclusive) in &bounds {
+            if let Some(ref bound) = *bound {
+                let ordering = compare(&value, bound);
+                if ordering == Some(invalid) || (!inclusive && ordering == Some(Ordering::Equal)) {
+                    return Err(format!(
+                        "Value '{}' must be {} {}.",
+                        value, description, bound
+                    ));
+                }
+            }
+        }
+
+        let length = value.chars().count();
+        if st.length.is_some_and(|l| length != l)
+            || st.min_length.is_some_and(|l| length < l)
+            || st.max_length.is_some_and(|l| length > l)
+        {
+            return Err(format!("Value '{}' has an invalid length.", value));
+        }
+
+        if st.total_digits.is_some() || st.fraction_digits.is_some() {
+            let (total, fraction) = digits(&value)
+                .ok_or_else(|| format!("Value '{}' is not a valid decimal.", value))?;
+            if let Some(max) = st.total_digits.filter(|&max| total > max) {
+                return Err(format!("Value '{}' has more than {} digits.", value, max));
+            }
+            if let Some(max) = st.fraction_digits.filter(|&max| fraction > max) {
+                return Err(format!(
+                    "Value '{}' has more than {} fraction digits.",
+                    value, max
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The whitespace normalization of values of the type `ty`.
+    fn white_space(&self, ty: &'s TypeRef) -> WhiteSpace {
+        let st = match *ty {
+            TypeRef::Builtin(ref name) => {
+                return match name.as_str() {
+                    "string" | "anySimpleType" => WhiteSpace::Preserve,
+                    "normalizedString" => WhiteSpace::Replace,
+                    _ => WhiteSpace::Collapse,
+                }
+            }
+            TypeRef::Named(ref name) => match self.schema.simple_types.get(name) {
+                Some(st) => st,
+                None => return WhiteSpace::Preserve,
+            },
+            TypeRef::Simple(ref st) => st,
+            TypeRef::Complex(_) => return WhiteSpace::Preserve,
+        };
+        st.white_space.unwrap_or_else(|| self.white_space(&st.base))
+    }
+}
+
+fn text(e: Element) -> String {
+    e.children()
+        .into_iter()
+        .filter_map(|c| c.text())
+        .map(|t| t.text())
+        .collect()
+}
+
+/// Compares numerically if both values are numbers, lexically otherwise.
+fn compare(a: &str, b: &str) -> Option<Ordering> {
+    match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b),
+        _ => Some(a.trim().cmp(b.trim())),
+    }
+}
+
+/// The number of significant digits and fraction digits of a decimal.
+fn digits(value: &str) -> Option<(usize, usize)> {
+    let unsigned = value.trim_start_matches(['+', '-']);
+    let (integer, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    if !builtin_pattern("decimal").unwrap().is_match(value) {
+        return None;
+    }
+    let integer = integer.trim_start_matches('0');
+    let fraction = fraction.trim_end_matches('0');
+    Some((integer.len() + fraction.len(), fraction.len()))
+}
+
+/// The supported built-in simple types and the patterns of their lexical
+/// spaces, if they are checked.
+const BUILTINS: &[(&str, Option<&str>)] = &[
+    ("anySimpleType", None),
+    ("string", None),
+    ("normalizedString", None),
+    ("token", None),
+    ("anyURI", None),
+    ("boolean", Some("true|false|1|0")),
+    ("decimal", Some(r"[+\-]?(\d+(\.\d*)?|\.\d+)")),
+    ("integer", Some(r"[+\-]?\d+")),
+    ("nonNegativeInteger", Some(r"\+?\d+|-0+")),
+    ("positiveInteger", Some(r"\+?0*[1-9]\d*")),
+    ("nonPositiveInteger", Some(r"-\d+|\+?0+")),
+    ("negativeInteger", Some(r"-0*[1-9]\d*")),
+    ("long", Some(r"[+\-]?\d+")),
+    ("int", Some(r"[+\-]?\d+")),
+    ("short", Some(r"[+\-]?\d+")),
+    ("byte", Some(r"[+\-]?\d+")),
+    ("unsignedLong", Some(r"[+\-]?\d+")),
+    ("unsignedInt", Some(r"[+\-]?\d+")),
+    ("unsignedShort", Some(r"[+\-]?\d+")),
+    ("unsignedByte", Some(r"[+\-]?\d+")),
+    (
+        "float",
+        Some(r"[+\-]?(\d+(\.\d*)?|\.\d+)([eE][+\-]?\d+)?|-?INF|NaN"),
+    ),
+    (
+        "double",
+        Some(r"[+\-]?(\d+(\.\d*)?|\.\d+)([eE][+\-]?\d+)?|-?INF|NaN"),
+    ),
+    ("date", Some(r"-?\d{4,}-\d\d-\d\d(Z|[+\-]\d\d:\d\d)?")),
+    ("time", Some(r"\d\d:\d\d:\d\d(\.\d+)?(Z|[+\-]\d\d:\d\d)?")),
+    (
+        "dateTime",
+        Some(r"-?\d{4,}-\d\d-\d\dT\d\d:\d\d:\d\d(\.\d+)?(Z|[+\-]\d\d:\d\d)?"),
+    ),
+    ("gYear", Some(r"-?\d{4,}(Z|[+\-]\d\d:\d\d)?")),
+    ("gYearMonth", Some(r"-?\d{4,}-\d\d(Z|[+\-]\d\d:\d\d)?")),
+    ("gMonth", Some(r"--\d\d(Z|[+\-]\d\d:\d\d)?")),
+    ("gMonthDay", Some(r"--\d\d-\d\d(Z|[+\-]\d\d:\d\d)?")),
+    ("gDay", Some(r"---\d\d(Z|[+\-]\d\d:\d\d)?")),
+    (
+        "duration",
+        Some(r"-?P(\d+Y)?(\d+M)?(\d+D)?(T(\d+H)?(\d+M)?(\d+(\.\d+)?S)?)?"),
+    ),
+    ("hexBinary", Some(r"([0-9a-fA-F]{2})*")),
+    ("base64Binary", Some(r"[A-Za-z0-9+/= ]*")),
+    ("language", Some(r"[a-zA-Z]{1,8}(-[a-zA-Z0-9]{1,8})*")),
+    ("Name", Some(r"[\i]\c*")),
+    ("QName", Some(r"[\i]\c*")),
+    ("NMTOKEN", Some(r"\c+")),
+    ("NCName", Some(r"[\i]\c*")),
+    ("ID", Some(r"[\i]\c*")),
+    ("IDREF", Some(r"[\i]\c*")),
+    ("ENTITY", Some(r"[\i]\c*")),
+];
+
+/// The compiled pattern of the built-in type `name`, compiled once.
+fn builtin_pattern(name: &str) -> Option<&'static Pattern> {
+    static PATTERNS: OnceLock<BTreeMap<&str, Pattern>> = OnceLock::new();
+    PATTERNS
+        .get_or_init(|| {
+            BUILTINS
+                .iter()
+                .filter_map(|&(name, pattern)| pattern.map(|p| (name, Pattern::new(p).unwrap())))
+                .collect()
+        })
+        .get(name)
+}
+
+fn check_builtin(name: &str, value: &str) -> Result<(), String> {
+    let trimmed = value.trim();
+    let invalid = || format!("Value '{}' is not a valid {}.", trimmed, name);
+    if let Some(pattern) = builtin_pattern(name) {
+        if !pattern.is_match(trimmed) {
+            return Err(invalid());
+        }
+    }
+    let integer_range = |min: i128, max: i128| match trimmed.trim_start_matches('+').parse::<i128>()
+    {
+        Ok(i) if i >= min && i <= max => Ok(()),
+        _ => Err(invalid()),
+    };
+    match name {
+        "long" => integer_range(i64::MIN as i128, i64::MAX as i128),
+        "int" => integer_range(i32::MIN as i128, i32::MAX as i128),
+        "short" => integer_range(i16::MIN as i128, i16::MAX as i128),
+        "byte" => integer_range(i8::MIN as i128, i8::MAX as i128),
+        "unsignedLong" => integer_range(0, u64::MAX as i128),
+        "unsignedInt" => integer_range(0, u32::MAX as i128),
+        "unsignedShort" => integer_range(0, u16::MAX as i128),
+        "unsignedByte" => integer_range(0, u8::MAX as i128),
+        "QName"
+            if trimmed.starts_with(':')
+                || trimmed.ends_with(':')
+                || trimmed.matches(':').count() > 1 =>
+        {
+            Err(invalid())
+        }
+        "NCName" | "ID" | "IDREF" | "ENTITY" if trimmed.contains(':') => Err(invalid()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r#"<?xml version="1.0"?>
+<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema" xmlns:o="urn:order"
+           targetNamespace="urn:order" elementFormDefault="qualified">
+  <xs:element name="order" type="o:Order"/>
+  <xs:element name="note" type="xs:string"/>
+  <xs:complexType name="Base">
+    <xs:sequence>
+      <xs:element name="customer" type="o:Code"/>
+    </xs:sequence>
+    <xs:attribute name="id" type="xs:int" use="required"/>
+  </xs:complexType>
+  <xs:complexType name="Order">
+    <xs:complexContent>
+      <xs:extension base="o:Base">
+        <xs:sequence>
+          <xs:element name="line" minOccurs="1" maxOccurs="unbounded">
+            <xs:complexType>
+              <xs:simpleContent>
+                <xs:extension base="xs:decimal">
+                  <xs:attribute name="unit" type="o:Unit"/>
+                </xs:extension>
+              </xs:simpleContent>
+            </xs:complexType>
+          </xs:element>
+          <xs:choice minOccurs="0">
+            <xs:element ref="o:note"/>
+            <xs:element name="priority">
+              <xs:simpleType>
+                <xs:restriction base="xs:integer">
+                  <xs:minInclusive value="1"/>
+                  <xs:maxExclusive value="4"/>
+                </xs:restriction>
+              </xs:simpleType>
+            </xs:element>
+          </xs:choice>
+        </xs:sequence>
+      </xs:extension>
+    </xs:complexContent>
+  </xs:complexType>
+  <xs:simpleType name="Code">
+    <xs:restriction base="xs:string">
+      <xs:pattern value="[A-Z]{3}-\d+"/>
+      <xs:maxLength value="8"/>
+    </xs:restriction>
+  </xs:simpleType>
+  <xs:simpleType name="Unit">
+    <xs:restriction base="xs:token">
+      <xs:enumeration value="kg"/>
+      <xs:enumeration value="pcs"/>
+    </xs:restriction>
+  </xs:simpleType>
+</xs:schema>"#;
+
+    fn validate(xml: &str) -> Vec<String> {
+        let schema = Schema::parse(SCHEMA).unwrap();
+        let reader = Reader::from_str(xml, None).unwrap();
+        schema
+            .validate(&reader)
+            .unwrap()
+            .iter()
+            .map(|e| e.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn valid_document() {
+        let errors = validate(
+            r#"<order xmlns="urn:order" id="7"><customer>ABC-12</customer><line unit=" kg ">1.5</line><line>2</line><priority>3</priority></order>"#,
+        );
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    #[test]
+    fn invalid_values() {
+        let errors = validate(
+            r#"<order xmlns="urn:order" id="x" extra="1"><customer>abc-1</customer><line unit="m">1.5.0</line><priority>4</priority></order>"#,
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "/order[1]/@id: Value 'x' is not a valid int.",
+                "/order[1]/@extra: Attribute is not declared.",
+                "/order[1]/customer[1]: Value 'abc-1' does not match the pattern '[A-Z]{3}-\\d+'.",
+                "/order[1]/line[1]/@unit: Value 'm' is not one of 'kg', 'pcs'.",
+                "/order[1]/line[1]: Value '1.5.0' is not a valid decimal.",
+                "/order[1]/priority[1]: Value '4' must be less than 4.",
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_structure() {
+        assert_eq!(
+            validate(r#"<order xmlns="urn:order"><customer>ABC-1</customer></order>"#),
+            vec![
+                "/order[1]: Missing required attribute 'id'.",
+                "/order[1]: Required child elements are missing.",
+            ]
+        );
+        assert_eq!(
+            validate(
+                r#"<order xmlns="urn:order" id="1"><customer>ABC-1</customer><line>1</line><note/><priority>1</priority></order>"#
+            ),
+            vec!["/order[1]/priority[1]: Element is not expected here."]
+        );
+        assert_eq!(
+            validate(r#"<order id="1"/>"#),
+            vec!["/order[1]: Element is not declared in the schema."]
+        );
+        assert_eq!(
+            validate(
+                r#"<order xmlns="urn:order" id="1">text<customer>ABC-1</customer><line><b/></line></order>"#
+            ),
+            vec![
+                "/order[1]: Text content is not allowed.",
+                "/order[1]/line[1]/b[1]: Element content is not allowed.",
+            ]
+        );
+    }
+
+    #[test]
+    fn unsupported_schema() {
+        let xsd = r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema"><xs:import namespace="urn:x"/></xs:schema>"#;
+        assert!(Schema::parse(xsd).is_err());
+        assert!(Schema::parse("<schema/>").is_err());
+
+        let xsd = r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema"><xs:element name="a" type="xs:bogusType"/></xs:schema>"#;
+        let err = Schema::parse(xsd).unwrap_err();
+        assert!(err.to_string().contains("xs:bogusType"), "{}", err);
+
+        let unknown = |body: &str| {
+            let xsd = format!(
+                r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">{}</xs:schema>"#,
+                body
+            );
+            Schema::parse(&xsd).unwrap_err().to_string()
+        };
+        assert!(unknown(r#"<xs:element name="a" type="T"/>"#).contains("Unknown type 'T'"));
+        assert!(unknown(
+            r#"<xs:element name="a"><xs:complexType><xs:attribute name="b" type="T"/></xs:complexType></xs:element>"#
+        )
+        .contains("Unknown simple type 'T'"));
+        assert!(unknown(
+            r#"<xs:complexType name="C"><xs:sequence><xs:element ref="e"/></xs:sequence></xs:complexType>"#
+        )
+        .contains("Unknown element 'e'"));
+        assert!(unknown(
+            r#"<xs:simpleType name="A"><xs:restriction base="B"/></xs:simpleType><xs:simpleType name="B"><xs:restriction base="A"/></xs:simpleType>"#
+        )
+        .contains("derived from itself"));
+    }
+
+    #[test]
+    fn digit_and_whitespace_facets() {
+        let xsd = r#"<xs:schema xmlns:xs="http://www.w3.org/2001/XMLSchema">
+  <xs:element name="r">
+    <xs:complexType>
+      <xs:attribute name="amount">
+        <xs:simpleType>
+          <xs:restriction base="xs:decimal">
+            <xs:totalDigits value="4"/>
+            <xs:fractionDigits value="1"/>
+          </xs:restriction>
+        </xs:simpleType>
+      </xs:attribute>
+      <xs:attribute name="count">
+        <xs:simpleType>
+          <xs:restriction base="xs:decimal">
+            <xs:totalDigits value="2"/>
+            <xs:fractionDigits value="0"/>
+          </xs:restriction>
+        </xs:simpleType>
+      </xs:attribute>
+      <xs:attribute name="code">
+        <xs:simpleType>
+          <xs:restriction base="xs:string">
+            <xs:whiteSpace value="collapse"/>
+            <xs:length value="3"/>
+          </xs:restriction>
+        </xs:simpleType>
+      </xs:attribute>
+    </xs:complexType>
+  </xs:element>
+</xs:schema>"#;
+        let schema = Schema::parse(xsd).unwrap();
+        let check = |xml: &str| {
+            let reader = Reader::from_str(xml, None).unwrap();
+            schema
+                .validate(&reader)
+                .unwrap()
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert!(check(r#"<r amount="012.30" count="12" code=" a b "/>"#).is_empty());
+        assert_eq!(
+            check(r#"<r amount="123.45" count="12345.678" code="abcd"/>"#),
+            vec![
+                "/r[1]/@amount: Value '123.45' has more than 4 digits.",
+                "/r[1]/@count: Value '12345.678' has more than 2 digits.",
+                "/r[1]/@code: Value 'abcd' has an invalid length.",
+            ]
+        );
+        assert_eq!(
+            check(r#"<r amount="1.25" count="1.5"/>"#),
+            vec![
+                "/r[1]/@amount: Value '1.25' has more than 1 fraction digits.",
+                "/r[1]/@count: Value '1.5' has more than 0 fraction digits.",
+            ]
+        );
+    }
+}